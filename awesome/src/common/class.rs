@@ -129,6 +129,13 @@ impl<'lua, S: ObjectStateType> Class<'lua, S> {
         self.class.borrow::<ClassState<S>>()
             .map(|class| class.checker.clone())
     }
+
+    /// Get the signals that are emitted for every object of this class.
+    pub fn signals(&self) -> rlua::Result<Table<'lua>> {
+        let table = self.class.get_user_value::<Table>()?;
+        let meta = table.get_metatable().expect("Class had no meta table!");
+        meta.get::<_, Table>("signals")
+    }
 }
 
 fn set_index_miss_handler<'lua>(_: &'lua Lua,
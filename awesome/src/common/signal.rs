@@ -7,6 +7,7 @@
 use rlua::{self, Function, Lua, Table, ToLua, ToLuaMulti, Value};
 
 use ::GLOBAL_SIGNALS;
+use common::class::Class;
 use common::object::{Object, ObjectStateType};
 
 /// Connects functions to a signal. Creates a new entry in the table if it
@@ -90,6 +91,30 @@ fn emit_signals<'lua, A>(_: &'lua Lua,
     Ok(())
 }
 
+/// Connects functions to a signal of the class. Class signals are emitted
+/// for every object of that class.
+pub fn connect_class_signal<S: ObjectStateType>(lua: &Lua,
+                                                class: Class<S>,
+                                                name: String,
+                                                funcs: &[Function])
+                                                -> rlua::Result<()> {
+    let signals = class.signals()?;
+    connect_signals(lua, signals, name, funcs)
+}
+
+/// Evaluate the functions associated with a signal of the class.
+pub fn emit_class_signal<'lua, A, S>(lua: &'lua Lua,
+                                     class: Class<'lua, S>,
+                                     name: String,
+                                     args: A)
+                                     -> rlua::Result<()>
+    where A: ToLuaMulti<'lua> + Clone,
+          S: ObjectStateType
+{
+    let signals = class.signals()?;
+    emit_signals(lua, signals, name, args)
+}
+
 /// Connect the function to the named signal in the global signal list.
 pub fn global_connect_signal<'lua>(lua: &'lua Lua,
                                    (name, func): (String, Function<'lua>))
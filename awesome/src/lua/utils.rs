@@ -14,7 +14,6 @@ const MOD_NAMES: [&str; 8] = ["Shift", "Caps", "Control", "Alt", "Mod2", "Mod3",
 #[allow(dead_code)]
 const MOUSE_EVENTS: [u32; 5] = [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA];
 
-#[allow(dead_code)]
 const MOD_TYPES: [(KeyboardModifier, Key); 7] = [
     (KeyboardModifier::WLR_MODIFIER_SHIFT, KEY_Shift_L),
     (KeyboardModifier::WLR_MODIFIER_CAPS,  KEY_Caps_Lock),
@@ -44,7 +43,6 @@ pub fn mods_to_lua<'lua>(lua: &'lua Lua, mods: &[Key]) -> rlua::Result<Table<'lu
 }
 
 /// Convert a single number to a modifier list.
-#[allow(dead_code)]
 pub fn num_to_mods(modifiers: KeyboardModifier) -> Vec<Key> {
     let mut res = vec![];
     for (mod_km, mod_k) in MOD_TYPES.iter() {
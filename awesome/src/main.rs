@@ -74,11 +74,12 @@ use rlua::{LightUserData, Lua, Table};
 use log::Level;
 use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
 use xcb::{xkb, Connection};
-use wayland_client::{Display, GlobalManager, EventQueue, GlobalError};
+use wayland_client::{Display, GlobalEvent, GlobalManager, EventQueue, GlobalError};
 use wayland_client::protocol::{wl_compositor, wl_shm, wl_output, wl_display::RequestsTrait};
 use wayland_client::sys::client::wl_display;
 
 use self::lua::{LUA, NEXT_LUA};
+use wayland_protocols::window_management::zway_cooler_window_manager_v1;
 use wayland_protocols::xdg_shell::xdg_wm_base;
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -164,25 +165,42 @@ fn init_wayland() {
             exit(1);
         }
     };
+    let mut bind_globals = global_filter!(
+        [
+            wl_output::WlOutput,
+            wayland_obj::WL_OUTPUT_VERSION,
+            wayland_obj::Output::new
+        ],
+        [
+            wl_compositor::WlCompositor,
+            wayland_obj::WL_COMPOSITOR_VERSION,
+            wayland_obj::wl_compositor_init
+        ],
+        [
+            wl_shm::WlShm,
+            wayland_obj::WL_SHM_VERSION,
+            wayland_obj::wl_shm_init
+        ],
+        [
+            zway_cooler_window_manager_v1::ZwayCoolerWindowManagerV1,
+            wayland_obj::WINDOW_MANAGER_VERSION,
+            wayland_obj::window_manager_init
+        ]
+    );
     let globals = GlobalManager::new_with_cb(
         display.get_registry().unwrap(),
-        global_filter!(
-            [
-                wl_output::WlOutput,
-                wayland_obj::WL_OUTPUT_VERSION,
-                wayland_obj::Output::new
-            ],
-            [
-                wl_compositor::WlCompositor,
-                wayland_obj::WL_COMPOSITOR_VERSION,
-                wayland_obj::wl_compositor_init
-            ],
-            [
-                wl_shm::WlShm,
-                wayland_obj::WL_SHM_VERSION,
-                wayland_obj::wl_shm_init
-            ]
-        ),
+        move |event, registry| {
+            match event {
+                GlobalEvent::New { id, ref interface, .. } if interface == "wl_output" => {
+                    wayland_obj::output_global_added(id)
+                },
+                GlobalEvent::Removed { id, ref interface } if interface == "wl_output" => {
+                    wayland_obj::output_global_removed(id)
+                },
+                _ => {}
+            }
+            bind_globals(event, registry)
+        }
     );
     event_queue.sync_roundtrip().unwrap();
     let xwm_base_proxy = match globals.instantiate_exact::
@@ -208,6 +226,7 @@ fn init_wayland() {
     event_queue.sync_roundtrip().unwrap();
     let mut wayland_state = WaylandState { display, event_queue };
     let display_ptr = wayland_state.display.c_ptr() as *mut wl_display;
+    wayland_obj::set_display(display_ptr);
     unsafe {
         #[link(name = "wayland_glib_interface", kind = "static")]
         extern "C" {
@@ -4,7 +4,7 @@
 
 use std::default::Default;
 
-use rlua::{self, Lua, Table, UserData, UserDataMethods, Value};
+use rlua::{self, Function, Lua, Table, UserData, UserDataMethods, Value};
 use wlroots::events::key_events::Key;
 use xcb::ffi::xproto::xcb_button_t;
 
@@ -36,7 +36,11 @@ impl UserData for ButtonState {
 impl<'lua> Button<'lua> {
     fn new(lua: &'lua Lua, args: Table) -> rlua::Result<Button<'lua>> {
         let class = class::class_setup(lua, "button")?;
+        let meta = lua.create_table()?;
+        meta.set("connect_signal", lua.create_function(connect_signal)?)?;
+        meta.set("disconnect_signal", lua.create_function(disconnect_signal)?)?;
         Ok(Button::allocate(lua, class)?.handle_constructor_argument(args)?
+                                        .add_to_meta(meta)?
                                         .build())
     }
 
@@ -110,3 +114,16 @@ fn get_modifiers<'lua>(lua: &'lua Lua, button: Button<'lua>) -> rlua::Result<Val
     use lua::mods_to_lua;
     mods_to_lua(lua, &button.modifiers()?).map(Value::Table)
 }
+
+/// Connect a function to a signal of the button, e.g. "press".
+fn connect_signal<'lua>(lua: &'lua Lua,
+                        (button, name, func): (Button<'lua>, String, Function<'lua>))
+                        -> rlua::Result<()> {
+    signal::connect_signal(lua, button, name, &[func])
+}
+
+fn disconnect_signal<'lua>(lua: &'lua Lua,
+                           (button, name): (Button<'lua>, String))
+                           -> rlua::Result<()> {
+    signal::disconnect_signal(lua, button, name)
+}
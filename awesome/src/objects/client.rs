@@ -4,24 +4,42 @@
 use std::default::Default;
 use std::hash::{Hash, Hasher};
 
-use rlua::{self, Lua, Table, UserData};
+use glib::translate::ToGlibPtr;
+use rlua::{self, prelude::LuaInteger, Function, LightUserData, Lua, Table, ToLua, UserData,
+           UserDataMethods, Value};
+use wlroots::{Area, Origin, Size};
 
 use common::{class::{self, Class, ClassBuilder},
-             object::Object};
+             object::{self, Object, ObjectBuilder},
+             property::Property,
+             signal};
+use wayland_obj::{Window, WindowChanges};
 
-#[derive(Clone, Debug, Hash)]
+pub const CLIENTS_HANDLE: &'static str = "__clients";
+
+#[derive(Clone, Debug, Default)]
 pub struct ClientState {
-    // TODO Fill in
-    pub dummy: i32
+    // The window in the compositor, unset once it has been unmanaged.
+    window: Option<Window>,
+    // The title of the client
+    name: String,
+    // The application id of the client
+    class: String,
+    // Client geometry in the output layout
+    geometry: Area,
+    // Does the client have keyboard focus
+    active: bool,
+    // The width of the border Way Cooler draws around the client
+    border_width: u32,
+    // The colour of the border, as "#rrggbb" or "#rrggbbaa"
+    border_color: String,
+    // Whether Way Cooler draws the decorations, instead of the client
+    server_decorations: bool
 }
 
-pub type Client<'lua> = Object<'lua, ClientState>;
+unsafe impl Send for ClientState {}
 
-impl Default for ClientState {
-    fn default() -> Self {
-        ClientState { dummy: 0 }
-    }
-}
+pub type Client<'lua> = Object<'lua, ClientState>;
 
 impl<'lua> PartialEq for Client<'lua> {
     fn eq(&self, other: &Self) -> bool {
@@ -33,24 +51,143 @@ impl<'lua> Eq for Client<'lua> {}
 
 impl<'lua> Hash for Client<'lua> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.state().unwrap().hash(state);
+        (&*self.state().unwrap() as *const ClientState).hash(state);
     }
 }
 
-/* This is currently unused.
- * TODO: Figure out if this will be needed later. */
-impl <'lua> Client<'lua> {
+impl<'lua> Client<'lua> {
     pub fn new(lua: &'lua Lua, args: Table) -> rlua::Result<Client<'lua>> {
         let class = class::class_setup(lua, "client")?;
-        Ok(Client::allocate(lua, class)?.handle_constructor_argument(args)?
-                                        .build())
+        Ok(object_setup(lua, Client::allocate(lua, class)?)?.handle_constructor_argument(args)?
+                                                             .build())
+    }
+
+    /// Create a client for a window that is mapped by Way Cooler.
+    fn from_window(lua: &'lua Lua, window: Window) -> rlua::Result<Client<'lua>> {
+        let mut client = Client::new(lua, lua.create_table()?)?;
+        client.state_mut()?.window = Some(window);
+        Ok(client)
+    }
+
+    pub fn window(&self) -> rlua::Result<Option<Window>> {
+        Ok(self.state()?.window.clone())
+    }
+
+    pub fn get_geometry(&self) -> rlua::Result<Area> {
+        Ok(self.state()?.geometry)
+    }
+}
+
+impl UserData for ClientState {
+    fn add_methods(methods: &mut UserDataMethods<Self>) {
+        object::default_add_methods(methods);
+    }
+}
+
+/// Start managing a window that was just mapped by Way Cooler.
+pub fn manage(lua: &Lua, window: Window, changes: WindowChanges) -> rlua::Result<()> {
+    let mut client = Client::from_window(lua, window)?;
+    let mut clients = lua.named_registry_value::<Vec<Client>>(CLIENTS_HANDLE)?;
+    clients.push(client.clone());
+    lua.set_named_registry_value(CLIENTS_HANDLE, clients.to_lua(lua)?)?;
+    let active = changes.activated.unwrap_or(false);
+    apply_changes(lua, &mut client, changes, false)?;
+    emit_signal(lua, client.clone(), "manage")?;
+    if active {
+        emit_signal(lua, client, "focus")?;
+    }
+    Ok(())
+}
+
+/// Update the client of a window with the changes sent by Way Cooler.
+pub fn update(lua: &Lua, window: Window, changes: WindowChanges) -> rlua::Result<()> {
+    let clients = lua.named_registry_value::<Vec<Client>>(CLIENTS_HANDLE)?;
+    for mut client in clients {
+        if client.window()?.as_ref() == Some(&window) {
+            return apply_changes(lua, &mut client, changes, true)
+        }
+    }
+    warn!("Got changes for a window that is not managed: {:?}", window);
+    Ok(())
+}
+
+/// Stop managing a window that Way Cooler closed.
+pub fn unmanage(lua: &Lua, window: Window) -> rlua::Result<()> {
+    let clients = lua.named_registry_value::<Vec<Client>>(CLIENTS_HANDLE)?;
+    let mut remaining = Vec::with_capacity(clients.len());
+    let mut unmanaged = Vec::new();
+    for client in clients {
+        if client.window()?.as_ref() == Some(&window) {
+            unmanaged.push(client);
+        } else {
+            remaining.push(client);
+        }
     }
+    lua.set_named_registry_value(CLIENTS_HANDLE, remaining.to_lua(lua)?)?;
+    for mut client in unmanaged {
+        let was_active = {
+            let mut state = client.state_mut()?;
+            state.window = None;
+            state.active
+        };
+        if was_active {
+            client.state_mut()?.active = false;
+            emit_signal(lua, client.clone(), "unfocus")?;
+        }
+        emit_signal(lua, client, "unmanage")?;
+    }
+    Ok(())
+}
+
+fn apply_changes<'lua>(lua: &'lua Lua,
+                       client: &mut Client<'lua>,
+                       changes: WindowChanges,
+                       notify: bool)
+                       -> rlua::Result<()> {
+    let WindowChanges { title,
+                        app_id,
+                        geometry,
+                        activated } = changes;
+    if let Some(title) = title {
+        client.state_mut()?.name = title;
+        if notify {
+            emit_signal(lua, client.clone(), "property::name")?;
+        }
+    }
+    if let Some(app_id) = app_id {
+        client.state_mut()?.class = app_id;
+        if notify {
+            emit_signal(lua, client.clone(), "property::class")?;
+        }
+    }
+    if let Some(geometry) = geometry {
+        client.state_mut()?.geometry = geometry;
+        if notify {
+            emit_signal(lua, client.clone(), "property::geometry")?;
+        }
+    }
+    if let Some(activated) = activated {
+        client.state_mut()?.active = activated;
+        if notify {
+            emit_signal(lua, client.clone(), if activated { "focus" } else { "unfocus" })?;
+        }
+    }
+    Ok(())
 }
 
-impl UserData for ClientState {}
+/// Emit the signal on the client, then on the client class.
+///
+/// This is how Awesome emits signals for clients.
+fn emit_signal<'lua>(lua: &'lua Lua, client: Client<'lua>, name: &str) -> rlua::Result<()> {
+    signal::emit_object_signal(lua, client.clone(), name.into(), ())?;
+    let class = class::class_setup::<ClientState>(lua, "client")?;
+    signal::emit_class_signal(lua, class, name.into(), client)
+}
 
 pub fn init(lua: &Lua) -> rlua::Result<Class<ClientState>> {
-    method_setup(lua, Class::builder(lua, "client", None)?)?
+    let clients: Vec<Client> = Vec::new();
+    lua.set_named_registry_value(CLIENTS_HANDLE, clients.to_lua(lua)?)?;
+    property_setup(lua, method_setup(lua, Class::builder(lua, "client", None)?)?)?
         .save_class("client")?
         .build()
 }
@@ -58,14 +195,310 @@ pub fn init(lua: &Lua) -> rlua::Result<Class<ClientState>> {
 fn method_setup<'lua>(lua: &'lua Lua,
                       builder: ClassBuilder<'lua, ClientState>)
                       -> rlua::Result<ClassBuilder<'lua, ClientState>> {
-    // TODO Do properly
-    use super::dummy;
-    builder.method("connect_signal".into(), lua.create_function(dummy)?)?
+    builder.method("connect_signal".into(), lua.create_function(connect_signal)?)?
            .method("__call".into(),
-               lua.create_function(|lua, args: Table| Client::new(lua, args))?)?
-           .method("get".into(), lua.create_function(dummy_table)?)
+                   lua.create_function(|lua, args: Table| Client::new(lua, args))?)?
+           .method("get".into(), lua.create_function(get_clients)?)
+}
+
+fn property_setup<'lua>(lua: &'lua Lua,
+                        builder: ClassBuilder<'lua, ClientState>)
+                        -> rlua::Result<ClassBuilder<'lua, ClientState>> {
+    builder.property(Property::new("name".into(),
+                                   None,
+                                   Some(lua.create_function(get_name)?),
+                                   None))?
+           .property(Property::new("class".into(),
+                                   None,
+                                   Some(lua.create_function(get_class)?),
+                                   None))?
+           .property(Property::new("active".into(),
+                                   None,
+                                   Some(lua.create_function(get_active)?),
+                                   None))?
+           .property(Property::new("border_width".into(),
+                                   None,
+                                   Some(lua.create_function(get_border_width)?),
+                                   Some(lua.create_function(set_border_width)?)))?
+           .property(Property::new("border_color".into(),
+                                   None,
+                                   Some(lua.create_function(get_border_color)?),
+                                   Some(lua.create_function(set_border_color)?)))?
+           .property(Property::new("server_decorations".into(),
+                                   None,
+                                   Some(lua.create_function(get_server_decorations)?),
+                                   Some(lua.create_function(set_server_decorations)?)))
+}
+
+fn object_setup<'lua>(lua: &'lua Lua,
+                      builder: ObjectBuilder<'lua, ClientState>)
+                      -> rlua::Result<ObjectBuilder<'lua, ClientState>> {
+    let table = lua.create_table()?;
+    table.set("geometry", lua.create_function(client_geometry)?)?;
+    table.set("kill", lua.create_function(client_kill)?)?;
+    table.set("raise", lua.create_function(client_raise)?)?;
+    table.set("capture", lua.create_function(client_capture)?)?;
+    builder.add_to_meta(table)
 }
 
-fn dummy_table<'lua>(lua: &'lua Lua, _: rlua::Value) -> rlua::Result<Table<'lua>> {
-    Ok(lua.create_table()?)
+fn connect_signal<'lua>(lua: &'lua Lua,
+                        (name, func): (String, Function<'lua>))
+                        -> rlua::Result<()> {
+    let class = class::class_setup::<ClientState>(lua, "client")?;
+    signal::connect_class_signal(lua, class, name, &[func])
+}
+
+fn get_clients<'lua>(lua: &'lua Lua, _: Value<'lua>) -> rlua::Result<Vec<Client<'lua>>> {
+    lua.named_registry_value::<Vec<Client>>(CLIENTS_HANDLE)
+}
+
+fn get_name<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<String> {
+    Ok(client.state()?.name.clone())
+}
+
+fn get_class<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<String> {
+    Ok(client.state()?.class.clone())
+}
+
+fn get_active<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<bool> {
+    Ok(client.state()?.active)
+}
+
+fn get_border_width<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<LuaInteger> {
+    Ok(client.state()?.border_width as LuaInteger)
+}
+
+fn set_border_width<'lua>(lua: &'lua Lua,
+                          (mut client, width): (Client<'lua>, LuaInteger))
+                          -> rlua::Result<()> {
+    if width < 0 {
+        return Err(rlua::Error::RuntimeError(format!("Invalid border width {}", width)))
+    }
+    client.state_mut()?.border_width = width as u32;
+    update_border(&client)?;
+    emit_signal(lua, client, "property::border_width")
+}
+
+fn get_border_color<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<String> {
+    Ok(client.state()?.border_color.clone())
+}
+
+fn set_border_color<'lua>(lua: &'lua Lua,
+                          (mut client, color): (Client<'lua>, String))
+                          -> rlua::Result<()> {
+    if parse_color(&color).is_none() {
+        return Err(rlua::Error::RuntimeError(format!("Invalid border color \"{}\"", color)))
+    }
+    client.state_mut()?.border_color = color;
+    update_border(&client)?;
+    emit_signal(lua, client, "property::border_color")
+}
+
+fn get_server_decorations<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<bool> {
+    Ok(client.state()?.server_decorations)
+}
+
+/// Decide whether Way Cooler or the client draws the decorations.
+///
+/// The client draws them unless this is set, e.g. by a rule.
+fn set_server_decorations<'lua>(lua: &'lua Lua,
+                                (mut client, server): (Client<'lua>, bool))
+                                -> rlua::Result<()> {
+    client.state_mut()?.server_decorations = server;
+    if let Some(window) = client.window()? {
+        window.set_server_decorations(server);
+    }
+    emit_signal(lua, client, "property::server_decorations")
+}
+
+/// Send the border of the client to Way Cooler.
+fn update_border(client: &Client) -> rlua::Result<()> {
+    let (width, color) = {
+        let state = client.state()?;
+        (state.border_width, parse_color(&state.border_color).unwrap_or(0x000000ff))
+    };
+    if let Some(window) = client.window()? {
+        window.set_border(width, color);
+    }
+    Ok(())
+}
+
+/// Parse a colour in the "#rrggbb" or "#rrggbbaa" format into 0xRRGGBBAA.
+fn parse_color(color: &str) -> Option<u32> {
+    if !color.starts_with('#') {
+        return None
+    }
+    let hex = &color[1..];
+    if !hex.chars().all(|c| c.is_digit(16)) {
+        return None
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(value << 8 | 0xff),
+        8 => Some(value),
+        _ => None
+    }
+}
+
+fn client_geometry<'lua>(lua: &'lua Lua,
+                         (client, geometry): (Client<'lua>, Option<Table<'lua>>))
+                         -> rlua::Result<Table<'lua>> {
+    let mut new_geo = client.get_geometry()?;
+    if let Some(geometry) = geometry {
+        if let Ok(x) = geometry.get::<_, i32>("x") {
+            new_geo.origin.x = x;
+        }
+        if let Ok(y) = geometry.get::<_, i32>("y") {
+            new_geo.origin.y = y;
+        }
+        if let Ok(width) = geometry.get::<_, i32>("width") {
+            new_geo.size.width = width;
+        }
+        if let Ok(height) = geometry.get::<_, i32>("height") {
+            new_geo.size.height = height;
+        }
+        if new_geo.size.width > 0 && new_geo.size.height > 0 {
+            if let Some(window) = client.window()? {
+                // The geometry is updated once Way Cooler tells us the
+                // client accepted it.
+                window.set_geometry(new_geo);
+            }
+        }
+    }
+    let Size { width, height } = new_geo.size;
+    let Origin { x, y } = new_geo.origin;
+    let res = lua.create_table()?;
+    res.set("x", x)?;
+    res.set("y", y)?;
+    res.set("height", height)?;
+    res.set("width", width)?;
+    Ok(res)
+}
+
+fn client_kill<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<()> {
+    if let Some(window) = client.window()? {
+        window.close();
+    }
+    Ok(())
+}
+
+fn client_raise<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<()> {
+    if let Some(window) = client.window()? {
+        window.focus();
+    }
+    Ok(())
+}
+
+/// Render the window of the client, on its own and at its size, into a
+/// cairo image surface.
+///
+/// Returns nil if the client has no window or it could not be rendered.
+fn client_capture<'lua>(_: &'lua Lua, client: Client<'lua>) -> rlua::Result<Value<'lua>> {
+    let size = client.get_geometry()?.size;
+    let image = match client.window()? {
+        Some(window) => window.capture(size),
+        None => None
+    };
+    Ok(match image {
+        Some(image) => {
+            let ptr = image.to_glib_none().0;
+            // Lua gets its own reference to the image, which it manages via
+            // LGI.
+            unsafe {
+                ::cairo_sys::cairo_surface_reference(ptr);
+            }
+            Value::LightUserData(LightUserData(ptr as _))
+        }
+        None => Value::Nil
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::client::{self, Client};
+    use rlua::{self, Lua};
+
+    #[test]
+    fn client_name_empty() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval(
+                 r#"
+local c = client{}
+assert(c.name == "")
+assert(c.active == false)
+"#,
+                 None
+        )
+    }
+
+    #[test]
+    fn client_class_signal() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval::<()>(r#"
+            managed = nil
+            client.connect_signal("manage", function(c) managed = c end)
+        "#, None)?;
+        let c = Client::new(&lua, lua.create_table()?)?;
+        lua.globals().set("c", c.clone())?;
+        client::emit_signal(&lua, c, "manage")?;
+        lua.eval(r#"
+            assert(managed == c, "Class signal was not emitted with the client")
+        "#, None)
+    }
+
+    #[test]
+    fn client_border() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval(r#"
+            local c = client{}
+            assert(c.border_width == 0)
+            c.border_width = 2
+            c.border_color = "#ff0000"
+            assert(c.border_width == 2)
+            assert(c.border_color == "#ff0000")
+            assert(not pcall(function() c.border_color = "red" end))
+        "#, None)?;
+        assert_eq!(client::parse_color("#ff000080"), Some(0xff000080));
+        assert_eq!(client::parse_color("#00ff00"), Some(0x00ff00ff));
+        assert_eq!(client::parse_color("00ff00"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn client_capture_without_window() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval(r#"
+            local c = client{}
+            assert(c:capture() == nil, "Only windows from Way Cooler can be captured")
+        "#, None)
+    }
+
+    #[test]
+    fn client_server_decorations() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval(r#"
+            local c = client{}
+            assert(c.server_decorations == false, "Clients draw their own decorations")
+            c.server_decorations = true
+            assert(c.server_decorations == true)
+        "#, None)
+    }
+
+    #[test]
+    fn client_get_empty() -> rlua::Result<()> {
+        let lua = Lua::new();
+        client::init(&lua)?;
+        lua.eval(
+                 r#"
+client{}
+assert(#client.get() == 0, "Clients not from Way Cooler are not managed")
+"#,
+                 None
+        )
+    }
 }
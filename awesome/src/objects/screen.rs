@@ -146,6 +146,26 @@ pub fn add_screen(lua: &Lua, screen: Screen) -> rlua::Result<()> {
     Ok(())
 }
 
+/// Remove the screens that show an output that matches, marking them as no
+/// longer valid and emitting "removed" on them.
+pub fn remove_screens<F>(lua: &Lua, has_output: F) -> rlua::Result<()>
+    where F: Fn(&Output) -> bool
+{
+    let screens = lua.named_registry_value::<Vec<AnyUserData>>(SCREENS_HANDLE)?;
+    let (removed, kept): (Vec<_>, Vec<_>) = screens.into_iter().partition(|obj| {
+        let screen = Screen::cast(obj.clone().into()).unwrap();
+        let state = screen.state().expect("Could not get screen state");
+        state.outputs.iter().any(|output| has_output(output))
+    });
+    lua.set_named_registry_value(SCREENS_HANDLE, kept.to_lua(lua)?)?;
+    for obj in removed {
+        let mut screen = Screen::cast(obj.into())?;
+        screen.state_mut()?.valid = false;
+        emit_object_signal(lua, screen, "removed".into(), ())?;
+    }
+    Ok(())
+}
+
 /// Find a screen based on the output.
 pub fn get_screen(lua: &Lua, output: Output) -> rlua::Result<Screen> {
     lua.named_registry_value::<Vec<AnyUserData>>(SCREENS_HANDLE)?
@@ -2,12 +2,18 @@
 //! Awesome's equivalent of globalconf's properties are accessible via registry keys
 
 use cairo_sys::cairo_pattern_t;
-use rlua::{self, LightUserData, Lua, Table, ToLua, Value};
+use rlua::{self, AnyUserData, LightUserData, Lua, Table, ToLua, Value};
+use wlroots::{xkbcommon::xkb::keysyms::{KEY_Caps_Lock, KEY_Meta_L}, KeyboardModifier};
+use xcb::ffi::xproto::xcb_button_t;
 
-use objects::tag;
+use common::signal;
+use lua::num_to_mods;
+use objects::{button::Button, tag};
 
 /// Handle to the list of global key bindings
 pub const ROOT_KEYS_HANDLE: &'static str = "__ROOT_KEYS";
+/// Handle to the list of global mouse bindings
+pub const ROOT_BUTTONS_HANDLE: &'static str = "__ROOT_BUTTONS";
 
 pub fn init(lua: &Lua) -> rlua::Result<()> {
     // TODO Do properly
@@ -15,7 +21,7 @@ pub fn init(lua: &Lua) -> rlua::Result<()> {
 
     let root = lua.create_table()?;
     root.set("connect_signal", lua.create_function(dummy)?)?;
-    root.set("buttons", lua.create_function(dummy)?)?;
+    root.set("buttons", lua.create_function(root_buttons)?)?;
     root.set("wallpaper", lua.create_function(wallpaper)?)?;
     root.set("tags", lua.create_function(tags)?)?;
     root.set("keys", lua.create_function(root_keys)?)?;
@@ -61,22 +67,39 @@ fn tags<'lua>(lua: &'lua Lua, _: ()) -> rlua::Result<Table<'lua>> {
 ///
 /// These bindings will be available when you press keys on the root window.
 fn root_keys<'lua>(lua: &'lua Lua, key_array: Value<'lua>) -> rlua::Result<Value<'lua>> {
-    match key_array {
-        // Set the global keys
-        Value::Table(key_array) => {
+    root_bindings(lua, ROOT_KEYS_HANDLE, "keys", key_array)
+}
+
+/// Get or set global mouse bindings.
+///
+/// These bindings will be available when you use the mouse on the root
+/// window, e.g. by scrolling over the wallpaper.
+fn root_buttons<'lua>(lua: &'lua Lua, button_array: Value<'lua>) -> rlua::Result<Value<'lua>> {
+    root_bindings(lua, ROOT_BUTTONS_HANDLE, "buttons", button_array)
+}
+
+/// Get or set the bindings stored in the registry under the handle.
+fn root_bindings<'lua>(lua: &'lua Lua,
+                       handle: &'static str,
+                       kind: &str,
+                       array: Value<'lua>)
+                       -> rlua::Result<Value<'lua>> {
+    match array {
+        // Set the global bindings
+        Value::Table(array) => {
             let copy = lua.create_table()?;
             // NOTE We make a deep clone so they can't modify references.
-            for entry in key_array.clone().pairs() {
+            for entry in array.clone().pairs() {
                 let (key, value) = entry?;
                 copy.set::<Value, Value>(key, value)?;
             }
-            lua.set_named_registry_value(ROOT_KEYS_HANDLE, copy)?;
-            Ok(Value::Table(key_array))
+            lua.set_named_registry_value(handle, copy)?;
+            Ok(Value::Table(array))
         }
-        // Get the global keys
+        // Get the global bindings
         Value::Nil => {
             let res = lua.create_table()?;
-            for entry in lua.named_registry_value::<Table>(ROOT_KEYS_HANDLE).or(lua.create_table())?.pairs() {
+            for entry in lua.named_registry_value::<Table>(handle).or(lua.create_table())?.pairs() {
                 let (key, value) = entry?;
                 res.set::<Value, Value>(key, value)?;
             }
@@ -84,17 +107,55 @@ fn root_keys<'lua>(lua: &'lua Lua, key_array: Value<'lua>) -> rlua::Result<Value
         }
         v => {
             Err(rlua::Error::RuntimeError(format!("Expected nil or array \
-                                                   of keys, got {:?}",
-                                                  v)))
+                                                   of {}, got {:?}",
+                                                  kind, v)))
         }
     }
 }
 
+/// Press and release the global mouse bindings for the button, with exactly
+/// the modifiers held down.
+///
+/// Caps Lock and Num Lock stay on once they are pressed, so they only count
+/// for the bindings that name them.
+pub fn emit_button<'lua>(lua: &'lua Lua,
+                         button: xcb_button_t,
+                         modifiers: KeyboardModifier)
+                         -> rlua::Result<()> {
+    let buttons = lua.named_registry_value::<Table>(ROOT_BUTTONS_HANDLE)
+                     .or(lua.create_table())?;
+    for entry in buttons.pairs::<Value, AnyUserData>() {
+        let binding = Button::cast(entry?.1)?;
+        let mut binding_modifiers = binding.modifiers()?;
+        binding_modifiers.sort();
+        let mut held = modifiers;
+        if !binding_modifiers.contains(&KEY_Caps_Lock) {
+            held.remove(KeyboardModifier::WLR_MODIFIER_CAPS);
+        }
+        if !binding_modifiers.contains(&KEY_Meta_L) {
+            held.remove(KeyboardModifier::WLR_MODIFIER_MOD2);
+        }
+        let mut held = num_to_mods(held);
+        held.sort();
+        let matches = match binding.button()? {
+            Value::Integer(number) => number == button as _,
+            _ => false
+        };
+        if !matches || binding_modifiers != held {
+            continue
+        }
+        signal::emit_object_signal(lua, binding.clone(), "press".into(), ())?;
+        signal::emit_object_signal(lua, binding, "release".into(), ())?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use ::root;
-    use ::objects::{tag, key};
+    use ::objects::{button, tag, key};
     use rlua::Lua;
+    use wlroots::KeyboardModifier;
 
     #[test]
     fn tags_print() {
@@ -200,6 +261,83 @@ local res = root.keys()
 assert(res[1] == first)
 assert(res[2] == second)
 assert(res[3] == nil)
+"#,
+                 None
+        ).unwrap()
+    }
+
+    #[test]
+    fn buttons() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        lua.eval(
+                 r#"
+assert(next(root.buttons()) == nil)
+
+local first = button{ button = 4 }
+local second = button{ button = 5 }
+local res = root.buttons({ first, second })
+assert(res[1] == first)
+assert(res[2] == second)
+assert(res[3] == nil)
+"#,
+                 None
+        ).unwrap()
+    }
+
+    #[test]
+    fn buttons_emit() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        lua.eval::<()>(
+                 r#"
+presses = {}
+local up = button{ button = 4 }
+up:connect_signal("press", function() table.insert(presses, "up") end)
+local super_up = button{ button = 4, modifiers = { "Mod4" } }
+super_up:connect_signal("press", function() table.insert(presses, "super_up") end)
+root.buttons({ up, super_up })
+"#,
+                 None
+        ).unwrap();
+        root::emit_button(&lua, 4, KeyboardModifier::empty()).unwrap();
+        root::emit_button(&lua, 4, KeyboardModifier::WLR_MODIFIER_LOGO).unwrap();
+        root::emit_button(&lua, 5, KeyboardModifier::empty()).unwrap();
+        lua.eval::<()>(
+                 r#"
+assert(presses[1] == "up")
+assert(presses[2] == "super_up")
+assert(presses[3] == nil)
+"#,
+                 None
+        ).unwrap()
+    }
+
+    #[test]
+    fn buttons_emit_with_locked_modifiers() {
+        let lua = Lua::new();
+        button::init(&lua).unwrap();
+        root::init(&lua).unwrap();
+        lua.eval::<()>(
+                 r#"
+presses = {}
+local up = button{ button = 4 }
+up:connect_signal("press", function() table.insert(presses, "up") end)
+local num_up = button{ button = 4, modifiers = { "Mod2" } }
+num_up:connect_signal("press", function() table.insert(presses, "num_up") end)
+root.buttons({ up, num_up })
+"#,
+                 None
+        ).unwrap();
+        let num_lock = KeyboardModifier::WLR_MODIFIER_MOD2;
+        root::emit_button(&lua, 4, num_lock | KeyboardModifier::WLR_MODIFIER_CAPS).unwrap();
+        lua.eval::<()>(
+                 r#"
+assert(presses[1] == "up")
+assert(presses[2] == "num_up")
+assert(presses[3] == nil)
 "#,
                  None
         ).unwrap()
@@ -1,13 +1,49 @@
 //! Wrappers around Wayland objects
 
+use std::cell::Cell;
+use std::os::raw::c_int;
+use std::ptr;
+
+use wayland_client::sys::client::wl_display;
+
 mod output;
 mod xdg_shell;
 mod wl_compositor;
 mod wl_shm;
+mod window_management;
 
-pub use self::output::{WL_OUTPUT_VERSION, Output};
+pub use self::output::{WL_OUTPUT_VERSION, Output, output_global_added,
+                       output_global_removed};
 pub use self::xdg_shell::{XDG_WM_BASE_VERSION, XdgToplevel,
                           xdg_shell_init, create_xdg_toplevel};
 pub use self::wl_compositor::{WL_COMPOSITOR_VERSION, wl_compositor_init,
                               create_surface};
 pub use self::wl_shm::{WL_SHM_VERSION, wl_shm_init, create_buffer};
+pub use self::window_management::{WINDOW_MANAGER_VERSION, Window, WindowChanges,
+                                  window_manager_init};
+
+extern "C" {
+    fn wl_display_roundtrip(display: *mut wl_display) -> c_int;
+}
+
+thread_local! {
+    /// The connection to Way Cooler.
+    static DISPLAY: Cell<*mut wl_display> = Cell::new(ptr::null_mut());
+}
+
+/// Remember the connection to Way Cooler, so that we can wait on replies.
+pub fn set_display(display: *mut wl_display) {
+    DISPLAY.with(|cell| cell.set(display));
+}
+
+/// Wait until Way Cooler has handled everything we sent it, dispatching the
+/// events it sent back.
+///
+/// Returns false if the connection was lost.
+pub fn roundtrip() -> bool {
+    let display = DISPLAY.with(|cell| cell.get());
+    if display.is_null() {
+        return false
+    }
+    unsafe { wl_display_roundtrip(display) != -1 }
+}
@@ -1,6 +1,6 @@
 //! Wrapper around a wl_output
 
-use std::fmt;
+use std::{cell::Cell, fmt};
 
 use wlroots::{Area, Size, Origin};
 use wayland_client::protocol::wl_output::WlOutput;
@@ -12,6 +12,11 @@ use objects::screen::{self, Screen};
 /// The minimum version of the wl_output global to bind to.
 pub const WL_OUTPUT_VERSION: u32 = 2;
 
+thread_local! {
+    /// The name of the wl_output global that is being bound.
+    static BINDING_GLOBAL: Cell<u32> = Cell::new(0);
+}
+
 /// Wrapper around WlOutput.
 #[derive(Clone, Eq, PartialEq)]
 pub struct Output {
@@ -21,6 +26,8 @@ pub struct Output {
 /// The cached state for the WlOutput.
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 struct OutputState {
+    /// The name of the global the output was bound from.
+    global: u32,
     name: String,
     resolution: (i32, i32)
 }
@@ -28,7 +35,8 @@ struct OutputState {
 impl Output {
     pub fn new(new_proxy: Result<NewProxy<WlOutput>, u32>, _: ()) {
         let new_proxy = new_proxy.expect("Could not create WlOutput");
-        let state = Box::new(OutputState::default());
+        let global = BINDING_GLOBAL.with(Cell::get);
+        let state = Box::new(OutputState { global, ..OutputState::default() });
         let proxy = new_proxy.implement(move |event, mut proxy: Proxy<WlOutput>| {
             use wayland_client::protocol::wl_output::Event;
             let output = Output { proxy: proxy.clone() };
@@ -87,6 +95,25 @@ impl Output {
     pub fn name(&self) -> &str {
         unwrap_state(self).name.as_str()
     }
+
+    pub fn global(&self) -> u32 {
+        unwrap_state(self).global
+    }
+}
+
+/// Remember the name of the wl_output global that is about to be bound.
+pub fn output_global_added(global: u32) {
+    BINDING_GLOBAL.with(|binding| binding.set(global));
+}
+
+/// Remove the screens of the output when Way Cooler removes its global,
+/// e.g. because the monitor was unplugged.
+pub fn output_global_removed(global: u32) {
+    LUA.with(|lua| {
+        let lua = lua.borrow();
+        screen::remove_screens(&*lua, |output| output.global() == global)
+            .expect("Could not remove the screens of the output");
+    });
 }
 
 impl fmt::Debug for Output {
@@ -0,0 +1,286 @@
+//! Wrappers around the private window management protocol, which Way Cooler
+//! uses to tell us about the windows that it maps.
+
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+use std::{fmt, mem};
+
+use cairo::{Format, ImageSurface};
+use rlua::{self, Lua};
+use tempfile;
+use wayland_client::{Proxy, NewProxy};
+use wayland_client::protocol::wl_buffer::RequestsTrait as WlBufferTrait;
+use wayland_protocols::window_management::{
+    zway_cooler_window_manager_v1::{self, ZwayCoolerWindowManagerV1},
+    zway_cooler_window_v1::{self, ZwayCoolerWindowV1, RequestsTrait as WindowTrait}};
+use wlroots::{Area, KeyboardModifier, Origin, Size};
+
+use lua::LUA;
+use objects::client;
+use root;
+use wayland_obj;
+
+/// The minimum version of the zway_cooler_window_manager_v1 global to bind to.
+pub const WINDOW_MANAGER_VERSION: u32 = 4;
+
+thread_local! {
+    /// The window manager global.
+    ///
+    /// This should remain local to just this module.
+    static WINDOW_MANAGER: RefCell<Option<Proxy<ZwayCoolerWindowManagerV1>>> =
+        RefCell::new(None);
+}
+
+/// A window that has been mapped by Way Cooler.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Window {
+    proxy: Proxy<ZwayCoolerWindowV1>
+}
+
+/// Changes to the state of a window that were sent by Way Cooler.
+///
+/// A field is only set if it changed since the last time changes were
+/// applied.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct WindowChanges {
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub geometry: Option<Area>,
+    pub activated: Option<bool>
+}
+
+/// The cached state for the `Window`.
+///
+/// This needs to be stored as the user data in the `Window` so that it
+/// can be accessed anywhere.
+#[derive(Debug, Default)]
+struct WindowState {
+    /// Changes that will be applied on the next done event.
+    pending: WindowChanges,
+    /// Whether a client has been created for the window yet.
+    managed: bool,
+    /// Whether the window was rendered into the buffer of the last capture,
+    /// once Way Cooler has told us.
+    captured: Option<bool>
+}
+
+impl Window {
+    fn new(new_proxy: NewProxy<ZwayCoolerWindowV1>) -> Self {
+        let proxy = new_proxy.implement(|event, mut proxy: Proxy<ZwayCoolerWindowV1>| {
+            use self::zway_cooler_window_v1::Event;
+            let window = Window { proxy: proxy.clone() };
+            match event {
+                Event::Title { title } => {
+                    unwrap_state_mut(&mut proxy).pending.title = Some(title);
+                },
+                Event::AppId { app_id } => {
+                    unwrap_state_mut(&mut proxy).pending.app_id = Some(app_id);
+                },
+                Event::Geometry { x, y, width, height } => {
+                    let geometry = Area { origin: Origin { x, y },
+                                          size: Size { width, height } };
+                    unwrap_state_mut(&mut proxy).pending.geometry = Some(geometry);
+                },
+                Event::Activated { activated } => {
+                    unwrap_state_mut(&mut proxy).pending.activated = Some(activated != 0);
+                },
+                Event::Done => {
+                    let (changes, managed) = {
+                        let state = unwrap_state_mut(&mut proxy);
+                        let changes = mem::replace(&mut state.pending, WindowChanges::default());
+                        (changes, mem::replace(&mut state.managed, true))
+                    };
+                    LUA.with(|lua| {
+                        let lua = lua.borrow();
+                        let res = if managed {
+                            client::update(&*lua, window, changes)
+                        } else {
+                            client::manage(&*lua, window, changes)
+                        };
+                        if let Err(err) = res {
+                            error!("Could not update client: {}", err);
+                        }
+                    });
+                },
+                Event::Closed => {
+                    LUA.with(|lua| {
+                        let lua = lua.borrow();
+                        if let Err(err) = client::unmanage(&*lua, window.clone()) {
+                            error!("Could not unmanage client: {}", err);
+                        }
+                    });
+                    window.destroy();
+                },
+                Event::Captured { .. } => {
+                    unwrap_state_mut(&mut proxy).captured = Some(true);
+                },
+                Event::CaptureFailed { .. } => {
+                    unwrap_state_mut(&mut proxy).captured = Some(false);
+                }
+            }
+        });
+        let state = Box::new(WindowState::default());
+        proxy.set_user_data(Box::into_raw(state) as _);
+        Window { proxy }
+    }
+
+    /// Ask Way Cooler to move and resize the window.
+    pub fn set_geometry(&self, geometry: Area) {
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = geometry;
+        self.proxy.set_geometry(x, y, width, height);
+    }
+
+    /// Ask Way Cooler to focus and raise the window.
+    pub fn focus(&self) {
+        self.proxy.focus();
+    }
+
+    /// Ask the client that owns the window to close it.
+    pub fn close(&self) {
+        self.proxy.close();
+    }
+
+    /// Ask Way Cooler to render the window into an image of the size, and
+    /// wait until it has.
+    ///
+    /// Returns `None` if the window could not be rendered, e.g. because it
+    /// was closed.
+    pub fn capture(&self, size: Size) -> Option<ImageSurface> {
+        let Size { width, height } = size;
+        if width <= 0 || height <= 0 {
+            return None
+        }
+        let mut temp_file = tempfile::tempfile().ok()?;
+        temp_file.set_len(width as u64 * height as u64 * 4).ok()?;
+        let buffer = wayland_obj::create_buffer(temp_file.as_raw_fd(), size).ok()?;
+        let mut proxy = self.proxy.clone();
+        unwrap_state_mut(&mut proxy).captured = None;
+        self.proxy.capture(&buffer);
+        while unwrap_state_mut(&mut proxy).captured.is_none() {
+            if !wayland_obj::roundtrip() {
+                break
+            }
+        }
+        buffer.destroy();
+        if unwrap_state_mut(&mut proxy).captured != Some(true) {
+            return None
+        }
+        // The buffer is argb8888, which is the same as the ARgb32 of cairo.
+        let mut pixels = Vec::new();
+        temp_file.seek(SeekFrom::Start(0)).ok()?;
+        temp_file.read_to_end(&mut pixels).ok()?;
+        let mut image = ImageSurface::create(Format::ARgb32, width, height).ok()?;
+        let image_stride = image.get_stride() as usize;
+        {
+            let mut data = image.get_data().ok()?;
+            let row_length = width as usize * 4;
+            for (row, pixels) in pixels.chunks(row_length).enumerate() {
+                let start = row * image_stride;
+                data[start..start + row_length].copy_from_slice(pixels);
+            }
+        }
+        Some(image)
+    }
+
+    /// Make Way Cooler draw the decorations of the window, or leave them to
+    /// the client.
+    pub fn set_server_decorations(&self, server: bool) {
+        use self::zway_cooler_window_v1::DecorationPolicy;
+        self.proxy.set_decoration_policy(if server {
+                                             DecorationPolicy::Server
+                                         } else {
+                                             DecorationPolicy::Client
+                                         });
+    }
+
+    /// Ask Way Cooler to draw a border with the colour, as 0xRRGGBBAA, around
+    /// the window.
+    pub fn set_border(&self, width: u32, color: u32) {
+        self.proxy.set_border(width, color);
+    }
+
+    /// Destroy the window object and its cached state.
+    ///
+    /// This is done when Way Cooler tells us the window has been closed.
+    fn destroy(&self) {
+        self.proxy.destroy();
+        unsafe {
+            let user_data = self.proxy.get_user_data() as *mut WindowState;
+            if !user_data.is_null() {
+                self.proxy.set_user_data(::std::ptr::null_mut());
+                Box::from_raw(user_data);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.proxy.c_ptr())
+    }
+}
+
+pub fn window_manager_init(new_proxy: Result<NewProxy<ZwayCoolerWindowManagerV1>, u32>, _: ()) {
+    let new_proxy = match new_proxy {
+        Ok(new_proxy) => new_proxy,
+        Err(version) => {
+            error!("Got zway_cooler_window_manager_v1 version {}, expected version {}",
+                   version, WINDOW_MANAGER_VERSION);
+            error!("Windows will not be managed, ensure Way Cooler is up to date");
+            return
+        }
+    };
+    let proxy = new_proxy.implement(|event, _proxy| {
+        use self::zway_cooler_window_manager_v1::Event;
+        match event {
+            Event::Window { id } => {
+                Window::new(id);
+            }
+            Event::Scroll { axis, steps, modifiers } => {
+                let modifiers = KeyboardModifier::from_bits_truncate(modifiers);
+                LUA.with(|lua| {
+                    let lua = lua.borrow();
+                    if let Err(err) = emit_scroll(&*lua, axis, steps, modifiers) {
+                        error!("Could not emit the root buttons: {}", err);
+                    }
+                });
+            }
+        }
+    });
+    WINDOW_MANAGER.with(|window_manager| {
+        *window_manager.borrow_mut() = Some(proxy);
+    });
+}
+
+/// Scrolling over the root window presses buttons 4 and 5 (up and down)
+/// or 6 and 7 (left and right) once per step, like it does on X11.
+fn emit_scroll(lua: &Lua,
+               axis: zway_cooler_window_manager_v1::Axis,
+               steps: i32,
+               modifiers: KeyboardModifier)
+               -> rlua::Result<()> {
+    use self::zway_cooler_window_manager_v1::Axis;
+    let button = match (axis, steps < 0) {
+        (Axis::VerticalScroll, true) => 4,
+        (Axis::VerticalScroll, false) => 5,
+        (Axis::HorizontalScroll, true) => 6,
+        (Axis::HorizontalScroll, false) => 7
+    };
+    for _ in 0..steps.abs() {
+        root::emit_button(lua, button, modifiers)?;
+    }
+    Ok(())
+}
+
+fn unwrap_state_mut<'this>(proxy: &'this mut Proxy<ZwayCoolerWindowV1>)
+                           -> &'this mut WindowState {
+    unsafe {
+        let user_data = proxy.get_user_data() as *mut WindowState;
+        if user_data.is_null() {
+            panic!("User data has not been set yet");
+        }
+        &mut *user_data
+    }
+}
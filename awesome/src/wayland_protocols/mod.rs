@@ -1,4 +1,5 @@
 //! Module for all the Wayland objects generated from the Wayland extension
 //! protocols used by Awesome to talk to Way Cooler.
 
+pub mod window_management;
 pub mod xdg_shell;
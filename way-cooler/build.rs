@@ -1,7 +1,12 @@
-use std::{env, fs, io::Write, path::Path, process::Command};
+extern crate wayland_scanner;
+
+use std::{env, fs, io::Write, path::{Path, PathBuf}, process::Command};
+
+const PROTOCOL_PATH: &'static str = "../protocols";
 
 fn main() {
     dump_git_version();
+    build_wayland_protocols();
 }
 
 /// Writes the current git hash to a file that is read by Way Cooler
@@ -41,3 +46,26 @@ fn in_release_commit() -> bool {
                                     .unwrap();
     result.status.success()
 }
+
+/// Build the interfaces of the wayland protocols that Way Cooler implements.
+///
+/// The server side of the protocols is implemented directly against
+/// libwayland-server, so only the C interfaces are needed for it. The client
+/// side is generated for the test client of the integration tests.
+fn build_wayland_protocols() {
+    let protocols = fs::read_dir(PROTOCOL_PATH).expect("Protocol build path invalid.");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    for protocol_path in protocols {
+        let protocol_path: fs::DirEntry = protocol_path.unwrap();
+        let path: PathBuf = protocol_path.path().into();
+        let mut file_name: String = protocol_path.file_name().into_string().unwrap();
+        if let Some(extension) = file_name.find(".xml") {
+            file_name.truncate(extension);
+        }
+        wayland_scanner::generate_c_code(path.clone(),
+                                         out_dir.join(file_name.clone() + "_api.rs"),
+                                         wayland_scanner::Side::Client);
+        wayland_scanner::generate_c_interfaces(path, out_dir.join(file_name + "_interface.rs"));
+    }
+}
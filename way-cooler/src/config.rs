@@ -0,0 +1,947 @@
+//! The configuration of Way Cooler.
+//!
+//! It's read from `$XDG_CONFIG_HOME/way-cooler/config`, or the file passed
+//! with `--config`, on start up and again every time Way Cooler gets a
+//! SIGHUP.
+//!
+//! The file is made of sections of `key = value` lines. Sections that
+//! configure a device can be given the name of the device, e.g. a
+//! `[keyboard "AT Translated Set 2 keyboard"]` section only applies to that
+//! keyboard and takes precedence over the `[keyboard]` section. Lines that
+//! start with `#` are comments.
+//!
+//! Devices that libinput provides are configured with `[input]` sections. They
+//! can be named after the device or its type, e.g. `[input "type:touchpad"]`.
+//!
+//! Outputs are configured with `[output]` sections, named after the output,
+//! e.g. `[output "eDP-1"]`, or after its make, model and serial number
+//! separated by spaces.
+//!
+//! The `[bindings]` section maps keys to what Way Cooler does when they are
+//! pressed, e.g. `Ctrl+Alt+BackSpace = terminate`.
+
+use std::{collections::HashMap, env, fmt, fs::{self, File}, io::{self, Read},
+          path::{Path, PathBuf}, str::FromStr};
+
+use wlroots::{xkbcommon::xkb, KeyboardHandle, KeyboardModifier};
+
+const DEFAULT_REPEAT_RATE: i32 = 25;
+const DEFAULT_REPEAT_DELAY: i32 = 600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Where the configuration was read from, if anywhere.
+    pub path: Option<PathBuf>,
+    /// The settings for every keyboard.
+    pub keyboard: KeyboardConfig,
+    /// The settings for specific keyboards, by device name.
+    pub keyboards: HashMap<String, KeyboardConfig>,
+    /// The libinput settings for every device.
+    pub input: InputConfig,
+    /// The libinput settings for devices by name or by `type:<type>`.
+    pub inputs: HashMap<String, InputConfig>,
+    /// The settings for every output.
+    pub output: OutputConfig,
+    /// The settings for outputs by name or by make, model and serial number.
+    pub outputs: HashMap<String, OutputConfig>,
+    /// The keys that Way Cooler handles itself.
+    ///
+    /// If the configuration has a `[bindings]` section it replaces the
+    /// default bindings.
+    pub bindings: Vec<::KeyBinding>,
+    /// The modifier that has to be held down to drag views with the pointer.
+    pub drag_modifier: KeyboardModifier,
+    /// Whether a copy of the clipboard is kept when the client it was copied
+    /// from goes away.
+    pub persist_clipboard: bool,
+    /// The clients that may read and set the clipboard and the primary
+    /// selection without having the keyboard focus.
+    pub data_control: DataControlPolicy
+}
+
+/// The keymap and repeat settings of a keyboard.
+///
+/// Anything that isn't set falls back to the settings for every keyboard and
+/// then to the defaults. The xkb settings default to the `XKB_DEFAULT_*`
+/// environment variables.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KeyboardConfig {
+    pub rules: Option<String>,
+    pub model: Option<String>,
+    pub layout: Option<String>,
+    pub variant: Option<String>,
+    pub options: Option<String>,
+    /// How many keys are repeated per second.
+    pub repeat_rate: Option<i32>,
+    /// How long a key is held down before it starts repeating, in
+    /// milliseconds.
+    pub repeat_delay: Option<i32>
+}
+
+/// The libinput settings of a device.
+///
+/// Anything that isn't set is left at the libinput default for the device.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputConfig {
+    pub tap: Option<bool>,
+    pub drag_lock: Option<bool>,
+    pub natural_scroll: Option<bool>,
+    pub accel_profile: Option<AccelProfile>,
+    /// The pointer acceleration, from -1.0 to 1.0.
+    pub accel_speed: Option<f64>,
+    pub left_handed: Option<bool>,
+    pub middle_emulation: Option<bool>,
+    pub scroll_method: Option<ScrollMethod>,
+    pub disable_while_typing: Option<bool>,
+    /// The name of the output that a touch device is mapped to.
+    pub map_to_output: Option<String>
+}
+
+/// The settings of an output.
+///
+/// Anything that isn't set is left at what the output prefers: its best
+/// mode, a scale of 1, no transform and a position to the right of the other
+/// outputs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OutputConfig {
+    pub enabled: Option<bool>,
+    pub mode: Option<DisplayMode>,
+    pub scale: Option<f32>,
+    pub transform: Option<Transform>,
+    /// Where the top left corner of the output is in the output layout.
+    pub position: Option<(i32, i32)>
+}
+
+/// A mode of an output, e.g. `1920x1080@60`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayMode {
+    pub width: i32,
+    pub height: i32,
+    /// The refresh rate in Hz. Without one the highest refresh rate is used.
+    pub refresh: Option<f64>
+}
+
+/// How an output is rotated, counter-clockwise, and flipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270
+}
+
+/// Which clients may use the data control protocol, e.g. clipboard managers.
+///
+/// It lets clients see everything that is copied, so by default nobody may.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataControlPolicy {
+    Nobody,
+    Everybody,
+    /// Only the clients that run one of the programs, by absolute path.
+    Programs(Vec<PathBuf>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelProfile {
+    Flat,
+    Adaptive
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollMethod {
+    NoScroll,
+    TwoFinger,
+    Edge,
+    OnButtonDown
+}
+
+/// A `[kind "name"]` section of the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: String,
+    pub name: Option<String>,
+    pub entries: Vec<Entry>
+}
+
+/// A `key = value` line in a section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    pub line: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { path: None,
+                 keyboard: KeyboardConfig::default(),
+                 keyboards: HashMap::default(),
+                 input: InputConfig::default(),
+                 inputs: HashMap::default(),
+                 output: OutputConfig::default(),
+                 outputs: HashMap::default(),
+                 bindings: ::default_bindings(),
+                 drag_modifier: KeyboardModifier::WLR_MODIFIER_LOGO,
+                 persist_clipboard: false,
+                 data_control: DataControlPolicy::default() }
+    }
+}
+
+impl Config {
+    /// Load the configuration from the path, or the default location if there
+    /// isn't one.
+    ///
+    /// A missing file is the same as an empty one. If the file can't be read
+    /// or parsed the error is logged and the default configuration is used.
+    pub fn load(path: Option<PathBuf>) -> Config {
+        let path = path.or_else(default_path);
+        let config = match path {
+            Some(ref path) => Config::read(path).unwrap_or_else(|err| {
+                error!("Could not load the configuration at {}: {}", path.display(), err);
+                Config::default()
+            }),
+            None => Config::default()
+        };
+        Config { path, ..config }
+    }
+
+    /// Load the configuration again from where it was loaded the first time.
+    ///
+    /// Unlike `load`, the current configuration is kept if the file can't be
+    /// read or parsed.
+    pub fn reload(&self) -> Config {
+        let path = match self.path {
+            Some(ref path) => path.clone(),
+            None => return self.clone()
+        };
+        match Config::read(&path) {
+            Ok(config) => Config { path: Some(path), ..config },
+            Err(err) => {
+                error!("Could not reload the configuration at {}: {}", path.display(), err);
+                self.clone()
+            }
+        }
+    }
+
+    fn read(path: &PathBuf) -> Result<Config, String> {
+        let mut source = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut source)) {
+            Ok(_) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                info!("No configuration at {}, using the defaults", path.display());
+            }
+            Err(err) => return Err(err.to_string())
+        }
+        let sections = parse_sections(&source).map_err(|err| err.to_string())?;
+        Ok(Config::from_sections(&sections))
+    }
+
+    /// Build the configuration from the sections of a configuration file.
+    ///
+    /// Unknown sections and settings are logged and skipped.
+    pub fn from_sections(sections: &[Section]) -> Config {
+        let mut config = Config::default();
+        if sections.iter().any(|section| section.kind == "bindings") {
+            config.bindings.clear();
+        }
+        for section in sections {
+            match (section.kind.as_str(), section.name.clone()) {
+                ("keyboard", None) => config.keyboard.update(section),
+                ("keyboard", Some(name)) => {
+                    config.keyboards.entry(name).or_insert_with(Default::default).update(section)
+                }
+                ("input", None) => config.input.update(section),
+                ("input", Some(name)) => {
+                    config.inputs.entry(name).or_insert_with(Default::default).update(section)
+                }
+                ("output", None) => config.output.update(section),
+                ("output", Some(name)) => {
+                    config.outputs.entry(name).or_insert_with(Default::default).update(section)
+                }
+                ("bindings", None) => config.update_bindings(section),
+                ("pointer", None) => config.update_pointer(section),
+                ("clipboard", None) => config.update_clipboard(section),
+                (kind, _) => warn!("Unknown configuration section \"{}\"", kind)
+            }
+        }
+        config
+    }
+
+    fn update_bindings(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match ::KeyBinding::parse(&entry.key, &entry.value) {
+                Ok(binding) => self.bindings.push(binding),
+                Err(err) => warn!("{} on line {} of the configuration", err, entry.line)
+            }
+        }
+    }
+
+    fn update_pointer(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match entry.key.as_str() {
+                "drag_modifier" => match ::parse_modifier(&entry.value) {
+                    Some(modifier) => self.drag_modifier = modifier,
+                    None => warn!("Unknown modifier \"{}\" on line {} of the configuration",
+                                  entry.value, entry.line)
+                },
+                _ => entry.unknown(section)
+            }
+        }
+    }
+
+    fn update_clipboard(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match entry.key.as_str() {
+                "persist" => {
+                    self.persist_clipboard = entry.flag().unwrap_or(self.persist_clipboard)
+                }
+                "data_control" => {
+                    self.data_control = match entry.parse() {
+                        Some(DataControlPolicy::Programs(programs)) => {
+                            absolute_programs(entry, programs)
+                        }
+                        Some(policy) => policy,
+                        None => self.data_control.clone()
+                    }
+                }
+                _ => entry.unknown(section)
+            }
+        }
+    }
+
+    /// Set up the keyboard with the settings for it.
+    pub fn apply_keyboard(&self, keyboard: &mut KeyboardHandle) {
+        let name = keyboard.run(|keyboard| keyboard.input_device().name())
+                           .ok()
+                           .and_then(|name| name)
+                           .unwrap_or_default();
+        self.keyboard_config(&name).apply(keyboard);
+    }
+
+    /// The libinput settings for the device with the name and type.
+    ///
+    /// Settings for the device name take precedence over the ones for its
+    /// type, which take precedence over the ones for every device.
+    pub fn input_config(&self, name: &str, device_type: &str) -> InputConfig {
+        let by_type = self.inputs.get(&format!("type:{}", device_type))
+                                 .map(|config| config.or(&self.input))
+                                 .unwrap_or_else(|| self.input.clone());
+        self.inputs.get(name).map(|config| config.or(&by_type)).unwrap_or(by_type)
+    }
+
+    /// The settings for the output with the name, e.g. `DP-1`, and the
+    /// identifier, its make, model and serial number separated by spaces.
+    ///
+    /// Settings for the identifier take precedence over the ones for the
+    /// name, which take precedence over the ones for every output.
+    pub fn output_config(&self, name: &str, identifier: &str) -> OutputConfig {
+        let by_name = self.outputs.get(name)
+                                  .map(|config| config.or(&self.output))
+                                  .unwrap_or_else(|| self.output.clone());
+        self.outputs.get(identifier).map(|config| config.or(&by_name)).unwrap_or(by_name)
+    }
+
+    /// The settings for the keyboard with the device name.
+    pub fn keyboard_config(&self, name: &str) -> KeyboardConfig {
+        match self.keyboards.get(name) {
+            Some(config) => config.or(&self.keyboard),
+            None => self.keyboard.clone()
+        }
+    }
+}
+
+impl InputConfig {
+    fn update(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match entry.key.as_str() {
+                "tap" => self.tap = entry.flag().or(self.tap),
+                "drag_lock" => self.drag_lock = entry.flag().or(self.drag_lock),
+                "natural_scroll" => self.natural_scroll = entry.flag().or(self.natural_scroll),
+                "accel_profile" => self.accel_profile = entry.parse().or(self.accel_profile),
+                "accel_speed" => {
+                    self.accel_speed = entry.parse_within(|speed| *speed >= -1.0 && *speed <= 1.0,
+                                                          "between -1 and 1")
+                                            .or(self.accel_speed)
+                }
+                "left_handed" => self.left_handed = entry.flag().or(self.left_handed),
+                "middle_emulation" => {
+                    self.middle_emulation = entry.flag().or(self.middle_emulation)
+                }
+                "scroll_method" => self.scroll_method = entry.parse().or(self.scroll_method),
+                "disable_while_typing" => {
+                    self.disable_while_typing = entry.flag().or(self.disable_while_typing)
+                }
+                "map_to_output" => self.map_to_output = Some(entry.value.clone()),
+                _ => entry.unknown(section)
+            }
+        }
+    }
+
+    /// Use the settings of the other configuration for anything that isn't
+    /// set in this one.
+    pub fn or(&self, other: &InputConfig) -> InputConfig {
+        InputConfig { tap: self.tap.or(other.tap),
+                      drag_lock: self.drag_lock.or(other.drag_lock),
+                      natural_scroll: self.natural_scroll.or(other.natural_scroll),
+                      accel_profile: self.accel_profile.or(other.accel_profile),
+                      accel_speed: self.accel_speed.or(other.accel_speed),
+                      left_handed: self.left_handed.or(other.left_handed),
+                      middle_emulation: self.middle_emulation.or(other.middle_emulation),
+                      scroll_method: self.scroll_method.or(other.scroll_method),
+                      disable_while_typing: self.disable_while_typing
+                                                .or(other.disable_while_typing),
+                      map_to_output: self.map_to_output.clone().or(other.map_to_output.clone()) }
+    }
+}
+
+impl OutputConfig {
+    fn update(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match entry.key.as_str() {
+                "enabled" => self.enabled = entry.flag().or(self.enabled),
+                "mode" => self.mode = entry.parse().or(self.mode),
+                "scale" => {
+                    self.scale = entry.parse_within(|scale| *scale > 0.0, "above 0")
+                                      .or(self.scale)
+                }
+                "transform" => self.transform = entry.parse().or(self.transform),
+                "position" => {
+                    self.position = entry.parse::<Position>()
+                                         .map(|Position(x, y)| (x, y))
+                                         .or(self.position)
+                }
+                _ => entry.unknown(section)
+            }
+        }
+    }
+
+    /// Use the settings of the other configuration for anything that isn't
+    /// set in this one.
+    pub fn or(&self, other: &OutputConfig) -> OutputConfig {
+        OutputConfig { enabled: self.enabled.or(other.enabled),
+                       mode: self.mode.or(other.mode),
+                       scale: self.scale.or(other.scale),
+                       transform: self.transform.or(other.transform),
+                       position: self.position.or(other.position) }
+    }
+}
+
+impl FromStr for DisplayMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<DisplayMode, ()> {
+        let (size, refresh) = match value.find('@') {
+            Some(index) => {
+                let refresh = value[index + 1..].trim_right_matches("Hz");
+                (&value[..index], Some(refresh.parse::<f64>().map_err(|_| ())?))
+            }
+            None => (value, None)
+        };
+        let index = size.find('x').ok_or(())?;
+        let width = size[..index].parse::<i32>().map_err(|_| ())?;
+        let height = size[index + 1..].parse::<i32>().map_err(|_| ())?;
+        if width <= 0 || height <= 0 || refresh.map_or(false, |refresh| refresh <= 0.0) {
+            return Err(())
+        }
+        Ok(DisplayMode { width,
+                         height,
+                         refresh })
+    }
+}
+
+impl FromStr for Transform {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Transform, ()> {
+        match value {
+            "normal" => Ok(Transform::Normal),
+            "90" => Ok(Transform::Rotate90),
+            "180" => Ok(Transform::Rotate180),
+            "270" => Ok(Transform::Rotate270),
+            "flipped" => Ok(Transform::Flipped),
+            "flipped-90" => Ok(Transform::Flipped90),
+            "flipped-180" => Ok(Transform::Flipped180),
+            "flipped-270" => Ok(Transform::Flipped270),
+            _ => Err(())
+        }
+    }
+}
+
+/// An `x,y` position in the output layout.
+struct Position(i32, i32);
+
+impl FromStr for Position {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Position, ()> {
+        let index = value.find(',').ok_or(())?;
+        let x = value[..index].trim().parse().map_err(|_| ())?;
+        let y = value[index + 1..].trim().parse().map_err(|_| ())?;
+        Ok(Position(x, y))
+    }
+}
+
+impl Default for DataControlPolicy {
+    fn default() -> DataControlPolicy {
+        DataControlPolicy::Nobody
+    }
+}
+
+impl DataControlPolicy {
+    /// Whether the client running the executable, given by its canonical
+    /// path, may use the protocol.
+    ///
+    /// The programs are compared by their canonical path too, so that links
+    /// to them are allowed but other executables with the same name aren't.
+    pub fn allows(&self, executable: Option<&Path>) -> bool {
+        match *self {
+            DataControlPolicy::Nobody => false,
+            DataControlPolicy::Everybody => true,
+            DataControlPolicy::Programs(ref programs) => {
+                let executable = match executable {
+                    Some(executable) => executable,
+                    None => return false
+                };
+                programs.iter()
+                        .filter_map(|program| fs::canonicalize(program).ok())
+                        .any(|program| program == executable)
+            }
+        }
+    }
+}
+
+impl FromStr for DataControlPolicy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<DataControlPolicy, ()> {
+        match value {
+            "none" | "disabled" => Ok(DataControlPolicy::Nobody),
+            "all" | "enabled" => Ok(DataControlPolicy::Everybody),
+            _ => {
+                let programs: Vec<PathBuf> = value.split(',')
+                                                  .map(str::trim)
+                                                  .filter(|program| !program.is_empty())
+                                                  .map(PathBuf::from)
+                                                  .collect();
+                if programs.is_empty() {
+                    return Err(())
+                }
+                Ok(DataControlPolicy::Programs(programs))
+            }
+        }
+    }
+}
+
+impl FromStr for AccelProfile {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<AccelProfile, ()> {
+        match value {
+            "flat" => Ok(AccelProfile::Flat),
+            "adaptive" => Ok(AccelProfile::Adaptive),
+            _ => Err(())
+        }
+    }
+}
+
+impl FromStr for ScrollMethod {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<ScrollMethod, ()> {
+        match value {
+            "none" => Ok(ScrollMethod::NoScroll),
+            "two_finger" => Ok(ScrollMethod::TwoFinger),
+            "edge" => Ok(ScrollMethod::Edge),
+            "on_button_down" => Ok(ScrollMethod::OnButtonDown),
+            _ => Err(())
+        }
+    }
+}
+
+impl KeyboardConfig {
+    fn update(&mut self, section: &Section) {
+        for entry in &section.entries {
+            match entry.key.as_str() {
+                "rules" => self.rules = Some(entry.value.clone()),
+                "model" => self.model = Some(entry.value.clone()),
+                "layout" => self.layout = Some(entry.value.clone()),
+                "variant" => self.variant = Some(entry.value.clone()),
+                "options" => self.options = Some(entry.value.clone()),
+                "repeat_rate" => self.repeat_rate = entry.parse().or(self.repeat_rate),
+                "repeat_delay" => self.repeat_delay = entry.parse().or(self.repeat_delay),
+                _ => entry.unknown(section)
+            }
+        }
+    }
+
+    /// Use the settings of the other configuration for anything that isn't
+    /// set in this one.
+    pub fn or(&self, other: &KeyboardConfig) -> KeyboardConfig {
+        KeyboardConfig { rules: self.rules.clone().or_else(|| other.rules.clone()),
+                         model: self.model.clone().or_else(|| other.model.clone()),
+                         layout: self.layout.clone().or_else(|| other.layout.clone()),
+                         variant: self.variant.clone().or_else(|| other.variant.clone()),
+                         options: self.options.clone().or_else(|| other.options.clone()),
+                         repeat_rate: self.repeat_rate.or(other.repeat_rate),
+                         repeat_delay: self.repeat_delay.or(other.repeat_delay) }
+    }
+
+    /// Compile the keymap the settings describe.
+    pub fn keymap(&self) -> Option<xkb::Keymap> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        // Empty names are filled in by xkbcommon from the environment.
+        let setting = |value: &Option<String>| value.clone().unwrap_or_default();
+        xkb::Keymap::new_from_names(&context,
+                                    &setting(&self.rules),
+                                    &setting(&self.model),
+                                    &setting(&self.layout),
+                                    &setting(&self.variant),
+                                    self.options.clone(),
+                                    xkb::KEYMAP_COMPILE_NO_FLAGS)
+    }
+
+    /// Set the keymap and the repeat info of the keyboard.
+    ///
+    /// The seat sends the repeat info on to the clients.
+    pub fn apply(&self, keyboard: &mut KeyboardHandle) {
+        let keymap = self.keymap();
+        if keymap.is_none() {
+            error!("Could not compile the keymap for {:?}", self);
+        }
+        with_handles!([(keyboard: {keyboard})] => {
+            if let Some(ref keymap) = keymap {
+                keyboard.set_keymap(keymap);
+            }
+            keyboard.set_repeat_info(self.repeat_rate.unwrap_or(DEFAULT_REPEAT_RATE),
+                                     self.repeat_delay.unwrap_or(DEFAULT_REPEAT_DELAY));
+        }).ok();
+    }
+}
+
+impl Entry {
+    /// Parse the value, logging it if it's invalid.
+    pub fn parse<T: FromStr>(&self) -> Option<T> {
+        let value = self.value.parse().ok();
+        if value.is_none() {
+            warn!("Invalid value \"{}\" for \"{}\" on line {} of the configuration",
+                  self.value, self.key, self.line);
+        }
+        value
+    }
+
+    /// Parse a value that has to be in a range, logging it if it's invalid
+    /// or out of the range.
+    pub fn parse_within<T, F>(&self, in_range: F, range: &str) -> Option<T>
+        where T: FromStr,
+              F: Fn(&T) -> bool
+    {
+        let value = self.parse()?;
+        if !in_range(&value) {
+            warn!("Invalid value \"{}\" for \"{}\" on line {} of the configuration, \
+                   expected a value {}",
+                  self.value, self.key, self.line, range);
+            return None
+        }
+        Some(value)
+    }
+
+    /// Parse a value that turns something on or off, logging it if it's
+    /// invalid.
+    pub fn flag(&self) -> Option<bool> {
+        match self.value.as_str() {
+            "true" | "yes" | "on" | "enabled" => Some(true),
+            "false" | "no" | "off" | "disabled" => Some(false),
+            _ => {
+                warn!("Invalid value \"{}\" for \"{}\" on line {} of the configuration, \
+                       expected \"enabled\" or \"disabled\"",
+                      self.value, self.key, self.line);
+                None
+            }
+        }
+    }
+
+    /// Log that the entry isn't a setting of the section.
+    pub fn unknown(&self, section: &Section) {
+        warn!("Unknown setting \"{}\" in section \"{}\" on line {} of the configuration",
+              self.key, section.kind, self.line);
+    }
+}
+
+/// Allow the programs of the entry that are given by their absolute path.
+///
+/// Anybody can name their program like a clipboard manager, so file names
+/// alone are rejected.
+fn absolute_programs(entry: &Entry, programs: Vec<PathBuf>) -> DataControlPolicy {
+    let (absolute, relative): (Vec<_>, Vec<_>) =
+        programs.into_iter().partition(|program| program.is_absolute());
+    for program in relative {
+        warn!("Ignoring \"{}\" for \"{}\" on line {} of the configuration, programs have to \
+               be given by the absolute path of their executable",
+              program.display(), entry.key, entry.line);
+    }
+    if absolute.is_empty() {
+        DataControlPolicy::Nobody
+    } else {
+        DataControlPolicy::Programs(absolute)
+    }
+}
+
+/// The configuration file in the XDG config directory.
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    config_home.map(|config_home| config_home.join("way-cooler").join("config"))
+}
+
+/// Split a configuration file into its sections.
+pub fn parse_sections(source: &str) -> Result<Vec<Section>, ParseError> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: &str| {
+            Err(ParseError { line: line_number,
+                             message: message.into() })
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        if line.starts_with('[') {
+            if !line.ends_with(']') {
+                return error("expected ']' at the end of the section header")
+            }
+            let header = line[1..line.len() - 1].trim();
+            let (kind, name) = match header.find(char::is_whitespace) {
+                Some(index) => (&header[..index], Some(header[index..].trim())),
+                None => (header, None)
+            };
+            let name = match name {
+                Some(name) if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') => {
+                    Some(name[1..name.len() - 1].to_string())
+                }
+                Some(_) => return error("section names must be quoted"),
+                None => None
+            };
+            if kind.is_empty() {
+                return error("empty section header")
+            }
+            sections.push(Section { kind: kind.into(),
+                                    name,
+                                    entries: Vec::new() });
+            continue
+        }
+        let (key, value) = match line.find('=') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => return error("expected \"key = value\"")
+        };
+        if key.is_empty() {
+            return error("missing the name of the setting")
+        }
+        match sections.last_mut() {
+            Some(section) => section.entries.push(Entry { key: key.into(),
+                                                          value: value.into(),
+                                                          line: line_number }),
+            None => return error("settings have to be in a section")
+        }
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::symlink;
+
+    use tempfile;
+
+    use super::*;
+
+    const CONFIG: &'static str = r#"
+# Everybody types in German, except on the Dvorak keyboard.
+[keyboard]
+layout = de
+repeat_rate = 30
+
+[keyboard "Kinesis Advantage2"]
+layout = us
+variant = dvorak
+"#;
+
+    #[test]
+    fn parse_sections_and_entries() {
+        let sections = parse_sections(CONFIG).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].kind, "keyboard");
+        assert_eq!(sections[0].name, None);
+        assert_eq!(sections[0].entries[0],
+                   Entry { key: "layout".into(),
+                           value: "de".into(),
+                           line: 4 });
+        assert_eq!(sections[1].name, Some("Kinesis Advantage2".into()));
+        assert_eq!(sections[1].entries.len(), 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_sections("layout = de").unwrap_err().line, 1);
+        assert_eq!(parse_sections("[keyboard\nlayout = de").unwrap_err().line, 1);
+        assert_eq!(parse_sections("[keyboard]\nlayout").unwrap_err().line, 2);
+        assert!(parse_sections("[keyboard Kinesis]").is_err());
+    }
+
+    #[test]
+    fn keyboard_config_falls_back() {
+        let config = Config::from_sections(&parse_sections(CONFIG).unwrap());
+        let dvorak = config.keyboard_config("Kinesis Advantage2");
+        assert_eq!(dvorak.layout, Some("us".into()));
+        assert_eq!(dvorak.variant, Some("dvorak".into()));
+        assert_eq!(dvorak.repeat_rate, Some(30));
+        let other = config.keyboard_config("AT Translated Set 2 keyboard");
+        assert_eq!(other.layout, Some("de".into()));
+        assert_eq!(other.variant, None);
+    }
+
+    #[test]
+    fn input_config_precedence() {
+        let config = Config::from_sections(&parse_sections(r#"
+[input]
+accel_speed = 0.5
+natural_scroll = disabled
+
+[input "type:touchpad"]
+tap = enabled
+natural_scroll = enabled
+scroll_method = two_finger
+
+[input "SynPS/2 Synaptics TouchPad"]
+scroll_method = edge
+accel_profile = flat
+accel_speed = 2.0
+"#).unwrap());
+        let synaptics = config.input_config("SynPS/2 Synaptics TouchPad", "touchpad");
+        assert_eq!(synaptics,
+                   InputConfig { tap: Some(true),
+                                 natural_scroll: Some(true),
+                                 scroll_method: Some(ScrollMethod::Edge),
+                                 accel_profile: Some(AccelProfile::Flat),
+                                 accel_speed: Some(0.5),
+                                 ..InputConfig::default() });
+        let touchpad = config.input_config("Elan Touchpad", "touchpad");
+        assert_eq!(touchpad.scroll_method, Some(ScrollMethod::TwoFinger));
+        assert_eq!(touchpad.accel_profile, None);
+        let mouse = config.input_config("Logitech USB Mouse", "pointer");
+        assert_eq!(mouse,
+                   InputConfig { accel_speed: Some(0.5),
+                                 natural_scroll: Some(false),
+                                 ..InputConfig::default() });
+    }
+
+    #[test]
+    fn output_config_precedence() {
+        let config = Config::from_sections(&parse_sections(r#"
+[output]
+scale = 2
+
+[output "DP-1"]
+mode = 2560x1440@143.91Hz
+position = 1920, 0
+transform = 90
+
+[output "Dell Inc. DELL U2515H 9X2VY5CA0QTL"]
+mode = 1920x1080
+scale = 1
+
+[output "HDMI-A-1"]
+enabled = disabled
+scale = 0
+"#).unwrap());
+        let dell = config.output_config("DP-1", "Dell Inc. DELL U2515H 9X2VY5CA0QTL");
+        assert_eq!(dell,
+                   OutputConfig { mode: Some(DisplayMode { width: 1920,
+                                                           height: 1080,
+                                                           refresh: None }),
+                                  scale: Some(1.0),
+                                  transform: Some(Transform::Rotate90),
+                                  position: Some((1920, 0)),
+                                  ..OutputConfig::default() });
+        let other = config.output_config("DP-1", "Unknown Unknown Unknown");
+        assert_eq!(other.mode.and_then(|mode| mode.refresh), Some(143.91));
+        assert_eq!(other.scale, Some(2.0));
+        let hdmi = config.output_config("HDMI-A-1", "");
+        assert_eq!(hdmi.enabled, Some(false));
+        assert_eq!(hdmi.scale, Some(2.0));
+        assert!("1920x".parse::<DisplayMode>().is_err());
+        assert!("1920x1080@0".parse::<DisplayMode>().is_err());
+    }
+
+    #[test]
+    fn bindings_replace_the_defaults() {
+        assert_eq!(Config::default().bindings, ::default_bindings());
+        let config = Config::from_sections(&parse_sections("[bindings]\n\
+                                                            Logo+Shift+e = terminate\n\
+                                                            [pointer]\n\
+                                                            drag_modifier = Alt")
+                                                .unwrap());
+        assert_eq!(config.bindings, vec![::KeyBinding::parse("Logo+Shift+e", "terminate")
+                                             .unwrap()]);
+        assert_eq!(config.drag_modifier, KeyboardModifier::WLR_MODIFIER_ALT);
+    }
+
+    #[test]
+    fn invalid_values_are_skipped() {
+        let config = Config::from_sections(&parse_sections("[keyboard]\nrepeat_rate = fast")
+                                                .unwrap());
+        assert_eq!(config.keyboard.repeat_rate, None);
+    }
+
+    #[test]
+    fn clipboard_is_only_kept_when_enabled() {
+        assert!(!Config::default().persist_clipboard);
+        let config = Config::from_sections(&parse_sections("[clipboard]\npersist = enabled")
+                                                .unwrap());
+        assert!(config.persist_clipboard);
+        let config = Config::from_sections(&parse_sections("[clipboard]\npersist = maybe")
+                                                .unwrap());
+        assert!(!config.persist_clipboard);
+    }
+
+    #[test]
+    fn data_control_is_limited_to_the_programs() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipman = dir.path().join("clipman");
+        File::create(&clipman).unwrap();
+        let link = dir.path().join("clipman-link");
+        symlink(&clipman, &link).unwrap();
+        let clipman = fs::canonicalize(clipman).unwrap();
+        assert!(!Config::default().data_control.allows(Some(&clipman)));
+
+        let source = format!("[clipboard]\ndata_control = wl-paste, {}", link.display());
+        let policy = Config::from_sections(&parse_sections(&source).unwrap()).data_control;
+        assert!(policy.allows(Some(&clipman)));
+        assert!(!policy.allows(Some(Path::new("/usr/bin/wl-paste"))));
+        assert!(!policy.allows(None));
+
+        // Programs have to be given by their path.
+        let config = Config::from_sections(&parse_sections("[clipboard]\ndata_control = clipman")
+                                                .unwrap());
+        assert_eq!(config.data_control, DataControlPolicy::Nobody);
+        assert_eq!("all".parse(), Ok(DataControlPolicy::Everybody));
+        assert!(" , ".parse::<DataControlPolicy>().is_err());
+    }
+}
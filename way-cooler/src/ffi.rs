@@ -0,0 +1,25 @@
+//! Listening to the signals of the wlroots objects that are used directly.
+//!
+//! Signals of objects that live as long as the display, like the globals,
+//! are listened to until the end, so their listeners are never removed.
+
+use std::{mem, os::raw::c_void};
+
+use wayland_sys::server::{signal::wl_signal_add, wl_listener, wl_signal, WAYLAND_SERVER_HANDLE};
+
+/// Call `notify` every time the signal is emitted, until the listener is
+/// removed with `remove_listener`.
+pub unsafe fn add_listener(signal: *mut wl_signal,
+                           notify: unsafe extern "C" fn(*mut wl_listener, *mut c_void))
+                           -> *mut wl_listener {
+    let listener = Box::into_raw(Box::new(wl_listener { link: mem::zeroed(),
+                                                        notify }));
+    wl_signal_add(signal, listener);
+    listener
+}
+
+/// Stop listening to the signal and free the listener.
+pub unsafe fn remove_listener(listener: *mut wl_listener) {
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_list_remove, &mut (*listener).link);
+    Box::from_raw(listener);
+}
@@ -0,0 +1,168 @@
+//! Key bindings that are handled by Way Cooler itself, instead of being sent
+//! to the clients.
+
+use wlroots::{xkbcommon::xkb::{self, KEY_BackSpace, KEY_NoSymbol}, KeyboardModifier};
+
+/// What a key binding does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingAction {
+    /// Stop Way Cooler.
+    Terminate,
+    /// Switch to the virtual terminal.
+    SwitchVt(u32)
+}
+
+/// A keysym that, with exactly the modifiers held down, runs the action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub keysym: u32,
+    pub modifiers: KeyboardModifier,
+    pub action: BindingAction
+}
+
+impl KeyBinding {
+    /// Parse a binding from keys like `Ctrl+Alt+BackSpace` and an action like
+    /// `terminate`.
+    pub fn parse(keys: &str, action: &str) -> Result<KeyBinding, String> {
+        let mut names: Vec<&str> = keys.split('+').map(str::trim).collect();
+        let key = names.pop().unwrap_or_default();
+        let mut modifiers = KeyboardModifier::empty();
+        for name in names {
+            modifiers |= parse_modifier(name).ok_or_else(|| format!("Unknown modifier \"{}\"",
+                                                                      name))?;
+        }
+        let keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+        if keysym == KEY_NoSymbol {
+            return Err(format!("Unknown key \"{}\"", key))
+        }
+        let action = BindingAction::parse(action)?;
+        Ok(KeyBinding { keysym,
+                        modifiers,
+                        action })
+    }
+
+    /// Whether the binding is triggered by the keysym while the modifiers are
+    /// held down.
+    ///
+    /// Letters match regardless of their case, so that `Logo+Shift+e` is
+    /// triggered by the `E` that Shift produces. Locked modifiers, like caps
+    /// lock and num lock, are ignored unless the binding asks for them.
+    pub fn matches(&self, keysym: u32, modifiers: KeyboardModifier) -> bool {
+        let locked = (KeyboardModifier::WLR_MODIFIER_CAPS | KeyboardModifier::WLR_MODIFIER_MOD2) -
+                     self.modifiers;
+        self.keysym == fold_case(keysym) && self.modifiers == modifiers - locked
+    }
+}
+
+impl BindingAction {
+    fn parse(action: &str) -> Result<BindingAction, String> {
+        let mut words = action.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("terminate"), None, _) => Ok(BindingAction::Terminate),
+            (Some("switch_vt"), Some(vt), None) => {
+                vt.parse()
+                  .ok()
+                  .filter(|vt| *vt > 0)
+                  .map(BindingAction::SwitchVt)
+                  .ok_or_else(|| format!("Invalid virtual terminal \"{}\"", vt))
+            }
+            _ => Err(format!("Unknown action \"{}\"", action))
+        }
+    }
+}
+
+/// The keysym as it's parsed from its name, which is the lowercase one for
+/// letters.
+fn fold_case(keysym: u32) -> u32 {
+    match xkb::keysym_from_name(&xkb::keysym_get_name(keysym), xkb::KEYSYM_CASE_INSENSITIVE) {
+        KEY_NoSymbol => keysym,
+        folded => folded
+    }
+}
+
+/// The bindings that are used when the configuration doesn't have any.
+pub fn default_bindings() -> Vec<KeyBinding> {
+    vec![KeyBinding { keysym: KEY_BackSpace,
+                      modifiers: KeyboardModifier::WLR_MODIFIER_CTRL |
+                                 KeyboardModifier::WLR_MODIFIER_ALT,
+                      action: BindingAction::Terminate }]
+}
+
+/// Parse the name of a modifier, e.g. `Ctrl` or `Logo`.
+pub fn parse_modifier(name: &str) -> Option<KeyboardModifier> {
+    match name.to_lowercase().as_str() {
+        "shift" => Some(KeyboardModifier::WLR_MODIFIER_SHIFT),
+        "ctrl" | "control" => Some(KeyboardModifier::WLR_MODIFIER_CTRL),
+        "alt" | "mod1" => Some(KeyboardModifier::WLR_MODIFIER_ALT),
+        "mod2" => Some(KeyboardModifier::WLR_MODIFIER_MOD2),
+        "mod3" => Some(KeyboardModifier::WLR_MODIFIER_MOD3),
+        "logo" | "super" | "mod4" => Some(KeyboardModifier::WLR_MODIFIER_LOGO),
+        "mod5" => Some(KeyboardModifier::WLR_MODIFIER_MOD5),
+        _ => None
+    }
+}
+
+/// Find the binding triggered by any of the keysyms with the modifiers.
+pub fn find_binding(bindings: &[KeyBinding],
+                    keysyms: &[u32],
+                    modifiers: KeyboardModifier)
+                    -> Option<KeyBinding> {
+    bindings.iter()
+            .find(|binding| keysyms.iter().any(|keysym| binding.matches(*keysym, modifiers)))
+            .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use wlroots::xkbcommon::xkb::{KEY_E, KEY_Escape, KEY_F1, KEY_e};
+
+    use super::*;
+
+    #[test]
+    fn parse_binding() {
+        let binding = KeyBinding::parse("Logo+Shift+Escape", "terminate").unwrap();
+        assert_eq!(binding,
+                   KeyBinding { keysym: KEY_Escape,
+                                modifiers: KeyboardModifier::WLR_MODIFIER_LOGO |
+                                           KeyboardModifier::WLR_MODIFIER_SHIFT,
+                                action: BindingAction::Terminate });
+        assert!(KeyBinding::parse("Hyper+Escape", "terminate").is_err());
+        assert!(KeyBinding::parse("Logo+NotAKey", "terminate").is_err());
+        assert!(KeyBinding::parse("Logo+Escape", "explode").is_err());
+        assert_eq!(KeyBinding::parse("Logo+F2", "switch_vt 2").unwrap().action,
+                   BindingAction::SwitchVt(2));
+        assert!(KeyBinding::parse("Logo+F2", "switch_vt zero").is_err());
+        assert!(KeyBinding::parse("Logo+F2", "switch_vt 0").is_err());
+    }
+
+    #[test]
+    fn bindings_need_the_exact_modifiers() {
+        let bindings = default_bindings();
+        let ctrl_alt = KeyboardModifier::WLR_MODIFIER_CTRL | KeyboardModifier::WLR_MODIFIER_ALT;
+        assert!(find_binding(&bindings, &[KEY_BackSpace], ctrl_alt).is_some());
+        assert!(find_binding(&bindings, &[KEY_BackSpace],
+                             ctrl_alt | KeyboardModifier::WLR_MODIFIER_CAPS).is_some());
+        assert!(find_binding(&bindings, &[KEY_BackSpace],
+                             KeyboardModifier::WLR_MODIFIER_CTRL).is_none());
+        assert!(find_binding(&bindings, &[KEY_BackSpace],
+                             ctrl_alt | KeyboardModifier::WLR_MODIFIER_SHIFT).is_none());
+        assert!(find_binding(&bindings, &[KEY_F1], ctrl_alt).is_none());
+    }
+
+    #[test]
+    fn bindings_ignore_the_case_of_letters() {
+        let bindings = vec![KeyBinding::parse("Logo+Shift+e", "terminate").unwrap()];
+        let logo_shift = KeyboardModifier::WLR_MODIFIER_LOGO | KeyboardModifier::WLR_MODIFIER_SHIFT;
+        assert!(find_binding(&bindings, &[KEY_E], logo_shift).is_some());
+        assert!(find_binding(&bindings, &[KEY_e], logo_shift).is_some());
+        assert!(find_binding(&bindings, &[KEY_E], KeyboardModifier::WLR_MODIFIER_LOGO).is_none());
+    }
+
+    #[test]
+    fn locked_modifiers_are_only_used_when_asked_for() {
+        let bindings = vec![KeyBinding::parse("Mod2+Escape", "terminate").unwrap()];
+        let num_lock = KeyboardModifier::WLR_MODIFIER_MOD2;
+        assert!(find_binding(&bindings, &[KEY_Escape], num_lock).is_some());
+        assert!(find_binding(&bindings, &[KEY_Escape], KeyboardModifier::empty()).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+//! Applies the `[input]` settings of the configuration to the devices that
+//! libinput provides, and maps absolute devices like touchscreens to their
+//! output.
+
+use libinput::{AccelProfile, Device, DeviceCapability, DeviceConfigError, FromRaw, ScrollMethod};
+use wlroots::{wlroots_sys::{wlr_input_device_is_libinput, wlr_libinput_get_device_handle},
+              Cursor, InputDevice, OutputHandle};
+
+/// The prefixes of the names of outputs that are built into the device, which
+/// is where a touchscreen is unless the configuration says otherwise.
+const BUILT_IN_OUTPUTS: [&str; 3] = ["eDP", "LVDS", "DSI"];
+
+/// Configure the device, if it comes from libinput.
+pub fn configure_input_device(config: &::Config, input_device: &InputDevice) {
+    let mut device = unsafe {
+        if !wlr_input_device_is_libinput(input_device.as_ptr()) {
+            return
+        }
+        Device::from_raw(wlr_libinput_get_device_handle(input_device.as_ptr()) as *mut _)
+    };
+    let name = input_device.name().unwrap_or_default();
+    let device_type = device_type(&device);
+    let input_config = config.input_config(&name, device_type);
+    debug!("Configuring {} ({}) with {:?}", name, device_type, input_config);
+    let report = |setting: &str, result: Result<(), DeviceConfigError>| {
+        if let Err(err) = result {
+            warn!("Could not set {} of {}: {:?}", setting, name, err);
+        }
+    };
+    if let Some(tap) = input_config.tap {
+        report("tap", device.config_tap_set_enabled(tap));
+    }
+    if let Some(drag_lock) = input_config.drag_lock {
+        report("drag_lock", device.config_tap_set_drag_lock_enabled(drag_lock));
+    }
+    if let Some(natural_scroll) = input_config.natural_scroll {
+        report("natural_scroll",
+               device.config_scroll_set_natural_scroll_enabled(natural_scroll));
+    }
+    if let Some(accel_profile) = input_config.accel_profile {
+        let accel_profile = match accel_profile {
+            ::AccelProfile::Flat => AccelProfile::Flat,
+            ::AccelProfile::Adaptive => AccelProfile::Adaptive
+        };
+        report("accel_profile", device.config_accel_set_profile(accel_profile));
+    }
+    if let Some(accel_speed) = input_config.accel_speed {
+        report("accel_speed", device.config_accel_set_speed(accel_speed));
+    }
+    if let Some(left_handed) = input_config.left_handed {
+        report("left_handed", device.config_left_handed_set(left_handed));
+    }
+    if let Some(middle_emulation) = input_config.middle_emulation {
+        report("middle_emulation",
+               device.config_middle_emulation_set_enabled(middle_emulation));
+    }
+    if let Some(scroll_method) = input_config.scroll_method {
+        let scroll_method = match scroll_method {
+            ::ScrollMethod::NoScroll => ScrollMethod::NoScroll,
+            ::ScrollMethod::TwoFinger => ScrollMethod::TwoFinger,
+            ::ScrollMethod::Edge => ScrollMethod::Edge,
+            ::ScrollMethod::OnButtonDown => ScrollMethod::OnButtonDown
+        };
+        report("scroll_method", device.config_scroll_set_method(scroll_method));
+    }
+    if let Some(disable_while_typing) = input_config.disable_while_typing {
+        report("disable_while_typing", device.config_dwt_set_enabled(disable_while_typing));
+    }
+}
+
+/// Map the device to its output, so that a point on the device lands on the
+/// same point of the output.
+///
+/// This is the output from the `map_to_output` setting of the device.
+/// Touchscreens without one are mapped to the output that is built into the
+/// device, if there is one. Anything else covers the whole output layout.
+pub fn map_input_to_output(config: &::Config,
+                           cursor: &mut Cursor,
+                           outputs: &mut [OutputHandle],
+                           input_device: &InputDevice,
+                           device_type: &str) {
+    let name = input_device.name().unwrap_or_default();
+    let configured = config.input_config(&name, device_type).map_to_output;
+    let index = outputs.iter().position(|output| {
+        let output_name = output.run(|output| output.name()).unwrap_or_default();
+        match configured {
+            Some(ref configured) => output_name == *configured,
+            None if device_type == "touch" => {
+                BUILT_IN_OUTPUTS.iter().any(|prefix| output_name.starts_with(prefix))
+            }
+            None => false
+        }
+    });
+    match index {
+        Some(index) => {
+            with_handles!([(output: {&mut outputs[index]})] => {
+                debug!("Mapping {} to {}", name, output.name());
+                cursor.map_input_to_output(input_device, Some(output));
+            }).unwrap();
+        }
+        None => cursor.map_input_to_output(input_device, None)
+    }
+}
+
+/// Map every touchscreen and tablet to its output again, e.g. after the
+/// outputs or the configuration changed.
+pub fn map_inputs_to_outputs(server: &mut ::Server) {
+    let ::Server { ref config,
+                   ref cursor,
+                   ref mut outputs,
+                   ref mut touches,
+                   ref mut tablet_tools,
+                   .. } = *server;
+    dehandle!(
+        @cursor = {cursor};
+        for touch in touches.iter_mut() {
+            touch.run(|touch| {
+                     map_input_to_output(config, cursor, outputs, touch.input_device(), "touch")
+                 })
+                 .ok();
+        }
+        for tool in tablet_tools.iter_mut() {
+            tool.run(|tool| {
+                    map_input_to_output(config,
+                                        cursor,
+                                        outputs,
+                                        tool.input_device(),
+                                        "tablet_tool")
+                })
+                .ok();
+        }
+    );
+}
+
+/// The type of the device, as used in `[input "type:<type>"]` sections.
+fn device_type(device: &Device) -> &'static str {
+    if device.has_capability(DeviceCapability::Pointer) {
+        // Touchpads are the pointers that can be tapped.
+        if device.config_tap_finger_count() > 0 {
+            "touchpad"
+        } else {
+            "pointer"
+        }
+    } else if device.has_capability(DeviceCapability::Keyboard) {
+        "keyboard"
+    } else if device.has_capability(DeviceCapability::Touch) {
+        "touch"
+    } else if device.has_capability(DeviceCapability::TabletTool) {
+        "tablet_tool"
+    } else if device.has_capability(DeviceCapability::TabletPad) {
+        "tablet_pad"
+    } else if device.has_capability(DeviceCapability::Switch) {
+        "switch"
+    } else {
+        "unknown"
+    }
+}
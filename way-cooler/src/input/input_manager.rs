@@ -1,5 +1,6 @@
 use wlroots::{Capability, CompositorHandle, InputManagerHandler, KeyboardHandle, KeyboardHandler,
-              PointerHandle, PointerHandler};
+              PointerHandle, PointerHandler, TabletPadHandle, TabletPadHandler, TabletToolHandle,
+              TabletToolHandler, TouchHandle, TouchHandler};
 
 pub struct InputManager;
 
@@ -18,7 +19,9 @@ impl InputManagerHandler for InputManager {
             @compositor = {compositor};
             @keyboard = {keyboard};
             let server: &mut ::Server = compositor.into();
-            server.keyboards.push(keyboard.weak_reference());
+            let mut handle = keyboard.weak_reference();
+            server.config.apply_keyboard(&mut handle);
+            server.keyboards.push(handle);
             // Now that we have at least one keyboard, update the seat capabilities.
             @seat = {&server.seat.seat};
             let mut capabilities = seat.capabilities();
@@ -36,6 +39,7 @@ impl InputManagerHandler for InputManager {
             @compositor = {compositor};
             @pointer = {pointer};
             let server: &mut ::Server = compositor.into();
+            ::configure_input_device(&server.config, pointer.input_device());
             server.pointers.push(pointer.weak_reference());
             if server.pointers.len() == 1 {
                 // Now that we have at least one keyboard, update the seat capabilities.
@@ -51,4 +55,50 @@ impl InputManagerHandler for InputManager {
         );
         Some(Box::new(::Pointer))
     }
+
+    fn touch_added(&mut self,
+                   compositor: CompositorHandle,
+                   touch: TouchHandle)
+                   -> Option<Box<TouchHandler>> {
+        dehandle!(
+            @compositor = {&compositor};
+            @touch = {touch};
+            let server: &mut ::Server = compositor.into();
+            ::configure_input_device(&server.config, touch.input_device());
+            server.touches.push(touch.weak_reference());
+            if server.touches.len() == 1 {
+                with_handles!([(seat: {&mut server.seat.seat})] => {
+                    let mut capabilities = seat.capabilities();
+                    capabilities.insert(Capability::Touch);
+                    seat.set_capabilities(capabilities);
+                }).expect("Seat was destroyed");
+            };
+            @cursor = {&server.cursor};
+            cursor.attach_input_device(touch.input_device())
+        );
+        with_handles!([(compositor: {compositor})] => {
+            ::map_inputs_to_outputs(compositor.into());
+        }).unwrap();
+        Some(Box::new(::Touch))
+    }
+
+    fn tablet_tool_added(&mut self,
+                         compositor: CompositorHandle,
+                         mut tablet: TabletToolHandle)
+                         -> Option<Box<TabletToolHandler>> {
+        with_handles!([(compositor: {compositor})] => {
+            ::tablet_tool_added(compositor.into(), &mut tablet);
+        }).unwrap();
+        Some(Box::new(::TabletTool))
+    }
+
+    fn tablet_pad_added(&mut self,
+                        compositor: CompositorHandle,
+                        mut pad: TabletPadHandle)
+                        -> Option<Box<TabletPadHandler>> {
+        with_handles!([(compositor: {compositor})] => {
+            ::tablet_pad_added(compositor.into(), &mut pad);
+        }).unwrap();
+        Some(Box::new(::TabletPad))
+    }
 }
@@ -1,36 +1,68 @@
-use wlroots::{key_events::KeyEvent, xkbcommon::xkb::{KEY_Escape, KEY_Super_L, KEY_Super_R},
-              Capability, CompositorHandle, KeyboardHandle, KeyboardHandler, WLR_KEY_PRESSED};
+use wlroots::{key_events::KeyEvent, Capability, CompositorHandle, KeyboardHandle, KeyboardHandler,
+              KeyboardModifier, WLR_KEY_PRESSED};
 
 pub struct Keyboard;
 
-fn key_is_meta(key: u32) -> bool {
-    // TODO configure meta key
-    key == KEY_Super_L || key == KEY_Super_R
+/// Run the binding for a key that was pressed, if it has one, or switch to
+/// another virtual terminal when there is one to switch to.
+///
+/// Returns whether Way Cooler handled the key, in which case it must not be
+/// sent to the clients. The release of a key that ran a binding is handled as
+/// well, so that clients never see half of a key press.
+pub fn handle_key(server: &mut ::Server,
+                  keycode: u32,
+                  keysyms: &[u32],
+                  modifiers: KeyboardModifier,
+                  pressed: bool)
+                  -> bool {
+    if !pressed {
+        return server.seat.bound_keys.remove(&keycode)
+    }
+    // The keymap only produces these with Ctrl+Alt+F1 to F12. Without a
+    // virtual terminal, e.g. when nested, they go to the clients instead.
+    if server.session.can_switch_vt() {
+        if let Some(vt) = keysyms.iter().filter_map(|keysym| ::vt_for_keysym(*keysym)).next() {
+            server.seat.bound_keys.insert(keycode);
+            server.session.switch_vt(vt);
+            return true
+        }
+    }
+    match ::find_binding(&server.config.bindings, keysyms, modifiers) {
+        Some(binding) => {
+            server.seat.bound_keys.insert(keycode);
+            run_binding(server, binding.action);
+            true
+        }
+        None => false
+    }
+}
+
+pub fn run_binding(server: &mut ::Server, action: ::BindingAction) {
+    match action {
+        ::BindingAction::Terminate => ::wlroots::terminate(),
+        ::BindingAction::SwitchVt(vt) => server.session.switch_vt(vt)
+    }
+}
+
+/// Keep track of the modifiers that are held down, e.g. for dragging views.
+pub fn modifiers_changed(server: &mut ::Server, modifiers: KeyboardModifier) {
+    server.seat.drag_modifier_held = modifiers.contains(server.config.drag_modifier);
 }
 
 impl KeyboardHandler for Keyboard {
     fn on_key(&mut self, compositor: CompositorHandle, keyboard: KeyboardHandle, event: &KeyEvent) {
+        let handled = with_handles!([(compositor: {&compositor}), (keyboard: {&keyboard})] => {
+            handle_key(compositor.into(),
+                       event.keycode(),
+                       &event.pressed_keys(),
+                       keyboard.get_modifiers(),
+                       event.key_state() == WLR_KEY_PRESSED)
+        }).unwrap();
+        if handled {
+            return
+        }
         dehandle!(
             @compositor = {compositor};
-            if event.key_state() == WLR_KEY_PRESSED {
-                for key in event.pressed_keys() {
-                    // TODO Keep it hardcoded, make this configurable by awesome
-                    if key == KEY_Escape {
-                        ::wlroots::terminate();
-                    }
-                    if key_is_meta(key) {
-                        let server: &mut ::Server = compositor.into();
-                        server.seat.meta = true;
-                    }
-                }
-            } else {
-                for key in event.pressed_keys() {
-                    if key_is_meta(key) {
-                        let server: &mut ::Server = compositor.into();
-                        server.seat.meta = false;
-                    }
-                }
-            };
             let server: &mut ::Server = compositor.into();
             @seat = {&server.seat.seat};
             @keyboard = {keyboard};
@@ -54,8 +86,9 @@ impl KeyboardHandler for Keyboard {
         dehandle!(
             @compositor = {compositor};
             let server: &mut ::Server = compositor.into();
-            @seat = {&server.seat.seat};
             @keyboard = {keyboard};
+            modifiers_changed(server, keyboard.get_modifiers());
+            @seat = {&server.seat.seat};
             seat.keyboard_notify_modifiers(&mut keyboard.get_modifier_masks())
         );
     }
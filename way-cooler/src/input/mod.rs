@@ -1,7 +1,15 @@
+mod bindings;
+mod device_config;
 mod input_manager;
 mod keyboard;
 mod pointer;
+mod tablet;
+mod touch;
 
+pub use self::bindings::*;
+pub use self::device_config::*;
 pub use self::input_manager::*;
 pub use self::keyboard::*;
 pub use self::pointer::*;
+pub use self::tablet::*;
+pub use self::touch::*;
@@ -1,5 +1,6 @@
 use wlroots::{pointer_events::*, Capability, CompositorHandle, PointerHandle, PointerHandler,
-              WLR_BUTTON_RELEASED};
+              WLR_BUTTON_PRESSED};
+use wlroots::wlroots_sys::{wlr_axis_orientation, wlr_axis_source};
 
 #[derive(Debug, Default)]
 pub struct Pointer;
@@ -10,66 +11,53 @@ impl PointerHandler for Pointer {
                           _: PointerHandle,
                           event: &AbsoluteMotionEvent) {
         dehandle!(
-            @compositor = {compositor};
-            let server: &mut ::Server = compositor.data.downcast_mut().unwrap();
-            let ::Server { ref cursor,
-                         ref mut xcursor_manager,
-                         ref mut seat,
-                         ref mut views,
-                         .. } = *server;
-            @cursor = {cursor};
+            @compositor = {&compositor};
+            let server: &mut ::Server = compositor.into();
+            @cursor = {&server.cursor};
             let (x, y) = event.pos();
-            cursor.warp_absolute(event.device(), x, y);
-            seat.update_cursor_position(cursor,
-                                        xcursor_manager,
-                                        views,
-                                        Some(event.time_msec())));
+            cursor.warp_absolute(event.device(), x, y));
+        with_handles!([(compositor: {compositor})] => {
+            pointer_moved(compositor.into(), Some(event.time_msec()));
+        }).unwrap();
     }
 
     fn on_motion(&mut self, compositor: CompositorHandle, _: PointerHandle, event: &MotionEvent) {
         dehandle!(
-            @compositor = {compositor};
+            @compositor = {&compositor};
             let server: &mut ::Server = compositor.into();
-            let ::Server { ref cursor,
-                         ref mut xcursor_manager,
-                         ref mut seat,
-                         ref mut views,
-                         .. } = *server;
-            @cursor = {cursor};
+            @cursor = {&server.cursor};
             let (x, y) = event.delta();
-            cursor.move_to(event.device(), x, y);
-            seat.update_cursor_position(cursor,
-                                        xcursor_manager,
-                                        views,
-                                        Some(event.time_msec())));
+            cursor.move_to(event.device(), x, y));
+        with_handles!([(compositor: {compositor})] => {
+            pointer_moved(compositor.into(), Some(event.time_msec()));
+        }).unwrap();
     }
 
     fn on_button(&mut self, compositor: CompositorHandle, _: PointerHandle, event: &ButtonEvent) {
-        dehandle!(
-            @compositor = {compositor};
-            let server: &mut ::Server = compositor.into();
-            let ::Server { ref cursor,
-                         ref mut views,
-                         ref mut seat,
-                         .. } = *server;
-            @cursor = {cursor};
-            if event.state() == WLR_BUTTON_RELEASED {
-                seat.action = None;
-                seat.send_button(event);
-                return
-            };
+        with_handles!([(compositor: {compositor})] => {
+            pointer_button(compositor.into(),
+                           event.time_msec(),
+                           event.button(),
+                           event.state() == WLR_BUTTON_PRESSED);
+        }).unwrap();
+    }
 
-            if let (Some(view), _, _, _) = ::Seat::view_at_pointer(views, cursor) {
-                seat.focus_view(view.clone(), views);
+    fn on_axis(&mut self, compositor: CompositorHandle, _: PointerHandle, event: &AxisEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            pointer_axis(compositor.into(),
+                         event.time_msec(),
+                         event.orientation(),
+                         event.delta(),
+                         event.delta_discrete(),
+                         event.source());
+        }).unwrap();
+    }
 
-                let meta_held_down = seat.meta;
-                if meta_held_down && event.button() == BTN_LEFT {
-                    seat.move_view(cursor, &view, None);
-                }
-                seat.send_button(event);
-            } else {
-                seat.clear_focus();
-            });
+    fn on_frame(&mut self, compositor: CompositorHandle, _: PointerHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.seat.send_frame();
+        }).unwrap();
     }
 
     fn destroyed(&mut self, compositor: CompositorHandle, pointer: PointerHandle) {
@@ -92,3 +80,90 @@ impl PointerHandler for Pointer {
         }).unwrap();
     }
 }
+
+/// Update the seat after the cursor was moved, e.g. to move the view that is
+/// being dragged or to give the pointer focus to the surface underneath it.
+pub fn pointer_moved(server: &mut ::Server, time_msec: Option<u32>) {
+    {
+        let ::Server { ref cursor,
+                       ref mut xcursor_manager,
+                       ref layer_surfaces,
+                       ref mut seat,
+                       ref mut views,
+                       .. } = *server;
+        dehandle!(
+            @cursor = {cursor};
+            seat.update_cursor_position(cursor, xcursor_manager, views, layer_surfaces, time_msec)
+        );
+    }
+    ::damage_changes(server);
+}
+
+/// Handle a button being pressed or released at the position of the cursor.
+pub fn pointer_button(server: &mut ::Server, time_msec: u32, button: u32, pressed: bool) {
+    {
+        let ::Server { ref cursor,
+                       ref mut views,
+                       ref layer_surfaces,
+                       ref mut seat,
+                       .. } = *server;
+        dehandle!(
+            @cursor = {cursor};
+            if !pressed {
+                seat.action = None;
+                seat.send_button(time_msec, button, pressed);
+                return
+            };
+
+            let (lx, ly) = cursor.coords();
+            if ::layer_surface_at(layer_surfaces, &::LAYERS_ABOVE_VIEWS, lx, ly).is_some() {
+                // Clicking a panel shouldn't take the focus away from the view.
+                seat.send_button(time_msec, button, pressed);
+            } else if let (Some(view), _, _, _) = ::Seat::view_at_pointer(views, cursor) {
+                if view.wants_focus() {
+                    seat.focus_view(view.clone(), views);
+                }
+
+                if seat.drag_modifier_held && button == BTN_LEFT {
+                    seat.move_view(cursor, &view, None);
+                }
+                seat.send_button(time_msec, button, pressed);
+            } else {
+                seat.clear_focus();
+                if ::layer_surface_at(layer_surfaces, &::LAYERS_BELOW_VIEWS, lx, ly).is_some() {
+                    seat.send_button(time_msec, button, pressed);
+                }
+            }
+        );
+    }
+    ::damage_changes(server);
+}
+
+/// Handle a scroll at the position of the cursor.
+///
+/// The scroll goes to the surface underneath the cursor. If there is none it
+/// is given to the window manager instead, e.g. to switch tags.
+pub fn pointer_axis(server: &mut ::Server,
+                    time_msec: u32,
+                    orientation: wlr_axis_orientation,
+                    delta: f64,
+                    delta_discrete: i32,
+                    source: wlr_axis_source) {
+    let ::Server { ref cursor,
+                   ref mut views,
+                   ref layer_surfaces,
+                   ref seat,
+                   ref mut window_management,
+                   .. } = *server;
+    dehandle!(
+        @cursor = {cursor};
+        match ::Seat::surface_at_pointer(views, layer_surfaces, cursor) {
+            (Some(_), _, _) => {
+                seat.send_axis(time_msec, orientation, delta, delta_discrete, source)
+            }
+            (None, _, _) => {
+                window_management.scroll(orientation, delta, delta_discrete, seat.modifiers())
+            }
+        }
+    );
+}
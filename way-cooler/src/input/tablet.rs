@@ -0,0 +1,612 @@
+//! Drawing tablets.
+//!
+//! The tools and pads of a tablet are announced to clients with the
+//! tablet-v2 protocol. A tool that is over a surface whose client doesn't
+//! bind the protocol, or over empty space, moves the pointer instead and its
+//! tip acts as the left button.
+
+use std::{os::raw::c_void, ptr};
+
+use wayland_sys::server::{wl_display, wl_listener};
+use wlroots::{self, pointer_events::BTN_LEFT, CompositorHandle, InputDevice, SurfaceHandle,
+              TabletPadHandle, TabletPadHandler, TabletToolHandle, TabletToolHandler,
+              tablet_pad_events::{self, RingEvent, StripEvent},
+              tablet_tool_events::{self, AxisEvent, ProximityEvent, TipEvent},
+              wlroots_sys::*};
+
+use ::ffi::{add_listener, remove_listener};
+
+// The axes of a tool that an axis event can update.
+const AXIS_X: u32 = 1 << 0;
+const AXIS_Y: u32 = 1 << 1;
+const AXIS_DISTANCE: u32 = 1 << 2;
+const AXIS_PRESSURE: u32 = 1 << 3;
+const AXIS_TILT_X: u32 = 1 << 4;
+const AXIS_TILT_Y: u32 = 1 << 5;
+const AXIS_ROTATION: u32 = 1 << 6;
+const AXIS_SLIDER: u32 = 1 << 7;
+const AXIS_WHEEL: u32 = 1 << 8;
+
+/// Where the events of a tool go.
+#[derive(Debug, Clone, PartialEq)]
+enum Focus {
+    /// The tool is over a surface whose client binds the tablet protocol.
+    ///
+    /// The offset is the position of the surface in output layout
+    /// coordinates.
+    Surface {
+        surface: SurfaceHandle,
+        offset: (f64, f64)
+    },
+    /// The tool moves the pointer.
+    Pointer
+}
+
+impl Focus {
+    /// Whether the events can still go there, i.e. the surface wasn't
+    /// destroyed.
+    fn is_alive(&self) -> bool {
+        match *self {
+            Focus::Surface { ref surface, .. } => surface.run(|_| ()).is_ok(),
+            Focus::Pointer => true
+        }
+    }
+}
+
+/// A tool, e.g. a pen or an eraser, that has been used on a tablet.
+#[derive(Debug)]
+struct Tool {
+    tool: *mut wlr_tablet_tool,
+    tablet_tool: *mut wlr_tablet_v2_tablet_tool,
+    /// The last position of the tool on the tablet, from 0 to 1.
+    position: (f64, f64),
+    /// The last position of the tool in output layout coordinates.
+    layout_position: (f64, f64),
+    /// The last tilt of the tool, in degrees.
+    tilt: (f64, f64),
+    /// Where the events of the tool go while it is in proximity.
+    focus: Option<Focus>,
+    tip_down: bool
+}
+
+#[derive(Debug)]
+struct Pad {
+    device: *mut wlr_input_device,
+    pad: *mut wlr_tablet_v2_tablet_pad,
+    /// The surface the pad was last sent into.
+    surface: Option<SurfaceHandle>
+}
+
+/// The tablets, pads and tools that are announced to clients.
+#[derive(Debug)]
+pub struct Tablets {
+    manager: *mut wlr_tablet_manager_v2,
+    tablets: Vec<(*mut wlr_input_device, *mut wlr_tablet_v2_tablet)>,
+    pads: Vec<Pad>,
+    tools: Vec<Tool>
+}
+
+impl Default for Tablets {
+    fn default() -> Tablets {
+        Tablets { manager: ptr::null_mut(),
+                  tablets: Vec::new(),
+                  pads: Vec::new(),
+                  tools: Vec::new() }
+    }
+}
+
+impl Tablets {
+    /// Advertise the tablet manager global on the display.
+    pub fn new(display: *mut wl_display) -> Tablets {
+        let manager = unsafe { wlr_tablet_v2_create(display as *mut _) };
+        if manager.is_null() {
+            panic!("Could not create the tablet manager global");
+        }
+        Tablets { manager,
+                  ..Tablets::default() }
+    }
+
+    fn add_tablet(&mut self, seat: *mut wlr_seat, device: *mut wlr_input_device) {
+        let tablet = unsafe { wlr_tablet_create(self.manager, seat, device) };
+        if tablet.is_null() {
+            error!("Could not create the tablet");
+            return
+        }
+        self.tablets.push((device, tablet));
+    }
+
+    fn add_pad(&mut self, seat: *mut wlr_seat, device: *mut wlr_input_device) {
+        let pad = unsafe { wlr_tablet_pad_create(self.manager, seat, device) };
+        if pad.is_null() {
+            error!("Could not create the tablet pad");
+            return
+        }
+        self.pads.push(Pad { device,
+                             pad,
+                             surface: None });
+    }
+
+    fn remove_device(&mut self, device: *mut wlr_input_device) {
+        self.tablets.retain(|&(tablet_device, _)| tablet_device != device);
+        self.pads.retain(|pad| pad.device != device);
+    }
+
+    fn tablet(&self, device: *mut wlr_input_device) -> Option<*mut wlr_tablet_v2_tablet> {
+        self.tablets.iter()
+            .find(|&&(tablet_device, _)| tablet_device == device)
+            .map(|&(_, tablet)| tablet)
+    }
+
+    fn pad(&self, device: *mut wlr_input_device) -> Option<*mut wlr_tablet_v2_tablet_pad> {
+        self.pads.iter().find(|pad| pad.device == device).map(|pad| pad.pad)
+    }
+
+    /// Get the tool, announcing it to the clients the first time it's used.
+    fn tool(&mut self, seat: *mut wlr_seat, tool: *mut wlr_tablet_tool) -> Option<&mut Tool> {
+        let index = match self.tools.iter().position(|t| t.tool == tool) {
+            Some(index) => index,
+            None => {
+                let tablet_tool = unsafe { wlr_tablet_tool_create(self.manager, seat, tool) };
+                if tablet_tool.is_null() {
+                    error!("Could not create the tablet tool");
+                    return None
+                }
+                // The tool is forgotten about once libinput forgets about it.
+                unsafe {
+                    add_listener(&mut (*tool).events.destroy as *mut _ as *mut _, tool_destroyed);
+                }
+                self.tools.push(Tool { tool,
+                                       tablet_tool,
+                                       position: (0.0, 0.0),
+                                       layout_position: (0.0, 0.0),
+                                       tilt: (0.0, 0.0),
+                                       focus: None,
+                                       tip_down: false });
+                self.tools.len() - 1
+            }
+        };
+        Some(&mut self.tools[index])
+    }
+}
+
+pub struct TabletTool;
+
+impl TabletToolHandler for TabletTool {
+    fn on_proximity(&mut self,
+                    compositor: CompositorHandle,
+                    tablet: TabletToolHandle,
+                    event: &ProximityEvent) {
+        with_handles!([(compositor: {compositor}), (tablet: {tablet})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let server: &mut ::Server = compositor.into();
+            if event.state == wlr_tablet_tool_proximity_state::WLR_TABLET_TOOL_PROXIMITY_IN {
+                tool_moved(server,
+                           tablet.input_device(),
+                           event.tool,
+                           event.time_msec,
+                           Some(event.x),
+                           Some(event.y));
+            } else {
+                tool_left(server, event.tool, event.time_msec);
+            }
+        }).unwrap();
+    }
+
+    fn on_axis(&mut self,
+               compositor: CompositorHandle,
+               tablet: TabletToolHandle,
+               event: &AxisEvent) {
+        with_handles!([(compositor: {compositor}), (tablet: {tablet})] => {
+            let event = unsafe { &*event.as_ptr() };
+            tool_axis(compositor.into(), tablet.input_device(), event);
+        }).unwrap();
+    }
+
+    fn on_tip(&mut self,
+              compositor: CompositorHandle,
+              tablet: TabletToolHandle,
+              event: &TipEvent) {
+        with_handles!([(compositor: {compositor}), (tablet: {tablet})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let server: &mut ::Server = compositor.into();
+            tool_moved(server,
+                       tablet.input_device(),
+                       event.tool,
+                       event.time_msec,
+                       Some(event.x),
+                       Some(event.y));
+            let down = event.state == wlr_tablet_tool_tip_state::WLR_TABLET_TOOL_TIP_DOWN;
+            tool_tip(server, event.tool, event.time_msec, down);
+        }).unwrap();
+    }
+
+    fn on_button(&mut self,
+                 compositor: CompositorHandle,
+                 _: TabletToolHandle,
+                 event: &tablet_tool_events::ButtonEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let pressed = event.state == wlr_button_state::WLR_BUTTON_PRESSED;
+            tool_button(compositor.into(), event.tool, event.time_msec, event.button, pressed);
+        }).unwrap();
+    }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, tablet: TabletToolHandle) {
+        with_handles!([(compositor: {compositor}), (tablet: {tablet})] => {
+            let server: &mut ::Server = compositor.into();
+            let weak_reference = tablet.weak_reference();
+            server.tablet_tools.retain(|t| *t != weak_reference);
+            server.tablets.remove_device(tablet.input_device().as_ptr());
+            with_handles!([(cursor: {&mut server.cursor})] => {
+                cursor.deattach_input_device(tablet.input_device());
+            }).expect("Cursor was destroyed");
+        }).unwrap();
+    }
+}
+
+pub struct TabletPad;
+
+impl TabletPadHandler for TabletPad {
+    fn on_button(&mut self,
+                 compositor: CompositorHandle,
+                 pad: TabletPadHandle,
+                 event: &tablet_pad_events::ButtonEvent) {
+        with_handles!([(compositor: {compositor}), (pad: {pad})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let server: &mut ::Server = compositor.into();
+            let state = if event.state == wlr_button_state::WLR_BUTTON_PRESSED {
+                zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_PRESSED
+            } else {
+                zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_RELEASED
+            };
+            if let Some(pad) = server.tablets.pad(pad.input_device().as_ptr()) {
+                unsafe {
+                    wlr_send_tablet_v2_tablet_pad_button(pad,
+                                                         event.button as _,
+                                                         event.time_msec,
+                                                         state);
+                }
+            }
+        }).unwrap();
+    }
+
+    fn on_ring(&mut self, compositor: CompositorHandle, pad: TabletPadHandle, event: &RingEvent) {
+        with_handles!([(compositor: {compositor}), (pad: {pad})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let server: &mut ::Server = compositor.into();
+            let finger =
+                event.source == wlr_tablet_pad_ring_source::WLR_TABLET_PAD_RING_SOURCE_FINGER;
+            if let Some(pad) = server.tablets.pad(pad.input_device().as_ptr()) {
+                unsafe {
+                    wlr_send_tablet_v2_tablet_pad_ring(pad,
+                                                       event.ring,
+                                                       event.position,
+                                                       finger,
+                                                       event.time_msec);
+                }
+            }
+        }).unwrap();
+    }
+
+    fn on_strip(&mut self,
+                compositor: CompositorHandle,
+                pad: TabletPadHandle,
+                event: &StripEvent) {
+        with_handles!([(compositor: {compositor}), (pad: {pad})] => {
+            let event = unsafe { &*event.as_ptr() };
+            let server: &mut ::Server = compositor.into();
+            let finger =
+                event.source == wlr_tablet_pad_strip_source::WLR_TABLET_PAD_STRIP_SOURCE_FINGER;
+            if let Some(pad) = server.tablets.pad(pad.input_device().as_ptr()) {
+                unsafe {
+                    wlr_send_tablet_v2_tablet_pad_strip(pad,
+                                                        event.strip,
+                                                        event.position,
+                                                        finger,
+                                                        event.time_msec);
+                }
+            }
+        }).unwrap();
+    }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, pad: TabletPadHandle) {
+        with_handles!([(compositor: {compositor}), (pad: {pad})] => {
+            let server: &mut ::Server = compositor.into();
+            let weak_reference = pad.weak_reference();
+            server.tablet_pads.retain(|p| *p != weak_reference);
+            server.tablets.remove_device(pad.input_device().as_ptr());
+        }).unwrap();
+    }
+}
+
+/// Announce a new tablet to the clients and let it move the cursor.
+pub fn tablet_tool_added(server: &mut ::Server, tablet: &mut TabletToolHandle) {
+    let seat = server.seat.seat.run(|seat| seat.as_ptr()).expect("Seat was destroyed");
+    {
+        let ::Server { ref config,
+                       ref mut tablets,
+                       ref mut cursor,
+                       .. } = *server;
+        with_handles!([(tablet: {&mut *tablet}), (cursor: {cursor})] => {
+            ::configure_input_device(config, tablet.input_device());
+            tablets.add_tablet(seat, tablet.input_device().as_ptr());
+            cursor.attach_input_device(tablet.input_device());
+        }).unwrap();
+    }
+    server.tablet_tools.push(tablet.clone());
+    ::map_inputs_to_outputs(server);
+}
+
+/// Announce a new tablet pad to the clients.
+pub fn tablet_pad_added(server: &mut ::Server, pad: &mut TabletPadHandle) {
+    let seat = server.seat.seat.run(|seat| seat.as_ptr()).expect("Seat was destroyed");
+    {
+        let ::Server { ref config,
+                       ref mut tablets,
+                       .. } = *server;
+        with_handles!([(pad: {&mut *pad})] => {
+            ::configure_input_device(config, pad.input_device());
+            tablets.add_pad(seat, pad.input_device().as_ptr());
+        }).unwrap();
+    }
+    server.tablet_pads.push(pad.clone());
+}
+
+/// Move the tool to the position on the tablet, from 0 to 1. Coordinates
+/// that are `None` didn't change.
+///
+/// The tool moves to the surface underneath it, unless its tip is down, in
+/// which case it stays with the surface the tip went down on.
+fn tool_moved(server: &mut ::Server,
+              device: &InputDevice,
+              tool: *mut wlr_tablet_tool,
+              time_msec: u32,
+              x: Option<f64>,
+              y: Option<f64>) {
+    let seat = server.seat.seat.run(|seat| seat.as_ptr()).expect("Seat was destroyed");
+    let tablet = match server.tablets.tablet(device.as_ptr()) {
+        Some(tablet) => tablet,
+        None => return
+    };
+    let ((x, y), old_focus, tip_down) = match server.tablets.tool(seat, tool) {
+        Some(tool) => {
+            tool.position = (x.unwrap_or(tool.position.0), y.unwrap_or(tool.position.1));
+            (tool.position, tool.focus.clone(), tool.tip_down)
+        }
+        None => return
+    };
+    let (lx, ly) = server.cursor
+                         .run(|cursor| cursor.absolute_to_layout_coords(device, x, y))
+                         .unwrap();
+    if let Some(tool) = server.tablets.tool(seat, tool) {
+        tool.layout_position = (lx, ly);
+    }
+    let old_focus = old_focus.filter(Focus::is_alive);
+    let focus = match old_focus {
+        Some(ref focus) if tip_down => focus.clone(),
+        _ => focus_at(server, tablet, lx, ly)
+    };
+    if old_focus.as_ref() != Some(&focus) {
+        leave_focus(server, tool);
+        if let Focus::Surface { ref surface, .. } = focus {
+            enter_surface(server, tablet, tool, surface);
+        }
+        if let Some(tool) = server.tablets.tool(seat, tool) {
+            tool.focus = Some(focus.clone());
+        }
+    }
+    match focus {
+        Focus::Surface { offset, .. } => {
+            if let Some(tool) = server.tablets.tool(seat, tool) {
+                unsafe {
+                    wlr_send_tablet_v2_tablet_tool_motion(tool.tablet_tool,
+                                                          lx - offset.0,
+                                                          ly - offset.1);
+                }
+            }
+        }
+        Focus::Pointer => {
+            server.cursor.run(|cursor| cursor.warp(None, lx, ly)).unwrap();
+            ::pointer_moved(server, Some(time_msec));
+        }
+    }
+}
+
+/// Where the events of a tool at the position in output layout coordinates
+/// go.
+fn focus_at(server: &mut ::Server, tablet: *mut wlr_tablet_v2_tablet, lx: f64, ly: f64) -> Focus {
+    let (surface, sx, sy) = ::Seat::surface_at(&mut server.views, &server.layer_surfaces, lx, ly);
+    let surface = match surface {
+        Some(surface) => surface,
+        None => return Focus::Pointer
+    };
+    let accepts = surface.run(|surface| unsafe {
+                             wlr_surface_accepts_tablet_v2(tablet, surface.as_ptr())
+                         })
+                         .unwrap_or(false);
+    if accepts {
+        Focus::Surface { surface,
+                         offset: (lx - sx, ly - sy) }
+    } else {
+        Focus::Pointer
+    }
+}
+
+/// Send the tool into the surface, along with the pads so that their
+/// buttons go to the same client.
+fn enter_surface(server: &mut ::Server,
+                 tablet: *mut wlr_tablet_v2_tablet,
+                 tool: *mut wlr_tablet_tool,
+                 surface: &SurfaceHandle) {
+    let surface_ptr = match surface.run(|surface| surface.as_ptr()) {
+        Ok(surface_ptr) => surface_ptr,
+        Err(_) => return
+    };
+    if let Some(tool) = server.tablets.tools.iter().find(|t| t.tool == tool) {
+        unsafe {
+            wlr_send_tablet_v2_tablet_tool_proximity_in(tool.tablet_tool, tablet, surface_ptr)
+        }
+    }
+    for pad in &mut server.tablets.pads {
+        // A pad that was in a surface that has been destroyed since has to
+        // enter again, even if the new surface got the same address.
+        let entered = match pad.surface {
+            Some(ref pad_surface) => pad_surface == surface && pad_surface.run(|_| ()).is_ok(),
+            None => false
+        };
+        if !entered {
+            unsafe { wlr_send_tablet_v2_tablet_pad_enter(pad.pad, tablet, surface_ptr) };
+            pad.surface = Some(surface.clone());
+        }
+    }
+}
+
+/// Take the tool out of the surface it is over, if any.
+fn leave_focus(server: &mut ::Server, tool: *mut wlr_tablet_tool) {
+    if let Some(tool) = server.tablets.tools.iter_mut().find(|t| t.tool == tool) {
+        if let Some(Focus::Surface { .. }) = tool.focus.take() {
+            unsafe { wlr_send_tablet_v2_tablet_tool_proximity_out(tool.tablet_tool) }
+        }
+    }
+}
+
+/// Handle the tool leaving the proximity of the tablet.
+///
+/// If the tip was down on the pointer, the left button it pressed is
+/// released, or it would stay pressed.
+fn tool_left(server: &mut ::Server, tool: *mut wlr_tablet_tool, time_msec: u32) {
+    let pressed = match server.tablets.tools.iter_mut().find(|t| t.tool == tool) {
+        Some(tool) => {
+            let pressed = tool.tip_down && tool.focus == Some(Focus::Pointer);
+            tool.tip_down = false;
+            pressed
+        }
+        None => false
+    };
+    leave_focus(server, tool);
+    if pressed {
+        ::pointer_button(server, time_msec, BTN_LEFT, false);
+    }
+}
+
+fn tool_axis(server: &mut ::Server, device: &InputDevice, event: &wlr_event_tablet_tool_axis) {
+    let axes = event.updated_axes;
+    if axes & (AXIS_X | AXIS_Y) != 0 {
+        tool_moved(server,
+                   device,
+                   event.tool,
+                   event.time_msec,
+                   Some(event.x).filter(|_| axes & AXIS_X != 0),
+                   Some(event.y).filter(|_| axes & AXIS_Y != 0));
+    }
+    let tool = match server.tablets.tools.iter_mut().find(|t| t.tool == event.tool) {
+        Some(tool) => tool,
+        None => return
+    };
+    // Only the axes that changed are in the event, the other one of a pair
+    // keeps its last value.
+    if axes & AXIS_TILT_X != 0 {
+        tool.tilt.0 = event.tilt_x;
+    }
+    if axes & AXIS_TILT_Y != 0 {
+        tool.tilt.1 = event.tilt_y;
+    }
+    match tool.focus {
+        Some(Focus::Surface { .. }) => {}
+        _ => return
+    }
+    let tablet_tool = tool.tablet_tool;
+    unsafe {
+        if axes & AXIS_DISTANCE != 0 {
+            wlr_send_tablet_v2_tablet_tool_distance(tablet_tool, event.distance);
+        }
+        if axes & AXIS_PRESSURE != 0 {
+            wlr_send_tablet_v2_tablet_tool_pressure(tablet_tool, event.pressure);
+        }
+        if axes & (AXIS_TILT_X | AXIS_TILT_Y) != 0 {
+            wlr_send_tablet_v2_tablet_tool_tilt(tablet_tool, tool.tilt.0, tool.tilt.1);
+        }
+        if axes & AXIS_ROTATION != 0 {
+            wlr_send_tablet_v2_tablet_tool_rotation(tablet_tool, event.rotation);
+        }
+        if axes & AXIS_SLIDER != 0 {
+            wlr_send_tablet_v2_tablet_tool_slider(tablet_tool, event.slider);
+        }
+        if axes & AXIS_WHEEL != 0 {
+            wlr_send_tablet_v2_tablet_tool_wheel(tablet_tool, event.wheel_delta, 0);
+        }
+    }
+}
+
+/// Handle the tip of the tool touching the tablet or being lifted from it.
+fn tool_tip(server: &mut ::Server, tool: *mut wlr_tablet_tool, time_msec: u32, down: bool) {
+    let focus = match server.tablets.tools.iter_mut().find(|t| t.tool == tool) {
+        Some(tool) => {
+            tool.tip_down = down;
+            tool.focus.clone().map(|focus| (focus, tool.tablet_tool, tool.layout_position))
+        }
+        None => None
+    };
+    match focus {
+        Some((Focus::Surface { .. }, tablet_tool, (lx, ly))) => {
+            if down {
+                // Touching a view with the tool focuses it, like clicking it.
+                focus_view_at(server, lx, ly);
+                unsafe { wlr_send_tablet_v2_tablet_tool_down(tablet_tool) }
+            } else {
+                unsafe { wlr_send_tablet_v2_tablet_tool_up(tablet_tool) }
+            }
+        }
+        Some((Focus::Pointer, _, _)) => ::pointer_button(server, time_msec, BTN_LEFT, down),
+        None => {}
+    }
+}
+
+fn tool_button(server: &mut ::Server,
+               tool: *mut wlr_tablet_tool,
+               time_msec: u32,
+               button: u32,
+               pressed: bool) {
+    let focus = server.tablets
+                      .tools
+                      .iter()
+                      .find(|t| t.tool == tool)
+                      .and_then(|tool| tool.focus.clone().map(|focus| (focus, tool.tablet_tool)));
+    match focus {
+        Some((Focus::Surface { .. }, tablet_tool)) => {
+            let state = if pressed {
+                zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_PRESSED
+            } else {
+                zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_RELEASED
+            };
+            unsafe { wlr_send_tablet_v2_tablet_tool_button(tablet_tool, button, state) }
+        }
+        Some((Focus::Pointer, _)) => ::pointer_button(server, time_msec, button, pressed),
+        None => {}
+    }
+}
+
+fn focus_view_at(server: &mut ::Server, lx: f64, ly: f64) {
+    {
+        let ::Server { ref mut seat,
+                       ref mut views,
+                       .. } = *server;
+        if let (Some(view), _, _, _) = ::Seat::view_at(views, lx, ly) {
+            if view.wants_focus() {
+                seat.focus_view(view, views);
+            }
+        }
+    }
+    ::damage_changes(server);
+}
+
+unsafe extern "C" fn tool_destroyed(listener: *mut wl_listener, data: *mut c_void) {
+    let tool = data as *mut wlr_tablet_tool;
+    remove_listener(listener);
+    if let Some(compositor) = wlroots::compositor_handle() {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.tablets.tools.retain(|t| t.tool != tool);
+        }).ok();
+    }
+}
@@ -0,0 +1,160 @@
+//! Touchscreens.
+//!
+//! A touch point that goes down on a view is sent to the surface underneath
+//! it with `wl_touch`. Anywhere else, e.g. on a panel or the wallpaper, the
+//! touch point moves the pointer and holds down the left button instead.
+
+use std::time::Duration;
+
+use wlroots::{pointer_events::BTN_LEFT, touch_events::*, Capability, CompositorHandle,
+              InputDevice, Origin, TouchHandle, TouchHandler};
+
+#[derive(Debug, Default)]
+pub struct Touch;
+
+impl TouchHandler for Touch {
+    fn on_down(&mut self, compositor: CompositorHandle, _: TouchHandle, event: &DownEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let (x, y) = event.position();
+            let (lx, ly) = layout_coords(server, event.device(), x, y);
+            touch_down(server, event.time_msec(), event.touch_id(), lx, ly);
+        }).unwrap();
+    }
+
+    fn on_motion(&mut self, compositor: CompositorHandle, _: TouchHandle, event: &MotionEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let (x, y) = event.position();
+            let (lx, ly) = layout_coords(server, event.device(), x, y);
+            touch_motion(server, event.time_msec(), event.touch_id(), lx, ly);
+        }).unwrap();
+    }
+
+    fn on_up(&mut self, compositor: CompositorHandle, _: TouchHandle, event: &UpEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            touch_up(compositor.into(), event.time_msec(), event.touch_id());
+        }).unwrap();
+    }
+
+    fn on_cancel(&mut self, compositor: CompositorHandle, _: TouchHandle, event: &CancelEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            touch_up(compositor.into(), event.time_msec(), event.touch_id());
+        }).unwrap();
+    }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, touch: TouchHandle) {
+        with_handles!([(compositor: {compositor}), (touch: {touch})] => {
+            let server: &mut ::Server = compositor.into();
+            let weak_reference = touch.weak_reference();
+            server.touches.retain(|t| *t != weak_reference);
+            if server.touches.is_empty() {
+                with_handles!([(seat: {&mut server.seat.seat})] => {
+                    let mut capabilities = seat.capabilities();
+                    capabilities.remove(Capability::Touch);
+                    seat.set_capabilities(capabilities);
+                }).expect("Seat was destroyed")
+            }
+            with_handles!([(cursor: {&mut server.cursor})] => {
+                cursor.deattach_input_device(touch.input_device());
+            }).expect("Cursor was destroyed");
+        }).unwrap();
+    }
+}
+
+/// Convert the position of a touch event, from 0 to 1 on the device, to
+/// output layout coordinates on the output the device is mapped to.
+fn layout_coords(server: &mut ::Server,
+                 device: &InputDevice,
+                 x: f64,
+                 y: f64)
+                 -> (f64, f64) {
+    server.cursor
+          .run(|cursor| cursor.absolute_to_layout_coords(device, x, y))
+          .unwrap()
+}
+
+/// Handle a touch point going down at the position in output layout
+/// coordinates.
+pub fn touch_down(server: &mut ::Server, time_msec: u32, touch_id: i32, lx: f64, ly: f64) {
+    let on_panel = ::layer_surface_at(&server.layer_surfaces, &::LAYERS_ABOVE_VIEWS, lx, ly)
+        .is_some();
+    let target = if on_panel {
+        (None, None, 0.0, 0.0)
+    } else {
+        ::Seat::view_at(&mut server.views, lx, ly)
+    };
+    match target {
+        (Some(view), Some(surface), sx, sy) => {
+            {
+                let ::Server { ref mut seat,
+                               ref mut views,
+                               .. } = *server;
+                if view.wants_focus() {
+                    seat.focus_view(view.clone(), views);
+                }
+                let offset = Origin::new((lx - sx) as i32, (ly - sy) as i32);
+                seat.touch_points.insert(touch_id, ::TouchPoint::Surface { offset });
+                dehandle!(
+                    @seat = {&seat.seat};
+                    @surface = {surface};
+                    seat.touch_notify_down(surface,
+                                           Duration::from_millis(time_msec as _),
+                                           touch_id,
+                                           sx,
+                                           sy)
+                );
+            }
+            ::damage_changes(server);
+        }
+        _ => {
+            // Only one touch point can drive the pointer at a time.
+            if server.seat.touch_points.values().any(|point| *point == ::TouchPoint::Pointer) {
+                return
+            }
+            server.seat.touch_points.insert(touch_id, ::TouchPoint::Pointer);
+            move_pointer(server, time_msec, lx, ly);
+            ::pointer_button(server, time_msec, BTN_LEFT, true);
+        }
+    }
+}
+
+/// Handle a touch point moving to the position in output layout coordinates.
+pub fn touch_motion(server: &mut ::Server, time_msec: u32, touch_id: i32, lx: f64, ly: f64) {
+    match server.seat.touch_points.get(&touch_id).cloned() {
+        Some(::TouchPoint::Surface { offset }) => {
+            dehandle!(
+                @seat = {&server.seat.seat};
+                seat.touch_notify_motion(Duration::from_millis(time_msec as _),
+                                         touch_id,
+                                         lx - offset.x as f64,
+                                         ly - offset.y as f64)
+            );
+            // A drag and drop icon follows the touch point that drags it.
+            if !server.seat.drag_icons.is_empty() {
+                ::damage_changes(server);
+            }
+        }
+        Some(::TouchPoint::Pointer) => move_pointer(server, time_msec, lx, ly),
+        None => {}
+    }
+}
+
+/// Handle a touch point being lifted, or cancelled.
+pub fn touch_up(server: &mut ::Server, time_msec: u32, touch_id: i32) {
+    match server.seat.touch_points.remove(&touch_id) {
+        Some(::TouchPoint::Surface { .. }) => {
+            dehandle!(
+                @seat = {&server.seat.seat};
+                seat.touch_notify_up(Duration::from_millis(time_msec as _), touch_id)
+            );
+        }
+        Some(::TouchPoint::Pointer) => ::pointer_button(server, time_msec, BTN_LEFT, false),
+        None => {}
+    }
+}
+
+fn move_pointer(server: &mut ::Server, time_msec: u32, lx: f64, ly: f64) {
+    server.cursor.run(|cursor| cursor.warp(None, lx, ly)).unwrap();
+    ::pointer_moved(server, Some(time_msec));
+}
@@ -33,36 +33,60 @@
 
 extern crate env_logger;
 extern crate getopts;
+extern crate input as libinput;
 #[macro_use]
 extern crate log;
 extern crate nix;
+#[cfg(test)]
+extern crate tempfile;
+#[cfg(test)]
+extern crate wayland_client;
+#[macro_use]
+extern crate wayland_sys;
 #[macro_use]
 pub(crate) extern crate wlroots;
 
+mod config;
 mod cursor;
+mod ffi;
 mod input;
 mod output;
+mod privileged;
 mod seat;
+mod selection;
+mod session;
 mod shells;
 mod view;
+mod wayland_protocols;
+mod window_management;
 mod xwayland;
 
+#[cfg(test)]
+mod tests;
+
+pub use self::config::*;
 pub use self::cursor::*;
 pub use self::input::*;
 pub use self::output::*;
+pub use self::privileged::*;
 pub use self::seat::*;
+pub use self::selection::*;
+pub use self::session::*;
 pub use self::shells::*;
 pub use self::view::*;
+pub use self::window_management::*;
 pub use self::xwayland::*;
 
-use std::{rc::Rc, env, fs::File, io::{self, BufRead, BufReader, Write},
-          path::Path, process::exit};
+use std::{rc::Rc, collections::HashMap, env, fs::File, io::{self, BufRead, BufReader, Write},
+          os::raw::{c_int, c_void}, path::{Path, PathBuf}, process::exit, ptr};
 
 use log::Level;
 use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet};
+use wayland_sys::server::*;
 
-use wlroots::{Compositor, CompositorBuilder, Cursor, CursorHandle, KeyboardHandle,
-              OutputHandle, OutputLayout, OutputLayoutHandle, PointerHandle, XCursorManager};
+use wlroots::{Area, Compositor, CompositorBuilder, Cursor, CursorHandle, KeyboardHandle,
+              OutputHandle, OutputLayout, OutputLayoutHandle, PointerHandle, SurfaceHandle,
+              TabletPadHandle, TabletToolHandle, TouchHandle, XCursorManager};
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 const GIT_VERSION: &'static str = include_str!(concat!(env!("OUT_DIR"), "/git-version.txt"));
@@ -77,8 +101,26 @@ pub struct Server {
     pub cursor: CursorHandle,
     pub keyboards: Vec<KeyboardHandle>,
     pub pointers: Vec<PointerHandle>,
+    pub touches: Vec<TouchHandle>,
+    pub tablet_tools: Vec<TabletToolHandle>,
+    pub tablet_pads: Vec<TabletPadHandle>,
     pub outputs: Vec<OutputHandle>,
-    pub views: Vec<Rc<View>>
+    pub views: Vec<Rc<View>>,
+    pub layer_surfaces: Vec<Rc<LayerSurface>>,
+    /// The area of each output that isn't taken by the exclusive zones of
+    /// layer surfaces, in output layout coordinates.
+    pub usable_areas: HashMap<OutputHandle, Area>,
+    /// The areas of everything that was drawn, used to find what to damage.
+    pub scene: Vec<Area>,
+    /// The outputs that each drawn surface was last told it is on.
+    pub surface_outputs: Vec<(SurfaceHandle, Vec<OutputHandle>)>,
+    pub window_management: WindowManagement,
+    pub config: Config,
+    pub session: Session,
+    pub tablets: Tablets,
+    pub output_management: OutputManagement,
+    pub decorations: Decorations,
+    pub selection: Selection
 }
 
 impl Default for Server {
@@ -96,13 +138,27 @@ impl Default for Server {
                  cursor: CursorHandle::default(),
                  keyboards: Vec::default(),
                  pointers: Vec::default(),
+                 touches: Vec::default(),
+                 tablet_tools: Vec::default(),
+                 tablet_pads: Vec::default(),
                  outputs: Vec::default(),
-                 views: Vec::default() }
+                 views: Vec::default(),
+                 layer_surfaces: Vec::default(),
+                 usable_areas: HashMap::default(),
+                 scene: Vec::default(),
+                 surface_outputs: Vec::default(),
+                 window_management: WindowManagement::default(),
+                 config: Config::default(),
+                 session: Session::default(),
+                 tablets: Tablets::default(),
+                 output_management: OutputManagement::default(),
+                 decorations: Decorations::default(),
+                 selection: Selection::default() }
     }
 }
 
 impl Server {
-    pub fn new(layout: OutputLayoutHandle, cursor: CursorHandle) -> Self {
+    pub fn new(layout: OutputLayoutHandle, cursor: CursorHandle, config: Config) -> Self {
         let mut xcursor_manager =
             XCursorManager::create("default".to_string(), 24).expect("Could not create xcursor \
                                                                       manager");
@@ -116,6 +172,7 @@ impl Server {
         Server { xcursor_manager,
                  layout,
                  cursor,
+                 config,
                  ..Server::default() }
     }
 }
@@ -125,6 +182,11 @@ compositor_data!(Server);
 fn main() {
     let mut opts = getopts::Options::new();
     let matches = match opts.optflag("", "version", "show version information")
+                            .optopt("c", "config", "use a different configuration file", "PATH")
+                            .optopt("",
+                                    "window-manager",
+                                    "start a different window manager than awesome",
+                                    "PROGRAM")
                             .parse(env::args().skip(1)) {
         Ok(m) => m,
         Err(f) => {
@@ -155,34 +217,109 @@ fn main() {
     detect_proprietary();
     detect_raspi();
     ensure_good_env();
-    let compositor = setup_compositor();
+    let config = Config::load(matches.opt_str("config").map(PathBuf::from));
+    let window_manager = matches.opt_str("window-manager").unwrap_or("awesome".into());
+    let mut compositor = setup_compositor(config);
     assert!(compositor.xwayland.is_some());
+    spawn_window_manager(&mut compositor, &window_manager);
     compositor.run();
 }
 
-pub fn setup_compositor() -> Compositor {
+pub fn setup_compositor(config: Config) -> Compositor {
+    init_compositor(config, |builder, server| builder.build_auto(server))
+}
+
+/// Set up the compositor, leaving the choice of backend to `build`.
+pub fn init_compositor<F>(config: Config, build: F) -> Compositor
+    where F: FnOnce(CompositorBuilder, Server) -> Compositor
+{
     let layout = OutputLayout::create(Box::new(OutputLayoutManager::new()));
     let cursor = Cursor::create(Box::new(CursorManager::new()));
-    let mut compositor = CompositorBuilder::new().gles2(true)
-                                                 .data_device(true)
-                                                 .output_manager(Box::new(OutputManager::new()))
-                                                 .input_manager(Box::new(InputManager::new()))
-                                                 .xwayland(Box::new(XWaylandManager::new()))
-                                                 .xdg_shell_v6_manager(Box::new(XdgV6ShellManager))
-                                                 .xdg_shell_manager(Box::new(XdgShellManager))
-                                                 .build_auto(Server::new(layout, cursor));
+    let builder = CompositorBuilder::new().gles2(true)
+                                          .data_device(true)
+                                          .output_manager(Box::new(OutputManager::new()))
+                                          .input_manager(Box::new(InputManager::new()))
+                                          .xwayland(Box::new(XWaylandManager::new()))
+                                          .xdg_shell_v6_manager(Box::new(XdgV6ShellManager))
+                                          .xdg_shell_manager(Box::new(XdgShellManager))
+                                          .layer_shell_manager(Box::new(LayerShellManager));
+    let mut compositor = build(builder, Server::new(layout, cursor, config));
     // NOTE We need to create this afterwards because it needs the compositor
     // running to announce the seat.
     let seat = wlroots::Seat::create(&mut compositor,
                                      "seat0".into(),
                                      Box::new(SeatManager::new()));
+    let window_management = WindowManagement::new(compositor.display as *mut _);
+    let session = Session::new(&mut compositor);
+    let tablets = Tablets::new(compositor.display as *mut _);
+    let output_management = OutputManagement::new(compositor.display as *mut _);
+    create_screencopy_manager(compositor.display as *mut _);
+    let decorations = Decorations::new(compositor.display as *mut _);
+    let xwayland = compositor.xwayland
+                             .as_mut()
+                             .map(|xwayland| xwayland.as_ptr())
+                             .unwrap_or(ptr::null_mut());
+    let selection = Selection::new(compositor.display as *mut _,
+                                   seat.run(|seat| seat.as_ptr()).expect("Seat was destroyed"),
+                                   xwayland);
+    let globals = PrivilegedGlobals { window_management: window_management.global(),
+                                      data_control: selection.data_control };
+    filter_privileged_globals(compositor.display as *mut _, globals);
     {
         let server: &mut Server = (&mut compositor).into();
         server.seat = Seat::new(seat);
+        server.window_management = window_management;
+        server.session = session;
+        server.tablets = tablets;
+        server.output_management = output_management;
+        server.decorations = decorations;
+        server.selection = selection;
+    }
+    unsafe {
+        let event_loop = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                       wl_display_get_event_loop,
+                                       compositor.display as *mut _);
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_event_loop_add_signal,
+                      event_loop,
+                      signal::SIGHUP as c_int,
+                      sighup_handle,
+                      ptr::null_mut());
     }
     compositor
 }
 
+/// Apply the configuration again, so it can be changed without restarting.
+pub fn reload_config(server: &mut Server) {
+    info!("Reloading the configuration");
+    server.config = server.config.reload();
+    {
+        let Server { ref config,
+                     ref mut keyboards,
+                     ref mut pointers,
+                     ref mut touches,
+                     ref mut tablet_tools,
+                     ref mut tablet_pads,
+                     .. } = *server;
+        for keyboard in keyboards.iter_mut() {
+            config.apply_keyboard(keyboard);
+        }
+        for pointer in pointers.iter_mut() {
+            pointer.run(|pointer| configure_input_device(config, pointer.input_device())).ok();
+        }
+        for touch in touches.iter_mut() {
+            touch.run(|touch| configure_input_device(config, touch.input_device())).ok();
+        }
+        for tablet in tablet_tools.iter_mut() {
+            tablet.run(|tablet| configure_input_device(config, tablet.input_device())).ok();
+        }
+        for pad in tablet_pads.iter_mut() {
+            pad.run(|pad| configure_input_device(config, pad.input_device())).ok();
+        }
+    }
+    configure_outputs(server);
+}
+
 /// Formats the log strings properly
 fn log_format(buf: &mut env_logger::fmt::Formatter, record: &log::Record) -> Result<(), io::Error> {
     let color = match record.level() {
@@ -320,3 +457,13 @@ fn log_environment() {
 extern "C" fn sig_handle(_: nix::libc::c_int) {
     wlroots::terminate();
 }
+
+/// Handler for SIGHUP, called from the event loop.
+unsafe extern "C" fn sighup_handle(_: c_int, _: *mut c_void) -> c_int {
+    if let Some(compositor) = wlroots::compositor_handle() {
+        with_handles!([(compositor: {compositor})] => {
+            reload_config(compositor.into());
+        }).ok();
+    }
+    0
+}
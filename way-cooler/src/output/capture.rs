@@ -0,0 +1,183 @@
+//! Renders a single view into a shm buffer, e.g. for the thumbnails of a
+//! window switcher.
+//!
+//! The view is rendered into a framebuffer of its own, the size of the shm
+//! buffer, and read back from there. The outputs aren't touched, so this
+//! works outside of their frames and for views bigger than any of them.
+
+use std::{os::raw::{c_int, c_uint}, ptr, rc::Rc, slice};
+
+use wayland_sys::server::{wl_resource, wl_shm_buffer, WAYLAND_SERVER_HANDLE};
+use wlroots::{wlroots_sys::*, SurfaceHandle};
+
+// The shm formats that can be read back from the renderer.
+const WL_SHM_FORMAT_ARGB8888: u32 = 0;
+const WL_SHM_FORMAT_XRGB8888: u32 = 1;
+
+// The parts of OpenGL ES 2 that are needed for the framebuffer, which the
+// wlroots renderer doesn't provide.
+const GL_FRAMEBUFFER: c_uint = 0x8D40;
+const GL_RENDERBUFFER: c_uint = 0x8D41;
+const GL_RGBA8_OES: c_uint = 0x8058;
+const GL_COLOR_ATTACHMENT0: c_uint = 0x8CE0;
+const GL_FRAMEBUFFER_COMPLETE: c_uint = 0x8CD5;
+
+#[link(name = "GLESv2")]
+extern "C" {
+    fn glGenFramebuffers(n: c_int, framebuffers: *mut c_uint);
+    fn glDeleteFramebuffers(n: c_int, framebuffers: *const c_uint);
+    fn glBindFramebuffer(target: c_uint, framebuffer: c_uint);
+    fn glGenRenderbuffers(n: c_int, renderbuffers: *mut c_uint);
+    fn glDeleteRenderbuffers(n: c_int, renderbuffers: *const c_uint);
+    fn glBindRenderbuffer(target: c_uint, renderbuffer: c_uint);
+    fn glRenderbufferStorage(target: c_uint, format: c_uint, width: c_int, height: c_int);
+    fn glFramebufferRenderbuffer(target: c_uint,
+                                 attachment: c_uint,
+                                 renderbuffer_target: c_uint,
+                                 renderbuffer: c_uint);
+    fn glCheckFramebufferStatus(target: c_uint) -> c_uint;
+}
+
+/// Render the view, with all of its sub surfaces and popups, into the shm
+/// buffer, scaled to the size of the buffer.
+///
+/// Returns whether the view could be rendered.
+pub fn capture_view(backend: *mut wlr_backend,
+                    view: &Rc<::View>,
+                    buffer: *mut wl_resource)
+                    -> bool {
+    unsafe {
+        let shm_buffer = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_get, buffer);
+        if shm_buffer.is_null() {
+            return false
+        }
+        let width = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_get_width, shm_buffer);
+        let height = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_get_height, shm_buffer);
+        let stride = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_get_stride, shm_buffer);
+        let format = match ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                         wl_shm_buffer_get_format,
+                                         shm_buffer) {
+            WL_SHM_FORMAT_ARGB8888 => wl_shm_format::WL_SHM_FORMAT_ARGB8888,
+            WL_SHM_FORMAT_XRGB8888 => wl_shm_format::WL_SHM_FORMAT_XRGB8888,
+            _ => return false
+        };
+        let egl = wlr_backend_get_egl(backend);
+        let renderer = wlr_backend_get_renderer(backend);
+        if egl.is_null() || renderer.is_null() ||
+           !wlr_egl_make_current(egl, ptr::null_mut(), ptr::null_mut())
+        {
+            return false
+        }
+        let (mut framebuffer, mut renderbuffer) = (0, 0);
+        glGenFramebuffers(1, &mut framebuffer);
+        glGenRenderbuffers(1, &mut renderbuffer);
+        glBindRenderbuffer(GL_RENDERBUFFER, renderbuffer);
+        glRenderbufferStorage(GL_RENDERBUFFER, GL_RGBA8_OES, width, height);
+        glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+        glFramebufferRenderbuffer(GL_FRAMEBUFFER,
+                                  GL_COLOR_ATTACHMENT0,
+                                  GL_RENDERBUFFER,
+                                  renderbuffer);
+        // The renderbuffer can't be created if it's too big for the GPU.
+        let read = glCheckFramebufferStatus(GL_FRAMEBUFFER) == GL_FRAMEBUFFER_COMPLETE &&
+                   render_view(renderer, view, width, height, stride, format, shm_buffer);
+        glBindFramebuffer(GL_FRAMEBUFFER, 0);
+        glBindRenderbuffer(GL_RENDERBUFFER, 0);
+        glDeleteFramebuffers(1, &framebuffer);
+        glDeleteRenderbuffers(1, &renderbuffer);
+        read
+    }
+}
+
+/// Render the view into the bound framebuffer and read it into the shm
+/// buffer.
+unsafe fn render_view(renderer: *mut wlr_renderer,
+                      view: &Rc<::View>,
+                      width: i32,
+                      height: i32,
+                      stride: i32,
+                      format: wl_shm_format,
+                      shm_buffer: *mut wl_shm_buffer)
+                      -> bool {
+    wlr_renderer_begin(renderer, width, height);
+    wlr_renderer_scissor(renderer, ptr::null_mut());
+    wlr_renderer_clear(renderer, [0.0; 4].as_ptr());
+    let mut projection = [0.0; 9];
+    wlr_matrix_projection(projection.as_mut_ptr(),
+                          width,
+                          height,
+                          wl_output_transform::WL_OUTPUT_TRANSFORM_NORMAL);
+    // Client side decorations can include shadows around the window, which
+    // aren't part of what is captured.
+    let offset = view.shell.clone().geometry().map(|area| area.origin).unwrap_or_default();
+    let size = view.get_size();
+    let scale_x = width as f32 / size.width.max(1) as f32;
+    let scale_y = height as f32 / size.height.max(1) as f32;
+    let scaled = |value: i32, scale: f32| (value as f32 * scale).round() as i32;
+    view.for_each_surface(&mut |surface: SurfaceHandle, sx, sy| {
+        with_handles!([(surface: {surface})] => {
+            let texture = wlr_surface_get_texture(surface.as_ptr());
+            if texture.is_null() {
+                return
+            }
+            let (surface_width, surface_height) = surface.current_state().size();
+            let render_box = wlr_box { x: scaled(sx - offset.x, scale_x),
+                                       y: scaled(sy - offset.y, scale_y),
+                                       width: scaled(surface_width, scale_x),
+                                       height: scaled(surface_height, scale_y) };
+            let transform = wlr_output_transform_invert((*surface.as_ptr()).current.transform);
+            let mut matrix = [0.0; 9];
+            wlr_matrix_project_box(matrix.as_mut_ptr(),
+                                   &render_box,
+                                   transform,
+                                   0.0,
+                                   projection.as_ptr());
+            wlr_render_texture_with_matrix(renderer, texture, matrix.as_ptr(), 1.0);
+        }).ok();
+    });
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_begin_access, shm_buffer);
+    let data = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_get_data, shm_buffer);
+    let mut flags = 0;
+    let read = wlr_renderer_read_pixels(renderer,
+                                        format,
+                                        &mut flags,
+                                        stride as u32,
+                                        width as u32,
+                                        height as u32,
+                                        0,
+                                        0,
+                                        0,
+                                        0,
+                                        data);
+    if read && flags & WLR_RENDERER_READ_PIXELS_Y_INVERT as u32 != 0 {
+        let data = slice::from_raw_parts_mut(data as *mut u8, (stride * height) as usize);
+        flip_rows(data, stride as usize);
+    }
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_shm_buffer_end_access, shm_buffer);
+    wlr_renderer_end(renderer);
+    read
+}
+
+/// Turn the image with the stride upside down.
+fn flip_rows(data: &mut [u8], stride: usize) {
+    let height = data.len() / stride;
+    for row in 0..height / 2 {
+        let (top, bottom) = data.split_at_mut((height - row - 1) * stride);
+        top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rows_are_flipped() {
+        let mut image = [1, 1, 2, 2, 3, 3];
+        flip_rows(&mut image, 2);
+        assert_eq!(image, [3, 3, 2, 2, 1, 1]);
+        let mut image = [1, 2, 3, 4];
+        flip_rows(&mut image, 1);
+        assert_eq!(image, [4, 3, 2, 1]);
+    }
+}
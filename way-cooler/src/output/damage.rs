@@ -0,0 +1,283 @@
+//! Damage tracking for the outputs, so that only what changed is repainted.
+//!
+//! Surfaces damage what the client says changed when they commit. Anything
+//! that moved, was restacked, mapped or unmapped is found by comparing what is
+//! drawn now with what was drawn the last time the damage was updated.
+//!
+//! The cursor is damaged by wlroots itself.
+
+use std::cmp::max;
+use std::mem;
+use std::rc::Rc;
+
+use wlroots::{wlroots_sys::*, Area, CompositorHandle, Layer, Origin, Size, SurfaceHandle,
+              SurfaceHandler};
+
+/// Damages what changed in the surface every time it commits.
+pub struct SurfaceDamage;
+
+impl SurfaceHandler for SurfaceDamage {
+    fn on_commit(&mut self, compositor: CompositorHandle, surface: SurfaceHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            damage_surface(server, &surface);
+            damage_changes(server);
+            schedule_frame_callbacks(server, &surface);
+        }).unwrap();
+    }
+}
+
+/// Convert an area in output layout coordinates to the buffer coordinates of
+/// the output at the origin with the scale.
+///
+/// The edges are rounded outwards, so that with a fractional scale the area
+/// covers every pixel that it touches.
+pub fn output_area(output_origin: Origin, scale: f32, area: Area) -> Area {
+    scale_area(output_origin, scale, area, f32::floor, f32::ceil)
+}
+
+/// Convert an area in output layout coordinates to the buffer coordinates of
+/// the output at the origin with the scale, rounding the edges to the
+/// nearest pixel.
+///
+/// Unlike `output_area`, areas that touch still touch afterwards without
+/// overlapping, which is what surfaces are drawn at.
+pub fn output_render_box(output_origin: Origin, scale: f32, area: Area) -> Area {
+    scale_area(output_origin, scale, area, f32::round, f32::round)
+}
+
+fn scale_area(output_origin: Origin,
+              scale: f32,
+              area: Area,
+              round_start: fn(f32) -> f32,
+              round_end: fn(f32) -> f32)
+              -> Area {
+    let Area { origin: Origin { x, y },
+               size: Size { width, height } } = area;
+    let (x, y) = (x - output_origin.x, y - output_origin.y);
+    let left = round_start(x as f32 * scale) as i32;
+    let top = round_start(y as f32 * scale) as i32;
+    let right = round_end((x + width) as f32 * scale) as i32;
+    let bottom = round_end((y + height) as f32 * scale) as i32;
+    Area::new(Origin::new(left, top), Size::new(right - left, bottom - top))
+}
+
+/// Whether the two areas overlap.
+pub fn areas_intersect(first: Area, second: Area) -> bool {
+    let Area { origin: Origin { x: x1, y: y1 },
+               size: Size { width: w1, height: h1 } } = first;
+    let Area { origin: Origin { x: x2, y: y2 },
+               size: Size { width: w2, height: h2 } } = second;
+    x1 < x2 + w2 && x2 < x1 + w1 && y1 < y2 + h2 && y2 < y1 + h1
+}
+
+/// Damage the area, in output layout coordinates, on every output.
+pub fn damage_area(server: &mut ::Server, area: Area) {
+    let ::Server { ref mut layout,
+                   ref mut outputs,
+                   .. } = *server;
+    for output in outputs.iter_mut() {
+        with_handles!([(layout: {&mut *layout}), (output: {output})] => {
+            let output_origin = layout.get_box(&mut *output).origin;
+            let area = output_area(output_origin, output.scale(), area);
+            output.damage().add_box(area);
+        }).ok();
+    }
+}
+
+/// Damage every output completely.
+pub fn damage_whole(server: &mut ::Server) {
+    for output in server.outputs.iter_mut() {
+        with_handles!([(output: {output})] => {
+            output.damage().add_whole();
+        }).ok();
+    }
+}
+
+/// Damage everything that changed on screen since the last time this was
+/// called.
+pub fn damage_changes(server: &mut ::Server) {
+    let scene = scene(server);
+    let old_scene = mem::replace(&mut server.scene, scene.clone());
+    for i in 0..max(scene.len(), old_scene.len()) {
+        let (old, new) = (old_scene.get(i).cloned(), scene.get(i).cloned());
+        if old != new {
+            for area in old.into_iter().chain(new) {
+                damage_area(server, area);
+            }
+        }
+    }
+    ::update_surface_outputs(server);
+}
+
+/// Damage what the last commit of the surface changed, wherever it is drawn.
+///
+/// The whole surface is damaged if it changed its size.
+fn damage_surface(server: &mut ::Server, surface: &SurfaceHandle) {
+    let mut drawn_at = None;
+    for_each_drawn_surface(server, &mut |drawn: &SurfaceHandle, area| {
+        if *drawn == *surface {
+            drawn_at = Some(area);
+        }
+    });
+    let area = match drawn_at {
+        Some(area) => area,
+        None => return
+    };
+    let damage = with_handles!([(surface: {surface})] => {
+        unsafe { commit_damage(surface.as_ptr()) }
+    }).ok()
+      .and_then(|damage| damage);
+    match damage {
+        Some(damage) => {
+            for damaged in damage {
+                let Origin { x, y } = damaged.origin;
+                let origin = Origin::new(area.origin.x + x, area.origin.y + y);
+                damage_area(server, Area::new(origin, damaged.size));
+            }
+        }
+        None => damage_area(server, area)
+    }
+}
+
+/// Make the outputs that the surface is shown on draw a frame if its client
+/// is waiting for a frame callback, even if the commit damaged nothing.
+fn schedule_frame_callbacks(server: &::Server, surface: &SurfaceHandle) {
+    let waiting = with_handles!([(surface: {surface})] => {
+        unsafe {
+            let callbacks = &(*surface.as_ptr()).current.frame_callback_list;
+            callbacks.next as *const wl_list != callbacks as *const wl_list
+        }
+    }).unwrap_or(false);
+    if !waiting {
+        return
+    }
+    let ::Server { ref surface_outputs,
+                   .. } = *server;
+    let shown_on = surface_outputs.iter()
+                                  .filter(|&&(ref drawn, _)| drawn == surface)
+                                  .flat_map(|&(_, ref outputs)| outputs);
+    for output in shown_on {
+        with_handles!([(output: {output})] => {
+            unsafe { wlr_output_schedule_frame(output.as_ptr()) }
+        }).ok();
+    }
+}
+
+/// The buffer damage of the last commit of the surface, in surface local
+/// coordinates.
+///
+/// Returns `None` if the surface changed its size.
+unsafe fn commit_damage(surface: *mut wlr_surface) -> Option<Vec<Area>> {
+    let (current, previous) = (&(*surface).current, &(*surface).previous);
+    if current.width != previous.width || current.height != previous.height {
+        return None
+    }
+    let mut damage: pixman_region32_t = mem::zeroed();
+    pixman_region32_init(&mut damage);
+    wlr_region_transform(&mut damage,
+                         &mut (*surface).buffer_damage,
+                         wlr_output_transform_invert(current.transform),
+                         current.buffer_width,
+                         current.buffer_height);
+    wlr_region_scale(&mut damage, &mut damage, 1.0 / current.scale as f32);
+    let mut count = 0;
+    let rectangles = pixman_region32_rectangles(&mut damage, &mut count);
+    let areas = (0..count as isize).map(|index| *rectangles.offset(index))
+                                   .map(|rect| {
+                                       Area::new(Origin::new(rect.x1, rect.y1),
+                                                 Size::new(rect.x2 - rect.x1, rect.y2 - rect.y1))
+                                   })
+                                   .collect();
+    pixman_region32_fini(&mut damage);
+    Some(areas)
+}
+
+/// The areas of everything that is drawn, from the bottom up.
+fn scene(server: &::Server) -> Vec<Area> {
+    let mut scene = Vec::new();
+    for_each_drawn_surface(server, &mut |_, area| scene.push(area));
+    // The borders aren't surfaces, so they are added on their own.
+    for view in server.views.iter().filter(|view| !view.minimized.get()) {
+        scene.extend(view.border.get().area(view.geometry()));
+    }
+    scene
+}
+
+/// Iterate over every surface that is drawn, from the bottom up, with its
+/// area in output layout coordinates.
+pub fn for_each_drawn_surface(server: &::Server, f: &mut FnMut(&SurfaceHandle, Area)) {
+    let ::Server { ref views,
+                   ref layer_surfaces,
+                   ref seat,
+                   ref cursor,
+                   .. } = *server;
+    let mut drawn = |surface: SurfaceHandle, lx: i32, ly: i32| {
+        if let Some(area) = surface_area(&surface, lx, ly) {
+            f(&surface, area);
+        }
+    };
+    let draw_layers = |layers: &[Layer], drawn: &mut FnMut(SurfaceHandle, i32, i32)| {
+        for layer in layers {
+            let surfaces = layer_surfaces.iter()
+                                         .filter(|surface| surface.layer == *layer)
+                                         .filter(|surface| surface.mapped.get());
+            for layer_surface in surfaces {
+                let Origin { x, y } = layer_surface.area.get().origin;
+                layer_surface.for_each_surface(&mut |surface, sx, sy| {
+                    drawn(surface, x + sx, y + sy)
+                });
+            }
+        }
+    };
+    let draw_views = |views: &[Rc<::View>], drawn: &mut FnMut(SurfaceHandle, i32, i32)| {
+        for view in views.iter().rev().filter(|view| !view.minimized.get()) {
+            let Origin { x, y } = view.origin.get();
+            view.for_each_surface(&mut |surface, sx, sy| drawn(surface, x + sx, y + sy));
+        }
+    };
+    let (views_above, views_below) = views.split_at(::views_above_layers(views));
+    draw_layers(&::LAYERS_BELOW_VIEWS, &mut drawn);
+    draw_views(views_below, &mut drawn);
+    draw_layers(&::LAYERS_ABOVE_VIEWS, &mut drawn);
+    draw_views(views_above, &mut drawn);
+    // Drag icons are drawn above everything else.
+    for drag_icon in &seat.drag_icons {
+        if let Some((surface, Origin { x, y })) = drag_icon.drawn_at(seat, cursor) {
+            drawn(surface, x, y);
+        }
+    }
+}
+
+fn surface_area(surface: &SurfaceHandle, lx: i32, ly: i32) -> Option<Area> {
+    with_handles!([(surface: {surface})] => {
+        let (width, height) = surface.current_state().size();
+        Area::new(Origin::new(lx, ly), Size::new(width, height))
+    }).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fractional_scales_round_outwards_for_damage() {
+        let area = Area::new(Origin::new(1, 1), Size::new(1, 1));
+        // The area covers 1.5 to 3.0, which touches the second and third pixel.
+        assert_eq!(output_area(Origin::default(), 1.5, area),
+                   Area::new(Origin::new(1, 1), Size::new(2, 2)));
+        let area = Area::new(Origin::new(110, 0), Size::new(100, 10));
+        assert_eq!(output_area(Origin::new(100, 0), 1.25, area),
+                   Area::new(Origin::new(12, 0), Size::new(126, 13)));
+    }
+
+    #[test]
+    fn touching_areas_touch_when_rendered() {
+        let left = Area::new(Origin::new(0, 0), Size::new(3, 3));
+        let right = Area::new(Origin::new(3, 0), Size::new(3, 3));
+        let left = output_render_box(Origin::default(), 1.5, left);
+        let right = output_render_box(Origin::default(), 1.5, right);
+        assert_eq!(left.origin.x + left.size.width, right.origin.x);
+        assert_eq!(right, Area::new(Origin::new(5, 0), Size::new(4, 5)));
+    }
+}
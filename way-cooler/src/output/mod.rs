@@ -1,7 +1,19 @@
+mod capture;
+mod damage;
 mod output;
+mod output_config;
 mod output_layout_manager;
+mod output_management;
 mod output_manager;
+mod screencopy;
+mod surface_outputs;
 
+pub use self::capture::*;
+pub use self::damage::*;
 pub use self::output::*;
+pub use self::output_config::*;
 pub use self::output_layout_manager::*;
+pub use self::output_management::*;
 pub use self::output_manager::*;
+pub use self::screencopy::*;
+pub use self::surface_outputs::*;
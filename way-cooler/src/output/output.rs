@@ -1,89 +1,200 @@
 use std::rc::Rc;
 
 use wlroots::utils::current_time;
-use wlroots::{project_box, Area, CompositorHandle, Origin, OutputHandle, OutputHandler,
-              OutputLayoutHandle, Renderer, Size, SurfaceHandle};
+use wlroots::{project_box, Area, CompositorHandle, Layer, Origin, OutputHandle, OutputHandler,
+              OutputLayoutHandle, PixmanRegion, Renderer, Size, SurfaceHandle,
+              wlroots_sys::{wlr_box, wlr_box_transform, wlr_output_transform_invert,
+                            wlr_output_transformed_resolution}};
 
 use ::Server;
 
+const BACKGROUND_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+
 pub struct Output;
 
 impl OutputHandler for Output {
     fn on_frame(&mut self, compositor: CompositorHandle, output: OutputHandle) {
+        let output_handle = output.clone();
         dehandle!(
             @compositor = {compositor};
             @output = {output};
             let state: &mut Server = compositor.data.downcast_mut().unwrap();
-            let Server { ref mut layout, ref mut views, .. } = *state;
+            let Server { ref mut layout,
+                         ref mut views,
+                         ref layer_surfaces,
+                         ref seat,
+                         ref cursor,
+                         ref surface_outputs,
+                         ref mut window_management,
+                         .. } = *state;
+            window_management.refresh(views, seat.focused.as_ref());
+            let mut damage = PixmanRegion::new();
+            if !output.damage().make_current(&mut damage) {
+                // Nothing changed since the last frame, but clients that
+                // asked for a frame callback still wait for one.
+                send_frame_done(surface_outputs, &output_handle);
+                return
+            }
+            let damage_areas = damage.rectangles();
             let renderer = compositor.renderer.as_mut().expect("gles2 disabled");
-            let mut renderer = renderer.render(output, None);
-            renderer.clear([0.25, 0.25, 0.25, 1.0]);
-            render_views(&mut renderer, layout, views)
+            let mut renderer = renderer.render(output, Some(damage));
+            for area in &damage_areas {
+                scissor_output(&mut renderer, *area);
+                renderer.clear(BACKGROUND_COLOR);
+            }
+            // A fullscreen view covers the layer surfaces, along with any
+            // view that is stacked above it.
+            let above_layers = ::views_above_layers(views);
+            let (views_above, views_below) = views.split_at_mut(above_layers);
+            render_layers(&mut renderer, layout, &damage_areas, layer_surfaces,
+                          &::LAYERS_BELOW_VIEWS);
+            render_views(&mut renderer, layout, &damage_areas, views_below);
+            render_layers(&mut renderer, layout, &damage_areas, layer_surfaces,
+                          &::LAYERS_ABOVE_VIEWS);
+            render_views(&mut renderer, layout, &damage_areas, views_above);
+            for drag_icon in &seat.drag_icons {
+                if let Some((mut surface, Origin { x, y })) = drag_icon.drawn_at(seat, cursor) {
+                    render_surface(&mut renderer, layout, &damage_areas, &mut surface, x, y);
+                }
+            }
+            renderer.render_scissor(None)
         )
     }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, output: OutputHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            ::output_removed(compositor.into(), &output);
+        }).unwrap();
+    }
 }
 
-#[allow(dead_code)]
+/// Only render into the damaged area of the output.
+///
+/// The area isn't transformed like the output is, unlike the scissor, so it
+/// has to be turned the same way first.
+fn scissor_output(renderer: &mut Renderer, area: Area) {
+    let output = renderer.output.as_ptr();
+    let scissor = unsafe {
+        let (mut width, mut height) = (0, 0);
+        wlr_output_transformed_resolution(output, &mut width, &mut height);
+        let transform = wlr_output_transform_invert((*output).transform);
+        let area = wlr_box { x: area.origin.x,
+                             y: area.origin.y,
+                             width: area.size.width,
+                             height: area.size.height };
+        let mut scissor = wlr_box { x: 0,
+                                    y: 0,
+                                    width: 0,
+                                    height: 0 };
+        wlr_box_transform(&mut scissor, &area, transform, width, height);
+        scissor
+    };
+    renderer.render_scissor(Some(Area::new(Origin::new(scissor.x, scissor.y),
+                                           Size::new(scissor.width, scissor.height))));
+}
+
+/// Tell the surfaces shown on the output that a frame was drawn, without
+/// rendering them.
+fn send_frame_done(surface_outputs: &[(SurfaceHandle, Vec<OutputHandle>)],
+                   output: &OutputHandle) {
+    let shown = surface_outputs.iter()
+                               .filter(|&&(_, ref outputs)| outputs.contains(output))
+                               .map(|&(ref surface, _)| surface);
+    for surface in shown {
+        with_handles!([(surface: {surface})] => {
+            surface.send_frame_done(current_time());
+        }).ok();
+    }
+}
+
+/// Render the parts of the surface at the output layout coordinates that are
+/// damaged.
 fn render_surface(renderer: &mut Renderer,
                   layout: &mut OutputLayoutHandle,
+                  damage: &[Area],
                   surface: &mut SurfaceHandle,
                   lx: i32,
                   ly: i32) {
     with_handles!([(surface: {surface}), (layout: {&mut *layout})] => {
         let (width, height) = surface.current_state().size();
-        let (render_width, render_height) =
-            (width * renderer.output.scale() as i32,
-            height * renderer.output.scale() as i32);
-        let render_box = Area::new(Origin::new(lx, ly),
-        Size::new(render_width,
-                  render_height));
-
-        if layout.intersects(renderer.output, render_box) {
-            let transform = renderer.output.get_transform().invert();
-            let matrix = project_box(render_box,
-                                     transform,
-                                     0.0,
-                                     renderer.output
-                                     .transform_matrix());
-            if !renderer.render_texture_with_matrix(surface.texture().as_ref().unwrap(), matrix) {
-              warn!("Could not render a surface");
+        let surface_box = Area::new(Origin::new(lx, ly), Size::new(width, height));
+        if !layout.intersects(renderer.output, surface_box) {
+            return
+        }
+        let output_origin = layout.get_box(&mut *renderer.output).origin;
+        let render_box = ::output_render_box(output_origin, renderer.output.scale(), surface_box);
+        // The buffer is transformed by the client, so it has to be turned
+        // back the other way to show up right side up.
+        let transform = surface.current_state().transform().invert();
+        let matrix = project_box(render_box,
+                                 transform,
+                                 0.0,
+                                 renderer.output.transform_matrix());
+        if let Some(texture) = surface.texture().as_ref() {
+            for area in damage.iter().filter(|area| ::areas_intersect(**area, render_box)) {
+                scissor_output(renderer, *area);
+                if !renderer.render_texture_with_matrix(texture, matrix) {
+                    warn!("Could not render a surface");
+                }
             }
-            surface.send_frame_done(current_time());
         }
+        surface.send_frame_done(current_time());
+    }).unwrap();
+}
 
+/// Render the parts of the rectangle at the output layout coordinates that
+/// are damaged.
+fn render_rect(renderer: &mut Renderer,
+               layout: &mut OutputLayoutHandle,
+               damage: &[Area],
+               area: Area,
+               color: [f32; 4]) {
+    with_handles!([(layout: {&mut *layout})] => {
+        if !layout.intersects(renderer.output, area) {
+            return
+        }
+        let output_origin = layout.get_box(&mut *renderer.output).origin;
+        let render_box = ::output_render_box(output_origin, renderer.output.scale(), area);
+        let matrix = renderer.output.transform_matrix();
+        for damaged in damage.iter().filter(|damaged| ::areas_intersect(**damaged, render_box)) {
+            scissor_output(renderer, *damaged);
+            renderer.render_colored_rect(render_box, color, matrix);
+        }
     }).unwrap();
 }
 
-/// Render all of the client views.
+/// Render the layer surfaces in each of the layers, from the bottom up.
+fn render_layers(renderer: &mut Renderer,
+                 layout: &mut OutputLayoutHandle,
+                 damage: &[Area],
+                 layer_surfaces: &[Rc<::LayerSurface>],
+                 layers: &[Layer]) {
+    for layer in layers {
+        let surfaces = layer_surfaces.iter()
+                                     .filter(|surface| surface.layer == *layer)
+                                     .filter(|surface| surface.mapped.get());
+        for layer_surface in surfaces {
+            let Origin { x, y } = layer_surface.area.get().origin;
+            layer_surface.for_each_surface(&mut |mut surface: SurfaceHandle, sx, sy| {
+                render_surface(renderer, layout, damage, &mut surface, x + sx, y + sy);
+            });
+        }
+    }
+}
+
+/// Render the client views that aren't minimized, with their borders.
 fn render_views(renderer: &mut Renderer,
                 layout: &mut OutputLayoutHandle,
-                views: &mut Vec<Rc<::View>>) {
-    for view in views.iter_mut().rev() {
-        let origin = view.origin.get();
-        view.for_each_surface(&mut |surface: SurfaceHandle, sx, sy| {
-            dehandle!(
-                @surface = {surface};
-                @layout = {&*layout};
-                let (width, height) = surface.current_state().size();
-                let (render_width, render_height) =
-                    (width * renderer.output.scale() as i32,
-                     height * renderer.output.scale() as i32);
-                let render_box = Area::new(Origin::new(origin.x + sx, origin.y + sy),
-                                           Size::new(render_width,
-                                                     render_height));
-
-                if layout.intersects(renderer.output, render_box) {
-                    let transform = renderer.output.get_transform().invert();
-                    let matrix = project_box(render_box,
-                                             transform,
-                                             0.0,
-                                             renderer.output
-                                             .transform_matrix());
-                    if !renderer.render_texture_with_matrix(surface.texture().as_ref().unwrap(), matrix) {
-                      warn!("Could not render a surface");
-                    }
-                    surface.send_frame_done(current_time());
-                })
+                damage: &[Area],
+                views: &mut [Rc<::View>]) {
+    for view in views.iter_mut().rev().filter(|view| !view.minimized.get()) {
+        let border = view.border.get();
+        for edge in border.edges(view.geometry()) {
+            render_rect(renderer, layout, damage, edge, border.rgba());
+        }
+        let Origin { x, y } = view.origin.get();
+        view.for_each_surface(&mut |mut surface: SurfaceHandle, sx, sy| {
+            render_surface(renderer, layout, damage, &mut surface, x + sx, y + sy);
         });
     }
 }
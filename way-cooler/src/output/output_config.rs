@@ -0,0 +1,257 @@
+//! Applies output settings, from the `[output]` sections of the configuration
+//! or from clients of the output management protocol, to the outputs.
+
+use wlroots::{wlroots_sys::{wl_output_transform::{self, *}, wlr_output_set_mode}, Area, Origin,
+              Output, OutputHandle, Size};
+
+/// Configure every output from the configuration and lay them out again.
+///
+/// The settings that clients applied are forgotten, the configuration was
+/// (re)loaded to replace them.
+pub fn configure_outputs(server: &mut ::Server) {
+    let configs = {
+        let ::Server { ref config,
+                       ref mut outputs,
+                       ref mut output_management,
+                       .. } = *server;
+        output_management.applied.clear();
+        outputs.iter_mut()
+               .filter_map(|output| {
+                   let output_config = output.run(|output| {
+                       config.output_config(&output.name(), &output_identifier(output))
+                   }).ok()?;
+                   Some((output.clone(), output_config))
+               })
+               .collect()
+    };
+    apply_output_configs(server, configs);
+}
+
+/// Configure an output that was plugged in, leaving the other outputs as
+/// they are.
+///
+/// Settings that a client applied to the output before it was unplugged are
+/// used again instead of the configuration.
+pub fn configure_new_output(server: &mut ::Server, mut output: OutputHandle) {
+    let output_config = {
+        let ::Server { ref config,
+                       ref output_management,
+                       .. } = *server;
+        output.run(|output| {
+                  let name = output.name();
+                  output_management.applied
+                                   .get(&name)
+                                   .cloned()
+                                   .unwrap_or_else(|| {
+                                       config.output_config(&name, &output_identifier(output))
+                                   })
+              })
+    };
+    if let Ok(output_config) = output_config {
+        apply_output_configs(server, vec![(output, output_config)]);
+    }
+}
+
+/// The make, model and serial number of the output, which configuration
+/// sections can be named after.
+fn output_identifier(output: &Output) -> String {
+    format!("{} {} {}", output.make(), output.model(), output.serial())
+}
+
+/// Configure the outputs with the settings and lay them out again.
+///
+/// Outputs with a position are added to the layout first so that the others
+/// are placed to the right of them. The cursor and any views that end up
+/// outside of the layout are brought back into it.
+pub fn apply_output_configs(server: &mut ::Server, configs: Vec<(OutputHandle, ::OutputConfig)>) {
+    with_handles!([(layout: {&mut server.layout})] => {
+        let mut placed = Vec::new();
+        let mut automatic = Vec::new();
+        for (mut output, output_config) in configs {
+            let enabled = with_handles!([(output: {&mut output})] => {
+                layout.remove(output);
+                configure_output(output, &output_config)
+            }).unwrap_or(false);
+            match output_config.position {
+                _ if !enabled => {}
+                Some((x, y)) => placed.push((output, Origin::new(x, y))),
+                None => automatic.push(output)
+            }
+        }
+        for (mut output, origin) in placed {
+            with_handles!([(output: {&mut output})] => {
+                layout.add(output, origin);
+            }).ok();
+        }
+        for mut output in automatic {
+            with_handles!([(output: {&mut output})] => {
+                layout.add_auto(output);
+            }).ok();
+        }
+    }).expect("Output layout was destroyed");
+    for output in server.outputs.clone() {
+        ::arrange_layers(server, &output);
+    }
+    {
+        let ::Server { ref mut xcursor_manager,
+                       ref mut cursor,
+                       ref mut outputs,
+                       .. } = *server;
+        // Every output shows the cursor at its own scale.
+        for output in outputs.iter_mut() {
+            output.run(|output| xcursor_manager.load(output.scale())).ok();
+        }
+        cursor.run(|cursor| xcursor_manager.set_cursor_image("left_ptr".to_string(), cursor))
+              .ok();
+    }
+    bring_into_layout(server);
+    ::map_inputs_to_outputs(server);
+    ::send_output_configuration(server);
+    ::update_surface_outputs(server);
+    ::damage_whole(server);
+}
+
+/// Move the cursor and the views that aren't on any output onto the first
+/// output in the layout.
+pub fn bring_into_layout(server: &mut ::Server) {
+    let ::Server { ref mut layout,
+                   ref mut cursor,
+                   ref views,
+                   ref usable_areas,
+                   .. } = *server;
+    let outputs = with_handles!([(layout: {layout})] => {
+        let mut boxes = Vec::new();
+        for (mut output, _) in layout.outputs() {
+            if let Ok(output_box) = output.run(|output| layout.get_box(output)) {
+                boxes.push((output, output_box));
+            }
+        }
+        boxes
+    }).expect("Output layout was destroyed");
+    let (output, output_box) = match outputs.first() {
+        Some(first) => first.clone(),
+        // There is nowhere to move anything to until an output is added.
+        None => return
+    };
+    let boxes: Vec<Area> = outputs.iter().map(|&(_, output_box)| output_box).collect();
+    with_handles!([(cursor: {cursor})] => {
+        let (x, y) = cursor.coords();
+        if !boxes.iter().any(|area| contains(*area, x as i32, y as i32)) {
+            let Area { origin, size } = output_box;
+            cursor.warp(None,
+                        (origin.x + size.width / 2) as f64,
+                        (origin.y + size.height / 2) as f64);
+        }
+    }).expect("Cursor was destroyed");
+    let usable_area = usable_areas.get(&output).cloned().unwrap_or(output_box);
+    for view in views {
+        let geometry = view.geometry();
+        if !boxes.iter().any(|area| ::areas_intersect(*area, geometry)) {
+            view.move_resize(Area { origin: usable_area.origin,
+                                    size: geometry.size });
+        }
+    }
+}
+
+/// Set the mode, scale and transform of the output and turn it on or off.
+///
+/// Returns whether the output is enabled.
+fn configure_output(output: &mut Output, output_config: &::OutputConfig) -> bool {
+    if output_config.enabled == Some(false) {
+        output.enable(false);
+        return false
+    }
+    output.enable(true);
+    match output_config.mode {
+        Some(mode) => set_mode(output, mode),
+        // The mode might have been removed from the configuration, so the
+        // one that was set for it must not stay.
+        None => set_preferred_mode(output)
+    }
+    output.set_scale(output_config.scale.unwrap_or(1.0));
+    output.transform(output_transform(output_config.transform.unwrap_or(::Transform::Normal)));
+    true
+}
+
+/// Switch the output to the mode with the size of the configured one.
+///
+/// Of the modes with that size, the one with the refresh rate closest to the
+/// configured one is picked, or the one with the highest refresh rate if the
+/// configuration doesn't have one.
+fn set_mode(output: &mut Output, mode: ::DisplayMode) {
+    // The refresh rates of the output modes are in mHz.
+    let refresh = mode.refresh.map(|refresh| (refresh * 1000.0).round() as i32);
+    let best = output.modes()
+                     .into_iter()
+                     .filter(|candidate| candidate.dimensions() == (mode.width, mode.height))
+                     .max_by_key(|candidate| match refresh {
+                                     Some(refresh) => -(candidate.refresh() - refresh).abs(),
+                                     None => candidate.refresh()
+                                 })
+                     .map(|best| best.as_ptr());
+    match best {
+        Some(best) => {
+            if !unsafe { wlr_output_set_mode(output.as_ptr(), best) } {
+                warn!("Could not set the mode of {} to {:?}", output.name(), mode);
+            }
+        }
+        None => warn!("{} has no {}x{} mode", output.name(), mode.width, mode.height)
+    }
+}
+
+/// Switch the output to the mode it prefers, or its first one if it doesn't
+/// prefer any. Outputs without modes, e.g. nested ones, are left alone.
+fn set_preferred_mode(output: &mut Output) {
+    let modes = output.modes();
+    let preferred = modes.iter()
+                         .find(|mode| unsafe { (*mode.as_ptr()).preferred })
+                         .or_else(|| modes.first())
+                         .map(|mode| mode.as_ptr());
+    if let Some(preferred) = preferred {
+        if !unsafe { wlr_output_set_mode(output.as_ptr(), preferred) } {
+            warn!("Could not set {} to its preferred mode", output.name());
+        }
+    }
+}
+
+/// Whether the output has a mode with the size and refresh rate.
+pub fn has_mode(output: &mut Output, mode: ::DisplayMode) -> bool {
+    let refresh = mode.refresh.map(|refresh| (refresh * 1000.0).round() as i32);
+    output.modes().into_iter().any(|candidate| {
+        candidate.dimensions() == (mode.width, mode.height) &&
+        refresh.map_or(true, |refresh| candidate.refresh() == refresh)
+    })
+}
+
+fn output_transform(transform: ::Transform) -> wl_output_transform {
+    match transform {
+        ::Transform::Normal => WL_OUTPUT_TRANSFORM_NORMAL,
+        ::Transform::Rotate90 => WL_OUTPUT_TRANSFORM_90,
+        ::Transform::Rotate180 => WL_OUTPUT_TRANSFORM_180,
+        ::Transform::Rotate270 => WL_OUTPUT_TRANSFORM_270,
+        ::Transform::Flipped => WL_OUTPUT_TRANSFORM_FLIPPED,
+        ::Transform::Flipped90 => WL_OUTPUT_TRANSFORM_FLIPPED_90,
+        ::Transform::Flipped180 => WL_OUTPUT_TRANSFORM_FLIPPED_180,
+        ::Transform::Flipped270 => WL_OUTPUT_TRANSFORM_FLIPPED_270
+    }
+}
+
+/// The inverse of `output_transform`.
+pub fn transform_from_output(transform: wl_output_transform) -> ::Transform {
+    match transform {
+        WL_OUTPUT_TRANSFORM_NORMAL => ::Transform::Normal,
+        WL_OUTPUT_TRANSFORM_90 => ::Transform::Rotate90,
+        WL_OUTPUT_TRANSFORM_180 => ::Transform::Rotate180,
+        WL_OUTPUT_TRANSFORM_270 => ::Transform::Rotate270,
+        WL_OUTPUT_TRANSFORM_FLIPPED => ::Transform::Flipped,
+        WL_OUTPUT_TRANSFORM_FLIPPED_90 => ::Transform::Flipped90,
+        WL_OUTPUT_TRANSFORM_FLIPPED_180 => ::Transform::Flipped180,
+        WL_OUTPUT_TRANSFORM_FLIPPED_270 => ::Transform::Flipped270
+    }
+}
+
+fn contains(area: Area, x: i32, y: i32) -> bool {
+    let Area { origin: Origin { x: left, y: top },
+               size: Size { width, height } } = area;
+    x >= left && x < left + width && y >= top && y < top + height
+}
@@ -0,0 +1,195 @@
+//! The wlr-output-management protocol, which lets tools like wlr-randr and
+//! kanshi list the outputs and their modes and change how they are
+//! configured while Way Cooler runs.
+
+use std::{collections::HashMap, mem, os::raw::c_void, ptr};
+
+use wayland_sys::{common::wl_list, server::{wl_display, wl_listener, wl_signal}};
+use wlroots::{self, wlroots_sys::*, OutputHandle};
+
+use ::ffi::add_listener;
+
+/// The output manager global.
+#[derive(Debug)]
+pub struct OutputManagement {
+    manager: *mut wlr_output_manager_v1,
+    /// The settings that clients applied, by the name of the output.
+    ///
+    /// They take precedence over the configuration until it is reloaded, also
+    /// for outputs that are unplugged and plugged in again.
+    pub applied: HashMap<String, ::OutputConfig>
+}
+
+impl Default for OutputManagement {
+    fn default() -> OutputManagement {
+        OutputManagement { manager: ptr::null_mut(),
+                           applied: HashMap::new() }
+    }
+}
+
+impl OutputManagement {
+    /// Advertise the output manager global on the display.
+    pub fn new(display: *mut wl_display) -> OutputManagement {
+        unsafe {
+            let manager = wlr_output_manager_v1_create(display as *mut _);
+            if manager.is_null() {
+                panic!("Could not create the output manager global");
+            }
+            add_listener(&mut (*manager).events.apply as *mut _ as *mut wl_signal,
+                         apply_requested);
+            add_listener(&mut (*manager).events.test as *mut _ as *mut wl_signal,
+                         test_requested);
+            OutputManagement { manager,
+                               applied: HashMap::new() }
+        }
+    }
+}
+
+/// Tell the output management clients how the outputs are configured now.
+///
+/// This has to be called every time an output or the layout changes.
+pub fn send_output_configuration(server: &mut ::Server) {
+    let ::Server { ref mut layout,
+                   ref mut outputs,
+                   ref output_management,
+                   .. } = *server;
+    let manager = output_management.manager;
+    if manager.is_null() {
+        return
+    }
+    with_handles!([(layout: {layout})] => {
+        unsafe {
+            let configuration = wlr_output_configuration_v1_create();
+            for output in outputs.iter_mut() {
+                with_handles!([(output: {output})] => {
+                    // The head starts out with the mode, scale and transform
+                    // of the output.
+                    let head = wlr_output_configuration_head_v1_create(configuration,
+                                                                       output.as_ptr());
+                    if head.is_null() {
+                        return
+                    }
+                    let layout_output = wlr_output_layout_get(layout.as_ptr(), output.as_ptr());
+                    if layout_output.is_null() {
+                        (*head).state.enabled = false;
+                    } else {
+                        (*head).state.x = (*layout_output).x;
+                        (*head).state.y = (*layout_output).y;
+                    }
+                }).ok();
+            }
+            wlr_output_manager_v1_set_configuration(manager, configuration);
+        }
+    }).expect("Output layout was destroyed");
+}
+
+unsafe extern "C" fn apply_requested(_: *mut wl_listener, data: *mut c_void) {
+    configuration_requested(data as *mut wlr_output_configuration_v1, true);
+}
+
+unsafe extern "C" fn test_requested(_: *mut wl_listener, data: *mut c_void) {
+    configuration_requested(data as *mut wlr_output_configuration_v1, false);
+}
+
+/// Check the configuration that a client asked for and apply it if it
+/// should be applied.
+///
+/// Outputs can't be tested without changing them, so a configuration passes
+/// the test if every output in it exists and supports the mode it is given.
+unsafe fn configuration_requested(configuration: *mut wlr_output_configuration_v1, apply: bool) {
+    let succeeded = wlroots::compositor_handle().and_then(|compositor| {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let configs: Option<Vec<_>> = heads(configuration)
+                .into_iter()
+                .map(|head| head_config(server, &(*head).state))
+                .collect();
+            match configs {
+                Some(configs) => {
+                    if apply {
+                        for &(ref output, ref output_config) in &configs {
+                            if let Ok(name) = output.clone().run(|output| output.name()) {
+                                server.output_management
+                                      .applied
+                                      .insert(name, output_config.clone());
+                            }
+                        }
+                        ::apply_output_configs(server, configs);
+                    }
+                    true
+                }
+                None => false
+            }
+        }).ok()
+    });
+    if succeeded == Some(true) {
+        wlr_output_configuration_v1_send_succeeded(configuration);
+    } else {
+        wlr_output_configuration_v1_send_failed(configuration);
+    }
+    wlr_output_configuration_v1_destroy(configuration);
+}
+
+/// The settings that the head of a configuration asks for.
+///
+/// Returns `None` if the output is gone or can't be configured like that.
+unsafe fn head_config(server: &mut ::Server,
+                      state: &wlr_output_head_v1_state)
+                      -> Option<(OutputHandle, ::OutputConfig)> {
+    let index = server.outputs
+                      .iter_mut()
+                      .position(|output| output.run(|output| output.as_ptr()).ok() ==
+                                         Some(state.output))?;
+    let output = &mut server.outputs[index];
+    if !state.enabled {
+        return Some((output.clone(),
+                     ::OutputConfig { enabled: Some(false),
+                                      ..::OutputConfig::default() }))
+    }
+    let mode = if state.mode.is_null() {
+        let refresh = state.custom_mode.refresh;
+        ::DisplayMode { width: state.custom_mode.width,
+                        height: state.custom_mode.height,
+                        refresh: if refresh > 0 {
+                            Some(refresh as f64 / 1000.0)
+                        } else {
+                            None
+                        } }
+    } else {
+        ::DisplayMode { width: (*state.mode).width,
+                        height: (*state.mode).height,
+                        refresh: Some((*state.mode).refresh as f64 / 1000.0) }
+    };
+    if !output.run(|output| ::has_mode(output, mode)).unwrap_or(false) {
+        warn!("Output management asked for an unsupported mode {:?}", mode);
+        return None
+    }
+    if state.scale <= 0.0 {
+        warn!("Output management asked for an invalid scale {}", state.scale);
+        return None
+    }
+    Some((output.clone(),
+          ::OutputConfig { enabled: Some(true),
+                           mode: Some(mode),
+                           scale: Some(state.scale),
+                           transform: Some(::transform_from_output(state.transform)),
+                           position: Some((state.x, state.y)) }))
+}
+
+/// The heads of the configuration, one for each output.
+unsafe fn heads(configuration: *mut wlr_output_configuration_v1)
+                -> Vec<*mut wlr_output_configuration_head_v1> {
+    // The heads are linked together through their link field.
+    let offset = {
+        let head: wlr_output_configuration_head_v1 = mem::zeroed();
+        &head.link as *const _ as usize - &head as *const _ as usize
+    };
+    let list = &mut (*configuration).heads as *mut _ as *mut wl_list;
+    let mut heads = Vec::new();
+    let mut link = (*list).next;
+    while link != list {
+        heads.push((link as *mut u8).offset(-(offset as isize)) as *mut _);
+        link = (*link).next;
+    }
+    heads
+}
@@ -1,4 +1,5 @@
-use wlroots::{CompositorHandle, OutputBuilder, OutputBuilderResult, OutputManagerHandler};
+use wlroots::{CompositorHandle, OutputBuilder, OutputBuilderResult, OutputHandle,
+              OutputManagerHandler};
 
 pub struct OutputManager;
 
@@ -13,27 +14,54 @@ impl OutputManagerHandler for OutputManager {
                              compositor: CompositorHandle,
                              builder: OutputBuilder<'output>)
                              -> Option<OutputBuilderResult<'output>> {
-        dehandle!(
-            @compositor = {compositor};
+        let res = with_handles!([(compositor: {&compositor})] => {
             let server: &mut ::Server = compositor.into();
             let res = builder.build_best_mode(::Output);
             server.outputs.push(res.output.clone());
-            let ::Server { ref mut cursor,
-                         ref mut layout,
-                         ref mut xcursor_manager,
-                         .. } = *server;
-            @layout = {layout};
-            @cursor = {cursor};
-            {
-                @output = {&res.output};
-                layout.add_auto(output);
+            with_handles!([(layout: {&mut server.layout}), (cursor: {&mut server.cursor})] => {
                 cursor.attach_output_layout(layout);
-                xcursor_manager.load(output.scale());
-                xcursor_manager.set_cursor_image("left_ptr".to_string(), cursor);
+            }).unwrap();
+            ::configure_new_output(server, res.output.clone());
+            res
+        }).unwrap();
+        dehandle!(
+            @compositor = {compositor};
+            let server: &mut ::Server = compositor.into();
+            {
+                @cursor = {&mut server.cursor};
                 let (x, y) = cursor.coords();
                 cursor.warp(None, x, y)
             }
-            Some(res)
-        )
+            ::damage_changes(server)
+        );
+        Some(res)
+    }
+}
+
+/// Forget about an output that was unplugged and move everything that was on
+/// it onto the outputs that are left.
+pub fn output_removed(server: &mut ::Server, output: &OutputHandle) {
+    server.outputs.retain(|other| other != output);
+    server.usable_areas.remove(output);
+    // The output is being destroyed, so it might not be possible to use it
+    // any more. wlroots removes it from the layout in that case.
+    with_handles!([(layout: {&mut server.layout}), (output: {output})] => {
+        layout.remove(output);
+    }).ok();
+    // Layer surfaces are placed on one output, their clients have to create
+    // them again for another one.
+    for layer_surface in server.layer_surfaces.iter().filter(|surface| surface.output == *output) {
+        layer_surface.layer_surface.run(|layer_surface| layer_surface.close()).ok();
+    }
+    ::bring_into_layout(server);
+    ::map_inputs_to_outputs(server);
+    ::send_output_configuration(server);
+    {
+        let ::Server { ref mut window_management,
+                       ref views,
+                       ref seat,
+                       .. } = *server;
+        window_management.refresh(views, seat.focused.as_ref());
     }
+    ::damage_whole(server);
 }
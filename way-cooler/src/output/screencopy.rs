@@ -0,0 +1,16 @@
+//! The wlr-screencopy protocol, which lets tools like grim and wf-recorder
+//! capture an output, or a region of it, into a shm buffer.
+//!
+//! wlroots implements the protocol, reading back the frames that
+//! `Output::on_frame` renders.
+
+use wayland_sys::server::wl_display;
+use wlroots::wlroots_sys::wlr_screencopy_manager_v1_create;
+
+/// Advertise the screencopy manager global on the display.
+pub fn create_screencopy_manager(display: *mut wl_display) {
+    let manager = unsafe { wlr_screencopy_manager_v1_create(display as *mut _) };
+    if manager.is_null() {
+        panic!("Could not create the screencopy manager global");
+    }
+}
@@ -0,0 +1,75 @@
+//! Tells surfaces which outputs they are shown on, so that their clients can
+//! draw them at the scale of those outputs.
+
+use std::mem;
+
+use wlroots::{wlroots_sys::{wlr_surface_send_enter, wlr_surface_send_leave}, OutputHandle,
+              SurfaceHandle};
+
+/// Send enter and leave events to the surfaces that moved onto or off of an
+/// output since the last time this was called.
+pub fn update_surface_outputs(server: &mut ::Server) {
+    let output_boxes: Vec<_> = {
+        let ::Server { ref mut layout,
+                       ref mut outputs,
+                       .. } = *server;
+        outputs.iter_mut()
+               .filter_map(|output| {
+                   with_handles!([(layout: {&mut *layout}), (output: {&mut *output})] => {
+                       layout.get_box(output)
+                   }).ok()
+                     .map(|output_box| (output.clone(), output_box))
+               })
+               .collect()
+    };
+    let mut surface_outputs = Vec::new();
+    ::for_each_drawn_surface(server, &mut |surface, area| {
+        let outputs = output_boxes.iter()
+                                  .filter(|&&(_, output_box)| ::areas_intersect(output_box, area))
+                                  .map(|&(ref output, _)| output.clone())
+                                  .collect();
+        surface_outputs.push((surface.clone(), outputs));
+    });
+    let old_surface_outputs = mem::replace(&mut server.surface_outputs, surface_outputs);
+    let ::Server { ref surface_outputs,
+                   ref outputs,
+                   .. } = *server;
+    let no_outputs = Vec::new();
+    for &(ref surface, ref surface_on) in surface_outputs {
+        let was_on = old_surface_outputs.iter()
+                                        .find(|&&(ref old_surface, _)| old_surface == surface)
+                                        .map(|&(_, ref was_on)| was_on)
+                                        .unwrap_or(&no_outputs);
+        for output in surface_on.iter().filter(|output| !was_on.contains(output)) {
+            send_enter_leave(surface, output, true);
+        }
+        for output in was_on.iter().filter(|output| !surface_on.contains(output)) {
+            if outputs.contains(output) {
+                send_enter_leave(surface, output, false);
+            }
+        }
+    }
+    // Surfaces that aren't drawn any more, e.g. because they were unmapped,
+    // leave every output.
+    for &(ref surface, ref was_on) in &old_surface_outputs {
+        if surface_outputs.iter().any(|&(ref drawn, _)| drawn == surface) {
+            continue
+        }
+        for output in was_on.iter().filter(|output| outputs.contains(output)) {
+            send_enter_leave(surface, output, false);
+        }
+    }
+}
+
+fn send_enter_leave(surface: &SurfaceHandle, output: &OutputHandle, enter: bool) {
+    // Surfaces that were destroyed don't have to leave anything.
+    with_handles!([(surface: {surface}), (output: {output})] => {
+        unsafe {
+            if enter {
+                wlr_surface_send_enter(surface.as_ptr(), output.as_ptr());
+            } else {
+                wlr_surface_send_leave(surface.as_ptr(), output.as_ptr());
+            }
+        }
+    }).ok();
+}
@@ -0,0 +1,92 @@
+//! Hides the globals that give a client power over the others from the
+//! clients that may not use them.
+//!
+//! The window management global is only shown to the window manager that Way
+//! Cooler started, and the data control global only to the programs that the
+//! `data_control` setting allows. A display only has one global filter, so
+//! every privileged global is checked by it.
+
+use std::{fs, ptr, os::raw::c_void, path::PathBuf};
+
+use wayland_sys::server::{wl_client, wl_display, wl_global, WAYLAND_SERVER_HANDLE};
+use wlroots;
+
+extern "C" {
+    // wayland-sys doesn't have this yet, libwayland-server exports it.
+    fn wl_display_set_global_filter(display: *mut wl_display,
+                                    filter: unsafe extern "C" fn(*const wl_client,
+                                                                 *const wl_global,
+                                                                 *mut c_void)
+                                                                 -> bool,
+                                    data: *mut c_void);
+}
+
+/// The globals that only some clients may bind.
+#[derive(Debug)]
+pub struct PrivilegedGlobals {
+    pub window_management: *mut wl_global,
+    pub data_control: *mut wl_global
+}
+
+/// Hide the privileged globals from the clients that may not use them.
+///
+/// The globals live as long as the display, so they are never forgotten.
+pub fn filter_privileged_globals(display: *mut wl_display, globals: PrivilegedGlobals) {
+    let globals = Box::into_raw(Box::new(globals));
+    unsafe {
+        wl_display_set_global_filter(display, filter_global, globals as *mut c_void);
+    }
+}
+
+/// The canonical path of the executable that the client runs, if it can be
+/// found.
+///
+/// An executable that was deleted or replaced since the client started isn't
+/// found, so it can't pass for the new one.
+unsafe fn client_executable(client: *const wl_client) -> Option<PathBuf> {
+    let mut pid = 0;
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                  wl_client_get_credentials,
+                  client as *mut _,
+                  &mut pid,
+                  ptr::null_mut(),
+                  ptr::null_mut());
+    fs::canonicalize(format!("/proc/{}/exe", pid)).ok()
+}
+
+/// Whether the client may see the global, with the privileged globals passed
+/// as the data.
+unsafe extern "C" fn filter_global(client: *const wl_client,
+                                   global: *const wl_global,
+                                   data: *mut c_void)
+                                   -> bool {
+    let globals = &*(data as *const PrivilegedGlobals);
+    let global = global as *mut wl_global;
+    if global != globals.window_management && global != globals.data_control {
+        return true
+    }
+    let compositor = match wlroots::compositor_handle() {
+        Some(compositor) => compositor,
+        None => return false
+    };
+    if global == globals.window_management {
+        let allowed = with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.window_management.is_window_manager(client)
+        }).unwrap_or(false);
+        if !allowed {
+            debug!("Hiding the window management protocol from {:?}",
+                   client_executable(client));
+        }
+        return allowed
+    }
+    let executable = client_executable(client);
+    let allowed = with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        server.config.data_control.allows(executable.as_ref().map(|path| path.as_path()))
+    }).unwrap_or(false);
+    if !allowed {
+        debug!("Hiding the data control protocol from {:?}", executable);
+    }
+    allowed
+}
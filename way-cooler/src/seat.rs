@@ -1,12 +1,13 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::time::Duration;
 use wlroots;
 use wlroots::events::seat_events::SetCursorEvent;
-use wlroots::pointer_events::ButtonEvent;
 use wlroots::utils::{current_time, Edges};
-use wlroots::{Area, CompositorHandle, Cursor, CursorHandle, DragIconHandle, Origin, SeatHandle,
-              SeatHandler, Size, SurfaceHandle, SurfaceHandler, XCursorManager};
+use wlroots::wlroots_sys::{wlr_axis_orientation, wlr_axis_source, wlr_seat_touch_get_point};
+use wlroots::{Area, CompositorHandle, Cursor, CursorHandle, DragIconHandle, KeyboardModifier,
+              Origin, SeatHandle, SeatHandler, Size, SurfaceHandle, SurfaceHandler,
+              XCursorManager, WLR_BUTTON_PRESSED, WLR_BUTTON_RELEASED};
 
 #[derive(Debug, Default)]
 pub struct SeatManager;
@@ -25,36 +26,128 @@ pub enum Action {
     }
 }
 
+/// Where the events of a touch point go.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TouchPoint {
+    /// The point went down on the surface of a view, which gets the events
+    /// of the point until it's lifted.
+    ///
+    /// The offset is the position of the surface in output layout
+    /// coordinates.
+    Surface { offset: Origin },
+    /// The point went down on a panel or on empty space, so it moves the
+    /// pointer and holds down the left button instead.
+    Pointer
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct DragIcon {
     pub handle: DragIconHandle
 }
 
+impl DragIcon {
+    /// The surface of the icon and where it is drawn in output layout
+    /// coordinates, under the cursor or the touch point that drags it.
+    ///
+    /// Returns `None` if the icon isn't mapped.
+    pub fn drawn_at(&self, seat: &Seat, cursor: &CursorHandle) -> Option<(SurfaceHandle, Origin)> {
+        let (surface, is_pointer, touch_id, sx, sy) = self.handle.run(|drag_icon| unsafe {
+            let icon = &*drag_icon.as_ptr();
+            if !icon.mapped {
+                return None
+            }
+            Some((drag_icon.surface(), icon.is_pointer, icon.touch_id, icon.sx, icon.sy))
+        }).ok()??;
+        let (x, y) = if is_pointer {
+            cursor.run(|cursor| cursor.coords()).ok()?
+        } else {
+            seat.touch_position(touch_id)?
+        };
+        Some((surface, Origin::new(x as i32 + sx, y as i32 + sy)))
+    }
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct Seat {
     pub seat: SeatHandle,
     pub focused: Option<Rc<::View>>,
+    /// A layer surface that took the keyboard focus away from the views.
+    pub focused_layer: Option<Rc<::LayerSurface>>,
     pub action: Option<Action>,
     pub has_client_cursor: bool,
-    pub meta: bool,
+    /// Whether the modifier to drag views with is held down.
+    pub drag_modifier_held: bool,
+    /// The keycodes of the keys that are held down and ran a binding.
+    pub bound_keys: HashSet<u32>,
+    /// The touch points that are down, by touch id.
+    pub touch_points: HashMap<i32, TouchPoint>,
     pub drag_icons: HashSet<DragIcon>
 }
 
 impl Seat {
     pub fn new(seat: SeatHandle) -> Seat {
         Seat { seat,
-               meta: false,
                ..Seat::default() }
     }
 
+    /// Where the touch point that went down on a surface is now, in output
+    /// layout coordinates.
+    pub fn touch_position(&self, touch_id: i32) -> Option<(f64, f64)> {
+        let offset = match self.touch_points.get(&touch_id) {
+            Some(&TouchPoint::Surface { offset }) => offset,
+            _ => return None
+        };
+        let point = self.seat
+                        .run(|seat| unsafe { wlr_seat_touch_get_point(seat.as_ptr(), touch_id) })
+                        .ok()?;
+        if point.is_null() {
+            return None
+        }
+        unsafe { Some((offset.x as f64 + (*point).sx, offset.y as f64 + (*point).sy)) }
+    }
+
     pub fn clear_focus(&mut self) {
         if let Some(focused_view) = self.focused.take() {
             focused_view.activate(false);
         }
+        if self.focused_layer.is_some() {
+            return
+        }
         dehandle!(@seat = {&self.seat};
                   seat.keyboard_clear_focus())
     }
 
+    /// Give keyboard focus to a layer surface, or back to the focused view.
+    pub fn focus_layer(&mut self, layer: Option<Rc<::LayerSurface>>) {
+        if self.focused_layer == layer {
+            return
+        }
+        self.focused_layer = layer;
+        let surface = match self.focused_layer {
+            Some(ref layer) => Some(layer.surface()),
+            None => self.focused.as_ref().map(|view| view.surface())
+        };
+        with_handles!([(seat: {&self.seat})] => {
+            match (surface, seat.get_keyboard()) {
+                (Some(surface), Some(keyboard)) => {
+                    with_handles!([(keyboard: {keyboard}), (surface: {surface})] => {
+                        seat.keyboard_notify_enter(surface,
+                                                   &mut keyboard.keycodes(),
+                                                   &mut keyboard.get_modifier_masks());
+                    }).unwrap();
+                },
+                (None, _) => seat.keyboard_clear_focus(),
+                _ => {}
+            }
+        }).unwrap();
+    }
+
+    /// The view that gets the focus after the focused one went away: the
+    /// topmost one that isn't minimized or an override redirect window.
+    pub fn next_focus(views: &[Rc<::View>]) -> Option<Rc<::View>> {
+        views.iter().find(|view| !view.minimized.get() && view.wants_focus()).cloned()
+    }
+
     pub fn focus_view(&mut self, view: Rc<::View>, views: &mut Vec<Rc<::View>>) {
         if let Some(ref focused) = self.focused {
             if *focused == view {
@@ -67,7 +160,14 @@ impl Seat {
 
         if let Some(idx) = views.iter().position(|v| *v == view) {
             let v = views.remove(idx);
-            views.insert(0, v);
+            // Open menus and tooltips of XWayland clients stay on top.
+            let top = views.iter().position(|v| v.wants_focus()).unwrap_or(views.len());
+            views.insert(top, v);
+        }
+
+        if self.focused_layer.is_some() {
+            // The view gets the keyboard back once the layer surface is done.
+            return
         }
 
         dehandle!(
@@ -82,12 +182,52 @@ impl Seat {
         );
     }
 
-    pub fn send_button(&self, event: &ButtonEvent) {
+    pub fn send_button(&self, time_msec: u32, button: u32, pressed: bool) {
+        let state = if pressed { WLR_BUTTON_PRESSED } else { WLR_BUTTON_RELEASED };
+        dehandle!(
+            @seat = {&self.seat};
+            seat.pointer_notify_button(Duration::from_millis(time_msec as _),
+            button,
+            state as u32));
+    }
+
+    /// Send a scroll to the surface with pointer focus.
+    pub fn send_axis(&self,
+                     time_msec: u32,
+                     orientation: wlr_axis_orientation,
+                     delta: f64,
+                     delta_discrete: i32,
+                     source: wlr_axis_source) {
         dehandle!(
             @seat = {&self.seat};
-            seat.pointer_notify_button(Duration::from_millis(event.time_msec() as _),
-            event.button(),
-            event.state() as u32));
+            seat.pointer_notify_axis(Duration::from_millis(time_msec as _),
+                                     orientation,
+                                     delta,
+                                     delta_discrete,
+                                     source));
+    }
+
+    /// Tell the surface with pointer focus that the pointer events that
+    /// belong together have all been sent.
+    pub fn send_frame(&self) {
+        dehandle!(
+            @seat = {&self.seat};
+            seat.pointer_notify_frame());
+    }
+
+    /// The modifiers that are held down on the keyboard of the seat.
+    pub fn modifiers(&self) -> KeyboardModifier {
+        dehandle!(
+            @seat = {&self.seat};
+            match seat.get_keyboard() {
+                Some(keyboard) => {
+                    with_handles!([(keyboard: {keyboard})] => {
+                        keyboard.get_modifiers()
+                    }).unwrap_or(KeyboardModifier::empty())
+                }
+                None => KeyboardModifier::empty()
+            }
+        )
     }
 
     pub fn move_view<O>(&mut self, cursor: &mut Cursor, view: &::View, start: O)
@@ -104,7 +244,7 @@ impl Seat {
             }
             Some(start) => {
                 let pos = Origin::new(lx as i32 - start.x, ly as i32 - start.y);
-                view.origin.replace(pos);
+                view.move_to(pos);
             }
         };
     }
@@ -132,43 +272,83 @@ impl Seat {
     pub fn view_at_pointer(views: &mut [Rc<::View>],
                            cursor: &mut Cursor)
                            -> (Option<Rc<::View>>, Option<SurfaceHandle>, f64, f64) {
-        for view in views {
-            match view.shell.clone() {
-                ::Shell::XdgV6(mut shell) => {
-                    let (mut sx, mut sy) = (0.0, 0.0);
-                    let surface = dehandle!(
-                        @shell = {shell};
-                        let (lx, ly) = cursor.coords();
-                        let Origin {x: shell_x, y: shell_y} = view.origin.get();
-                        let (view_sx, view_sy) = (lx - shell_x as f64, ly - shell_y as f64);
-                        shell.surface_at(view_sx, view_sy, &mut sx, &mut sy)
-                    );
-                    if surface.is_some() {
-                        return (Some(view.clone()), surface, sx, sy)
-                    }
-                },
-                ::Shell::Xdg(mut shell) => {
-                    let (mut sx, mut sy) = (0.0, 0.0);
-                    let surface = dehandle!(
-                        @shell = {shell};
-                        let (lx, ly) = cursor.coords();
-                        let Origin {x: shell_x, y: shell_y} = view.origin.get();
-                        let (view_sx, view_sy) = (lx - shell_x as f64, ly - shell_y as f64);
-                        shell.surface_at(view_sx, view_sy, &mut sx, &mut sy)
-                    );
-                    if surface.is_some() {
-                        return (Some(view.clone()), surface, sx, sy)
-                    }
-                }
+        let (lx, ly) = cursor.coords();
+        Seat::view_at(views, lx, ly)
+    }
+
+    /// Get the view at the position in output layout coordinates, along with
+    /// the surface of the view there and the surface local coordinates.
+    ///
+    /// Minimized views are skipped.
+    pub fn view_at(views: &mut [Rc<::View>],
+                   lx: f64,
+                   ly: f64)
+                   -> (Option<Rc<::View>>, Option<SurfaceHandle>, f64, f64) {
+        for view in views.iter().filter(|view| !view.minimized.get()) {
+            let Origin {x: shell_x, y: shell_y} = view.origin.get();
+            let (view_sx, view_sy) = (lx - shell_x as f64, ly - shell_y as f64);
+            let (mut sx, mut sy) = (0.0, 0.0);
+            let surface = match view.shell.clone() {
+                ::Shell::XdgV6(mut shell) => dehandle!(
+                    @shell = {shell};
+                    shell.surface_at(view_sx, view_sy, &mut sx, &mut sy)
+                ),
+                ::Shell::Xdg(mut shell) => dehandle!(
+                    @shell = {shell};
+                    shell.surface_at(view_sx, view_sy, &mut sx, &mut sy)
+                ),
+                ::Shell::XWayland(_) => dehandle!(
+                    @surface = {view.surface()};
+                    surface.surface_at(view_sx, view_sy, &mut sx, &mut sy)
+                )
+            };
+            if surface.is_some() {
+                return (Some(view.clone()), surface, sx, sy)
             }
         }
         (None, None, 0.0, 0.0)
     }
 
+    /// Get the surface under the pointer, taking the layer surfaces above and
+    /// below the views into account.
+    pub fn surface_at_pointer(views: &mut [Rc<::View>],
+                              layer_surfaces: &[Rc<::LayerSurface>],
+                              cursor: &mut Cursor)
+                              -> (Option<SurfaceHandle>, f64, f64) {
+        let (lx, ly) = cursor.coords();
+        Seat::surface_at(views, layer_surfaces, lx, ly)
+    }
+
+    /// Get the surface at the position in output layout coordinates, along
+    /// with the surface local coordinates.
+    pub fn surface_at(views: &mut [Rc<::View>],
+                      layer_surfaces: &[Rc<::LayerSurface>],
+                      lx: f64,
+                      ly: f64)
+                      -> (Option<SurfaceHandle>, f64, f64) {
+        let above_layers = ::views_above_layers(views);
+        let (views_above, views_below) = views.split_at_mut(above_layers);
+        if let (Some(_), surface, sx, sy) = Seat::view_at(views_above, lx, ly) {
+            return (surface, sx, sy)
+        }
+        if let Some((_, surface, sx, sy)) =
+            ::layer_surface_at(layer_surfaces, &::LAYERS_ABOVE_VIEWS, lx, ly) {
+            return (Some(surface), sx, sy)
+        }
+        if let (Some(_), surface, sx, sy) = Seat::view_at(views_below, lx, ly) {
+            return (surface, sx, sy)
+        }
+        match ::layer_surface_at(layer_surfaces, &::LAYERS_BELOW_VIEWS, lx, ly) {
+            Some((_, surface, sx, sy)) => (Some(surface), sx, sy),
+            None => (None, 0.0, 0.0)
+        }
+    }
+
     pub fn update_cursor_position(&mut self,
                                   cursor: &mut Cursor,
                                   xcursor_manager: &mut XCursorManager,
                                   views: &mut [Rc<::View>],
+                                  layer_surfaces: &[Rc<::LayerSurface>],
                                   time_msec: Option<u32>) {
         let time = if let Some(time_msec) = time_msec {
             Duration::from_millis(time_msec as u64)
@@ -219,7 +399,7 @@ impl Seat {
                 });
             }
             _ => {
-                let (_view, surface, sx, sy) = Seat::view_at_pointer(views, cursor);
+                let (surface, sx, sy) = Seat::surface_at_pointer(views, layer_surfaces, cursor);
                 match surface {
                     Some(surface) => {
                         dehandle!(
@@ -248,18 +428,23 @@ impl Seat {
 struct DragIconHandler;
 
 impl wlroots::DragIconHandler for DragIconHandler {
-    fn on_map(&mut self, _: CompositorHandle, _: DragIconHandle) {
-        // TODO damage the drag icon surface location
+    fn on_map(&mut self, compositor: CompositorHandle, _: DragIconHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 
-    fn on_unmap(&mut self, _: CompositorHandle, _: DragIconHandle) {
-        // TODO damage the drag icon surface location
+    fn on_unmap(&mut self, compositor: CompositorHandle, _: DragIconHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 
     fn destroyed(&mut self, compositor: CompositorHandle, drag_icon: DragIconHandle) {
         with_handles!([(compositor: {compositor})] => {
             let server: &mut ::Server = compositor.into();
             server.seat.drag_icons.remove(&DragIcon{ handle: drag_icon });
+            ::damage_changes(server);
         }).unwrap();
     }
 }
@@ -293,7 +478,7 @@ impl SeatHandler for SeatManager {
             let ::Server { ref mut seat, .. } = *server;
             seat.drag_icons.insert(DragIcon { handle: drag_icon });
         }).unwrap();
-        (Some(Box::new(DragIconHandler)), None)
+        (Some(Box::new(DragIconHandler)), Some(Box::new(::SurfaceDamage)))
     }
 }
 
@@ -0,0 +1,403 @@
+//! The clipboard and the primary selection of the seat.
+//!
+//! Clients share the primary selection, which is pasted with the middle
+//! button, with the primary-selection protocol. wlroots bridges it to
+//! XWayland along with the clipboard.
+//!
+//! Clipboard managers watch and set both selections with the data control
+//! protocol. Since that shows them everything that is copied, the global is
+//! hidden from every client that the `data_control` setting in the
+//! `[clipboard]` section doesn't allow. The setting is checked when a client
+//! binds the global, so clients that already did keep it after the
+//! configuration is reloaded.
+//!
+//! If `persist` is enabled in the `[clipboard]` section of the configuration,
+//! the contents of the clipboard are read from the client as soon as it sets
+//! them. Once that client destroys its selection, e.g. because it was closed
+//! right after copying, Way Cooler offers the copy in its place.
+
+use std::{mem, ptr, ffi::{CStr, CString}, os::{raw::{c_char, c_int, c_void}, unix::io::RawFd},
+          rc::Rc};
+
+use nix::{self, errno::Errno, fcntl::{self, FcntlArg}, libc, unistd};
+use wayland_sys::server::{wl_display, wl_event_source, wl_global, wl_listener,
+                          WAYLAND_SERVER_HANDLE};
+use wlroots::{self, wlroots_sys::*};
+
+use ::ffi::{add_listener, remove_listener};
+
+// The events that file descriptors are watched for.
+const WL_EVENT_READABLE: u32 = 0x01;
+const WL_EVENT_WRITABLE: u32 = 0x02;
+
+/// The most that is kept of the clipboard in each mime type, in bytes.
+///
+/// Mime types with more than this are dropped.
+const MAX_SAVED_SIZE: usize = 16 * 1024 * 1024;
+
+/// The contents of the clipboard in one mime type.
+#[derive(Debug, Clone)]
+struct Offer {
+    mime_type: String,
+    data: Rc<Vec<u8>>
+}
+
+/// A mime type of the clipboard that is being read from the client.
+#[derive(Debug)]
+struct Read {
+    fd: RawFd,
+    event_source: *mut wl_event_source,
+    mime_type: String,
+    data: Vec<u8>
+}
+
+/// The copy of the clipboard that is offered once the client that set it
+/// destroyed its selection.
+#[repr(C)]
+struct SavedSource {
+    source: wlr_data_source,
+    display: *mut wl_display,
+    offers: Vec<Offer>
+}
+
+/// A copy of the clipboard that is being written to a client.
+struct Write {
+    fd: RawFd,
+    event_source: *mut wl_event_source,
+    data: Rc<Vec<u8>>,
+    written: usize
+}
+
+static SAVED_SOURCE_IMPL: wlr_data_source_impl =
+    wlr_data_source_impl { send: Some(saved_source_send),
+                           accept: None,
+                           destroy: Some(saved_source_destroy),
+                           dnd_drop: None,
+                           dnd_finish: None,
+                           dnd_action: None };
+
+/// The selections of the seat.
+#[derive(Debug)]
+pub struct Selection {
+    display: *mut wl_display,
+    seat: *mut wlr_seat,
+    /// The data control global, which `filter_privileged_globals` hides from
+    /// the clients that may not use it.
+    pub data_control: *mut wl_global,
+    /// The client source of the clipboard that is being kept, with the
+    /// listener for when it's destroyed.
+    source: Option<(*mut wlr_data_source, *mut wl_listener)>,
+    reads: Vec<Read>,
+    /// The mime types of the clipboard that were read completely.
+    saved: Vec<Offer>
+}
+
+impl Default for Selection {
+    fn default() -> Selection {
+        Selection { display: ptr::null_mut(),
+                    seat: ptr::null_mut(),
+                    data_control: ptr::null_mut(),
+                    source: None,
+                    reads: Vec::new(),
+                    saved: Vec::new() }
+    }
+}
+
+impl Selection {
+    /// Advertise the primary selection and data control globals on the
+    /// display and share the selections of the seat with XWayland, if it's
+    /// running.
+    pub fn new(display: *mut wl_display,
+               seat: *mut wlr_seat,
+               xwayland: *mut wlr_xwayland)
+               -> Selection {
+        unsafe {
+            let manager = wlr_primary_selection_v1_device_manager_create(display as *mut _);
+            if manager.is_null() {
+                panic!("Could not create the primary selection device manager global");
+            }
+            let data_control = wlr_data_control_manager_v1_create(display as *mut _);
+            if data_control.is_null() {
+                panic!("Could not create the data control manager global");
+            }
+            if !xwayland.is_null() {
+                wlr_xwayland_set_seat(xwayland, seat);
+            }
+            add_listener(&mut (*seat).events.request_set_selection as *mut _ as *mut _,
+                         selection_requested);
+            add_listener(&mut (*seat).events.request_set_primary_selection as *mut _ as *mut _,
+                         primary_selection_requested);
+            add_listener(&mut (*seat).events.set_selection as *mut _ as *mut _,
+                         selection_set);
+            Selection { display,
+                        seat,
+                        data_control: (*data_control).global as *mut _,
+                        ..Selection::default() }
+        }
+    }
+
+    /// Start reading the new clipboard of the seat if it should be kept.
+    unsafe fn selection_changed(&mut self, persist: bool) {
+        let source = (*self.seat).selection_source;
+        // The clipboard is cleared before the source is destroyed, so the
+        // copy is kept until another client sets the clipboard.
+        if source.is_null() || (*source).impl_ == &SAVED_SOURCE_IMPL as *const _ {
+            return
+        }
+        self.forget();
+        if persist {
+            self.save(source);
+        }
+    }
+
+    /// Read every mime type of the clipboard from the client source.
+    unsafe fn save(&mut self, source: *mut wlr_data_source) {
+        let destroy = add_listener(&mut (*source).events.destroy as *mut _ as *mut _,
+                                   source_destroyed);
+        self.source = Some((source, destroy));
+        let event_loop = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                       wl_display_get_event_loop,
+                                       self.display);
+        for mime_type in mime_types(source) {
+            let (read_fd, write_fd) = match unistd::pipe2(fcntl::O_CLOEXEC) {
+                Ok(fds) => fds,
+                Err(err) => {
+                    warn!("Could not keep the clipboard: {}", err);
+                    return
+                }
+            };
+            fcntl::fcntl(read_fd, FcntlArg::F_SETFL(fcntl::O_NONBLOCK)).ok();
+            let c_mime_type = CString::new(mime_type.clone()).expect("Mime type had a nul byte");
+            // wlroots closes the write end once it passed it on.
+            wlr_data_source_send(source, c_mime_type.as_ptr(), write_fd);
+            let event_source = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                             wl_event_loop_add_fd,
+                                             event_loop,
+                                             read_fd,
+                                             WL_EVENT_READABLE,
+                                             offer_readable,
+                                             ptr::null_mut());
+            self.reads.push(Read { fd: read_fd,
+                                   event_source,
+                                   mime_type,
+                                   data: Vec::new() });
+        }
+    }
+
+    /// Read what the client sent for the mime type with the file descriptor.
+    unsafe fn read(&mut self, fd: RawFd) {
+        let index = match self.reads.iter().position(|read| read.fd == fd) {
+            Some(index) => index,
+            None => return
+        };
+        let mut buffer = [0; 4096];
+        let complete = loop {
+            let read = &mut self.reads[index];
+            match unistd::read(fd, &mut buffer) {
+                Ok(0) => break true,
+                Ok(count) => {
+                    read.data.extend_from_slice(&buffer[..count]);
+                    if read.data.len() > MAX_SAVED_SIZE {
+                        warn!("Not keeping {} from the clipboard, it's too big", read.mime_type);
+                        break false
+                    }
+                }
+                Err(nix::Error::Sys(Errno::EAGAIN)) => return,
+                Err(nix::Error::Sys(Errno::EINTR)) => {}
+                Err(_) => break false
+            }
+        };
+        let Read { fd,
+                   event_source,
+                   mime_type,
+                   data } = self.reads.remove(index);
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_remove, event_source);
+        unistd::close(fd).ok();
+        if complete {
+            self.saved.push(Offer { mime_type,
+                                    data: Rc::new(data) });
+        }
+        self.restore();
+    }
+
+    /// Offer the copy of the clipboard if the client source is gone and
+    /// everything was read.
+    unsafe fn restore(&mut self) {
+        if self.source.is_some() || !self.reads.is_empty() || self.saved.is_empty() {
+            return
+        }
+        let offers = mem::replace(&mut self.saved, Vec::new());
+        // Another client may have set the clipboard in the meantime.
+        if !(*self.seat).selection_source.is_null() {
+            return
+        }
+        let source = create_saved_source(self.display, offers);
+        let serial = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_next_serial, self.display);
+        wlr_seat_set_selection(self.seat, source, serial);
+    }
+
+    /// Stop keeping the clipboard.
+    unsafe fn forget(&mut self) {
+        if let Some((_, destroy)) = self.source.take() {
+            remove_listener(destroy);
+        }
+        for read in self.reads.drain(..) {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_remove, read.event_source);
+            unistd::close(read.fd).ok();
+        }
+        self.saved.clear();
+    }
+}
+
+unsafe fn mime_types(source: *mut wlr_data_source) -> Vec<String> {
+    let array = &(*source).mime_types;
+    let mime_types = array.data as *const *const c_char;
+    (0..array.size / mem::size_of::<*const c_char>())
+        .map(|index| CStr::from_ptr(*mime_types.offset(index as isize)))
+        .map(|mime_type| mime_type.to_string_lossy().into_owned())
+        .collect()
+}
+
+unsafe fn create_saved_source(display: *mut wl_display,
+                              offers: Vec<Offer>)
+                              -> *mut wlr_data_source {
+    let saved = Box::into_raw(Box::new(SavedSource { source: mem::zeroed(),
+                                                     display,
+                                                     offers }));
+    wlr_data_source_init(&mut (*saved).source, &SAVED_SOURCE_IMPL);
+    for offer in &(*saved).offers {
+        let mime_type = CString::new(offer.mime_type.clone()).expect("Mime type had a nul byte");
+        let slot = wl_array_add(&mut (*saved).source.mime_types,
+                                mem::size_of::<*mut c_char>()) as *mut *mut c_char;
+        if !slot.is_null() {
+            // wlroots frees the mime types along with the source.
+            *slot = libc::strdup(mime_type.as_ptr());
+        }
+    }
+    &mut (*saved).source
+}
+
+unsafe extern "C" fn saved_source_send(source: *mut wlr_data_source,
+                                       mime_type: *const c_char,
+                                       fd: i32) {
+    let saved = source as *mut SavedSource;
+    let mime_type = CStr::from_ptr(mime_type).to_string_lossy();
+    let data = match (*saved).offers.iter().find(|offer| offer.mime_type == mime_type) {
+        Some(offer) => offer.data.clone(),
+        None => {
+            unistd::close(fd).ok();
+            return
+        }
+    };
+    // The client may read slowly, so the copy is written whenever it's ready
+    // for more instead of all at once.
+    fcntl::fcntl(fd, FcntlArg::F_SETFL(fcntl::O_NONBLOCK)).ok();
+    let write = Box::into_raw(Box::new(Write { fd,
+                                               event_source: ptr::null_mut(),
+                                               data,
+                                               written: 0 }));
+    let event_loop = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                   wl_display_get_event_loop,
+                                   (*saved).display);
+    (*write).event_source = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                          wl_event_loop_add_fd,
+                                          event_loop,
+                                          fd,
+                                          WL_EVENT_WRITABLE,
+                                          offer_writable,
+                                          write as *mut c_void);
+}
+
+unsafe extern "C" fn saved_source_destroy(source: *mut wlr_data_source) {
+    // Writes that are still going on have their own reference to the data.
+    Box::from_raw(source as *mut SavedSource);
+}
+
+unsafe extern "C" fn offer_readable(fd: c_int, _: u32, _: *mut c_void) -> c_int {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        server.selection.read(fd);
+    }).unwrap();
+    0
+}
+
+unsafe extern "C" fn offer_writable(fd: c_int, _: u32, data: *mut c_void) -> c_int {
+    let write = data as *mut Write;
+    let done = loop {
+        let written = (*write).written;
+        if written == (*write).data.len() {
+            break true
+        }
+        match unistd::write(fd, &(*write).data[written..]) {
+            Ok(count) => (*write).written += count,
+            Err(nix::Error::Sys(Errno::EAGAIN)) => break false,
+            Err(nix::Error::Sys(Errno::EINTR)) => {}
+            Err(_) => break true
+        }
+    };
+    if done {
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_source_remove, (*write).event_source);
+        unistd::close((*write).fd).ok();
+        Box::from_raw(write);
+    }
+    0
+}
+
+unsafe extern "C" fn selection_requested(_: *mut wl_listener, data: *mut c_void) {
+    let event = data as *mut wlr_seat_request_set_selection_event;
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        wlr_seat_set_selection(server.selection.seat, (*event).source, (*event).serial);
+    }).unwrap();
+}
+
+unsafe extern "C" fn primary_selection_requested(_: *mut wl_listener, data: *mut c_void) {
+    let event = data as *mut wlr_seat_request_set_primary_selection_event;
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        wlr_seat_set_primary_selection(server.selection.seat, (*event).source, (*event).serial);
+    }).unwrap();
+}
+
+unsafe extern "C" fn selection_set(_: *mut wl_listener, _: *mut c_void) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        let persist = server.config.persist_clipboard;
+        server.selection.selection_changed(persist);
+    }).unwrap();
+}
+
+unsafe extern "C" fn source_destroyed(listener: *mut wl_listener, _: *mut c_void) {
+    if let Some(compositor) = wlroots::compositor_handle() {
+        // This can fail if the compositor is shutting down, in which case
+        // there is nobody left to paste anyways.
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let selection = &mut server.selection;
+            selection.source = None;
+            remove_listener(listener);
+            // The seat is still clearing the clipboard, so the copy is
+            // offered once it's done.
+            let event_loop = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                           wl_display_get_event_loop,
+                                           selection.display);
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_event_loop_add_idle,
+                          event_loop,
+                          restore_clipboard,
+                          ptr::null_mut());
+        }).ok();
+    }
+}
+
+unsafe extern "C" fn restore_clipboard(_: *mut c_void) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        server.selection.restore();
+    }).unwrap();
+}
@@ -0,0 +1,122 @@
+//! Switching virtual terminals, and pausing and resuming when the session is
+//! switched away from and back to.
+//!
+//! There is only a session when Way Cooler runs on a TTY, not when it's
+//! nested in another compositor or on the headless backend.
+
+use std::{mem, os::raw::c_void, ptr};
+
+use wayland_sys::server::{signal::wl_signal_add, wl_listener, wl_signal};
+use wlroots::{self, xkbcommon::xkb::{KEY_XF86Switch_VT_1, KEY_XF86Switch_VT_12}, Compositor,
+              wlroots_sys::{wlr_backend_get_session, wlr_session, wlr_session_change_vt}};
+
+#[derive(Debug)]
+pub struct Session {
+    session: *mut wlr_session,
+    /// Listens to the session being paused and resumed, for as long as the
+    /// session exists.
+    listener: *mut wl_listener
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session { session: ptr::null_mut(),
+                  listener: ptr::null_mut() }
+    }
+}
+
+impl Session {
+    /// Get the session of the backend, and listen for it being paused or
+    /// resumed.
+    pub fn new(compositor: &mut Compositor) -> Session {
+        unsafe {
+            let session = wlr_backend_get_session(compositor.backend.as_ptr());
+            if session.is_null() {
+                return Session::default()
+            }
+            let listener = Box::into_raw(Box::new(wl_listener { link: mem::zeroed(),
+                                                                notify: session_signal }));
+            wl_signal_add(&mut (*session).session_signal as *mut _ as *mut wl_signal, listener);
+            Session { session, listener }
+        }
+    }
+
+    /// Whether Way Cooler runs on a virtual terminal it can switch away from.
+    ///
+    /// Nested, headless and seat-less sessions can't.
+    pub fn can_switch_vt(&self) -> bool {
+        !self.session.is_null() && unsafe { (*self.session).vtnr != 0 }
+    }
+
+    /// Switch to the virtual terminal.
+    pub fn switch_vt(&self, vt: u32) {
+        if self.session.is_null() {
+            warn!("Can't switch to VT {} without a session", vt);
+            return
+        }
+        unsafe {
+            if !wlr_session_change_vt(self.session, vt) {
+                warn!("Could not switch to VT {}", vt);
+            }
+        }
+    }
+}
+
+/// The virtual terminal an XF86Switch_VT_* keysym switches to.
+pub fn vt_for_keysym(keysym: u32) -> Option<u32> {
+    if keysym >= KEY_XF86Switch_VT_1 && keysym <= KEY_XF86Switch_VT_12 {
+        Some(keysym - KEY_XF86Switch_VT_1 + 1)
+    } else {
+        None
+    }
+}
+
+/// Forget about any input that was in progress, it was released on another
+/// virtual terminal.
+pub fn session_paused(server: &mut ::Server) {
+    info!("Session paused");
+    let seat = &mut server.seat;
+    seat.action = None;
+    seat.bound_keys.clear();
+    seat.touch_points.clear();
+    seat.drag_modifier_held = false;
+}
+
+/// Draw everything again, the outputs were used by someone else.
+pub fn session_resumed(server: &mut ::Server) {
+    info!("Session resumed");
+    ::damage_whole(server);
+}
+
+unsafe extern "C" fn session_signal(_: *mut wl_listener, data: *mut c_void) {
+    let active = (*(data as *mut wlr_session)).active;
+    if let Some(compositor) = wlroots::compositor_handle() {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if active {
+                session_resumed(server)
+            } else {
+                session_paused(server)
+            }
+        }).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wlroots::xkbcommon::xkb::{KEY_F1, KEY_XF86Switch_VT_1, KEY_XF86Switch_VT_12};
+
+    use super::*;
+
+    #[test]
+    fn vt_keysyms() {
+        assert_eq!(vt_for_keysym(KEY_XF86Switch_VT_1), Some(1));
+        assert_eq!(vt_for_keysym(KEY_XF86Switch_VT_12), Some(12));
+        assert_eq!(vt_for_keysym(KEY_F1), None);
+    }
+
+    #[test]
+    fn no_vt_switching_without_a_session() {
+        assert!(!Session::default().can_switch_vt());
+    }
+}
@@ -0,0 +1,223 @@
+//! Negotiates with xdg-shell clients whether they draw their own title bars
+//! and borders, or leave it to Way Cooler, with the xdg-decoration protocol.
+//!
+//! Server side decorations are only borders, their width and colour are set
+//! by the window manager.
+
+use std::{os::raw::c_void, rc::Rc};
+
+use wayland_sys::server::{wl_display, wl_listener};
+use wlroots::{self, wlroots_sys::*, Area, Origin, Size};
+
+use ::ffi::{add_listener, remove_listener};
+
+use self::wlr_xdg_toplevel_decoration_v1_mode::*;
+
+/// Who draws the decorations of a view.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DecorationPolicy {
+    /// The client decides, decorations are drawn by Way Cooler if it
+    /// doesn't care.
+    Client,
+    /// Decorations are always drawn by Way Cooler.
+    Server
+}
+
+impl Default for DecorationPolicy {
+    fn default() -> DecorationPolicy {
+        DecorationPolicy::Client
+    }
+}
+
+/// The border that is drawn around a view.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Border {
+    pub width: i32,
+    /// The colour as 0xRRGGBBAA.
+    pub color: u32
+}
+
+impl Border {
+    /// The colour with premultiplied alpha, as the renderer expects it.
+    pub fn rgba(&self) -> [f32; 4] {
+        let channel = |shift: u32| ((self.color >> shift) & 0xff) as f32 / 255.0;
+        let alpha = channel(0);
+        [channel(24) * alpha, channel(16) * alpha, channel(8) * alpha, alpha]
+    }
+
+    /// The area the border around the view geometry takes up, including the
+    /// view.
+    ///
+    /// Returns `None` if there is no border.
+    pub fn area(&self, geometry: Area) -> Option<Area> {
+        if self.width <= 0 {
+            return None
+        }
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = geometry;
+        Some(Area::new(Origin::new(x - self.width, y - self.width),
+                       Size::new(width + 2 * self.width, height + 2 * self.width)))
+    }
+
+    /// The top, bottom, left and right edges of the border around the view
+    /// geometry.
+    pub fn edges(&self, geometry: Area) -> Vec<Area> {
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = match self.area(geometry) {
+            Some(area) => area,
+            None => return Vec::new()
+        };
+        let border = self.width;
+        vec![Area::new(Origin::new(x, y), Size::new(width, border)),
+             Area::new(Origin::new(x, y + height - border), Size::new(width, border)),
+             Area::new(Origin::new(x, y + border), Size::new(border, height - 2 * border)),
+             Area::new(Origin::new(x + width - border, y + border),
+                       Size::new(border, height - 2 * border))]
+    }
+}
+
+#[derive(Debug)]
+struct Decoration {
+    decoration: *mut wlr_xdg_toplevel_decoration_v1,
+    request_mode: *mut wl_listener,
+    destroy: *mut wl_listener
+}
+
+/// The decorations of the toplevels.
+#[derive(Debug, Default)]
+pub struct Decorations {
+    decorations: Vec<Decoration>
+}
+
+impl Decorations {
+    /// Advertise the decoration manager global on the display.
+    pub fn new(display: *mut wl_display) -> Decorations {
+        unsafe {
+            let manager = wlr_xdg_decoration_manager_v1_create(display as *mut _);
+            if manager.is_null() {
+                panic!("Could not create the decoration manager global");
+            }
+            add_listener(&mut (*manager).events.new_toplevel_decoration as *mut _ as *mut _,
+                         new_decoration);
+            Decorations::default()
+        }
+    }
+}
+
+/// Tell the client of the view who draws its decorations, e.g. after the
+/// policy of the view changed.
+pub fn update_decoration(server: &mut ::Server, view: &Rc<::View>) {
+    let xdg_surface = match view.shell.clone() {
+        ::Shell::Xdg(xdg_surface) => xdg_surface,
+        // The other shells don't negotiate decorations.
+        _ => return
+    };
+    let xdg_surface = match xdg_surface.run(|xdg_surface| xdg_surface.as_ptr()) {
+        Ok(xdg_surface) => xdg_surface,
+        Err(_) => return
+    };
+    let decoration = server.decorations
+                           .decorations
+                           .iter()
+                           .map(|decoration| decoration.decoration)
+                           .find(|&decoration| unsafe { (*decoration).surface == xdg_surface });
+    if let Some(decoration) = decoration {
+        unsafe { set_mode(decoration, view.decoration_policy.get()) }
+    }
+}
+
+unsafe fn set_mode(decoration: *mut wlr_xdg_toplevel_decoration_v1, policy: DecorationPolicy) {
+    let mode = match (policy, (*decoration).client_pending_mode) {
+        (DecorationPolicy::Client, WLR_XDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE) => {
+            WLR_XDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE
+        }
+        _ => WLR_XDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE
+    };
+    wlr_xdg_toplevel_decoration_v1_set_mode(decoration, mode);
+}
+
+/// The policy of the view the decoration belongs to.
+///
+/// Decorations are created before the view is mapped, in which case the
+/// default policy is used until the window manager sets one.
+fn policy(server: &mut ::Server,
+          decoration: *mut wlr_xdg_toplevel_decoration_v1)
+          -> DecorationPolicy {
+    server.views
+          .iter()
+          .find(|view| match view.shell.clone() {
+                    ::Shell::Xdg(xdg_surface) => {
+                        xdg_surface.run(|xdg_surface| xdg_surface.as_ptr()).ok() ==
+                        Some(unsafe { (*decoration).surface })
+                    }
+                    _ => false
+                })
+          .map(|view| view.decoration_policy.get())
+          .unwrap_or_default()
+}
+
+unsafe extern "C" fn new_decoration(_: *mut wl_listener, data: *mut c_void) {
+    let decoration = data as *mut wlr_xdg_toplevel_decoration_v1;
+    let request_mode = add_listener(&mut (*decoration).events.request_mode as *mut _ as *mut _,
+                                    mode_requested);
+    let destroy = add_listener(&mut (*decoration).events.destroy as *mut _ as *mut _,
+                               decoration_destroyed);
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        server.decorations.decorations.push(Decoration { decoration,
+                                                         request_mode,
+                                                         destroy });
+        set_mode(decoration, policy(server, decoration));
+    }).unwrap();
+}
+
+unsafe extern "C" fn mode_requested(_: *mut wl_listener, data: *mut c_void) {
+    let decoration = data as *mut wlr_xdg_toplevel_decoration_v1;
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        set_mode(decoration, policy(server, decoration));
+    }).unwrap();
+}
+
+unsafe extern "C" fn decoration_destroyed(_: *mut wl_listener, data: *mut c_void) {
+    let decoration = data as *mut wlr_xdg_toplevel_decoration_v1;
+    if let Some(compositor) = wlroots::compositor_handle() {
+        // This can fail if the compositor is shutting down, in which case
+        // the listeners are never called again anyways.
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let decorations = &mut server.decorations.decorations;
+            if let Some(index) = decorations.iter().position(|d| d.decoration == decoration) {
+                let Decoration { request_mode, destroy, .. } = decorations.remove(index);
+                remove_listener(request_mode);
+                remove_listener(destroy);
+            }
+        }).ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn borders_surround_the_geometry() {
+        let geometry = Area::new(Origin::new(10, 10), Size::new(100, 50));
+        let border = Border { width: 2,
+                              color: 0xff0000ff };
+        assert_eq!(border.area(geometry),
+                   Some(Area::new(Origin::new(8, 8), Size::new(104, 54))));
+        assert_eq!(border.edges(geometry),
+                   vec![Area::new(Origin::new(8, 8), Size::new(104, 2)),
+                        Area::new(Origin::new(8, 60), Size::new(104, 2)),
+                        Area::new(Origin::new(8, 10), Size::new(2, 50)),
+                        Area::new(Origin::new(110, 10), Size::new(2, 50))]);
+        assert_eq!(border.rgba(), [1.0, 0.0, 0.0, 1.0]);
+        let border = Border { width: 0,
+                              color: 0xff0000ff };
+        assert_eq!(border.area(geometry), None);
+        assert!(border.edges(geometry).is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Handles wlr-layer-shell surfaces: panels, wallpapers, lock screens and
+//! notifications that are drawn above or below the views.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use wlroots::{wlroots_sys::wlr_surface_point_accepts_input, Anchor, Area, CompositorHandle, Layer,
+              LayerShellHandler, LayerShellManagerHandler, LayerSurfaceHandle, Origin,
+              OutputHandle, Size, SurfaceHandle, SurfaceHandler};
+
+/// The layers that are drawn below the views, from the bottom up.
+pub const LAYERS_BELOW_VIEWS: [Layer; 2] = [Layer::Background, Layer::Bottom];
+
+/// The layers that are drawn above the views, from the bottom up.
+pub const LAYERS_ABOVE_VIEWS: [Layer; 2] = [Layer::Top, Layer::Overlay];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerSurface {
+    pub layer_surface: LayerSurfaceHandle,
+    /// The output the surface is placed on.
+    pub output: OutputHandle,
+    pub layer: Layer,
+    /// Where the surface is placed in output layout coordinates.
+    pub area: Cell<Area>,
+    pub mapped: Cell<bool>
+}
+
+impl LayerSurface {
+    pub fn surface(&self) -> SurfaceHandle {
+        self.layer_surface.run(|layer_surface| layer_surface.surface())
+            .expect("A layer shell client did not provide us a surface")
+    }
+
+    /// Whether the surface wants keyboard focus.
+    pub fn keyboard_interactive(&self) -> bool {
+        self.layer_surface.run(|layer_surface| layer_surface.current().keyboard_interactive())
+            .unwrap_or(false)
+    }
+
+    pub fn for_each_surface(&self, f: &mut FnMut(SurfaceHandle, i32, i32)) {
+        with_handles!([(layer_surface: {&self.layer_surface})] => {
+            layer_surface.for_each_surface(f);
+        }).unwrap();
+    }
+}
+
+/// Find the mapped layer surface in one of the layers that is under the
+/// point, along with the sub surface or popup and the surface local
+/// coordinates.
+///
+/// The layers are searched from the top down.
+pub fn layer_surface_at(layer_surfaces: &[Rc<LayerSurface>],
+                        layers: &[Layer],
+                        lx: f64,
+                        ly: f64)
+                        -> Option<(Rc<LayerSurface>, SurfaceHandle, f64, f64)> {
+    for layer in layers.iter().rev() {
+        let surfaces = layer_surfaces.iter()
+                                     .rev()
+                                     .filter(|surface| surface.layer == *layer)
+                                     .filter(|surface| surface.mapped.get());
+        for layer_surface in surfaces {
+            let Origin { x, y } = layer_surface.area.get().origin;
+            let mut drawn = Vec::new();
+            layer_surface.for_each_surface(&mut |surface, sx, sy| drawn.push((surface, sx, sy)));
+            // Popups, e.g. the menus of a panel, are drawn last and can
+            // reach outside of the surface, so they are checked first.
+            for (surface, surface_x, surface_y) in drawn.into_iter().rev() {
+                let sx = lx - (x + surface_x) as f64;
+                let sy = ly - (y + surface_y) as f64;
+                let accepts_input = with_handles!([(surface: {&surface})] => {
+                    unsafe { wlr_surface_point_accepts_input(surface.as_ptr(), sx, sy) }
+                }).unwrap_or(false);
+                if accepts_input {
+                    return Some((layer_surface.clone(), surface, sx, sy))
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Place all of the layer surfaces on the output, and update the area of the
+/// output that is left for the views.
+pub fn arrange_layers(server: &mut ::Server, output: &OutputHandle) {
+    let ::Server { ref mut layout,
+                   ref layer_surfaces,
+                   ref mut usable_areas,
+                   .. } = *server;
+    let full_area = match with_handles!([(layout: {layout}), (output: {output})] => {
+        layout.get_box(output)
+    }) {
+        Ok(area) => area,
+        // The output is not in the layout, there is nothing to arrange.
+        Err(_) => {
+            usable_areas.remove(output);
+            return
+        }
+    };
+    let mut usable_area = full_area;
+    let surfaces: Vec<_> = layer_surfaces.iter()
+                                         .filter(|surface| surface.output == *output)
+                                         .collect();
+    // Exclusive zones are claimed first so that every other surface is
+    // placed in what remains.
+    for &exclusive in &[true, false] {
+        for layer in LAYERS_ABOVE_VIEWS.iter().rev().chain(LAYERS_BELOW_VIEWS.iter().rev()) {
+            for surface in surfaces.iter().filter(|surface| surface.layer == *layer) {
+                arrange_surface(surface, full_area, &mut usable_area, exclusive);
+            }
+        }
+    }
+    usable_areas.insert(output.clone(), usable_area);
+}
+
+fn arrange_surface(surface: &LayerSurface,
+                   full_area: Area,
+                   usable_area: &mut Area,
+                   exclusive: bool) {
+    let mapped = surface.mapped.get();
+    let res = with_handles!([(layer_surface: {&surface.layer_surface})] => {
+        let (anchor, exclusive_zone, margin, (desired_width, desired_height)) = {
+            let state = layer_surface.current();
+            (state.anchor(), state.exclusive_zone(), state.margin(), state.desired_size())
+        };
+        if exclusive != (exclusive_zone > 0) {
+            return None
+        }
+        let bounds = if exclusive_zone == -1 { full_area } else { *usable_area };
+        let (margin_top, margin_right, margin_bottom, margin_left) =
+            (margin.0 as i32, margin.1 as i32, margin.2 as i32, margin.3 as i32);
+        let Area { origin: Origin { x: bounds_x, y: bounds_y },
+                   size: Size { width: bounds_width, height: bounds_height } } = bounds;
+        let (x, y);
+        let (mut width, mut height) = (desired_width as i32, desired_height as i32);
+
+        let horizontal = Anchor::LEFT | Anchor::RIGHT;
+        if anchor.contains(horizontal) {
+            if width == 0 {
+                width = bounds_width;
+            }
+            x = bounds_x + (bounds_width - width) / 2 + margin_left;
+            width -= margin_left + margin_right;
+        } else if anchor.contains(Anchor::LEFT) {
+            x = bounds_x + margin_left;
+        } else if anchor.contains(Anchor::RIGHT) {
+            x = bounds_x + bounds_width - width - margin_right;
+        } else {
+            x = bounds_x + bounds_width / 2 - width / 2;
+        }
+
+        let vertical = Anchor::TOP | Anchor::BOTTOM;
+        if anchor.contains(vertical) {
+            if height == 0 {
+                height = bounds_height;
+            }
+            y = bounds_y + (bounds_height - height) / 2 + margin_top;
+            height -= margin_top + margin_bottom;
+        } else if anchor.contains(Anchor::TOP) {
+            y = bounds_y + margin_top;
+        } else if anchor.contains(Anchor::BOTTOM) {
+            y = bounds_y + bounds_height - height - margin_bottom;
+        } else {
+            y = bounds_y + bounds_height / 2 - height / 2;
+        }
+
+        if width <= 0 || height <= 0 {
+            warn!("Closing layer surface that does not fit on its output");
+            layer_surface.close();
+            return None
+        }
+        layer_surface.configure(width as u32, height as u32);
+        if mapped {
+            apply_exclusive_zone(usable_area, anchor, exclusive_zone, margin);
+        }
+        Some(Area::new(Origin::new(x, y), Size::new(width, height)))
+    });
+    if let Ok(Some(area)) = res {
+        surface.area.set(area);
+    }
+}
+
+/// Shrink the usable area by the exclusive zone of a surface.
+///
+/// An exclusive zone only applies if the surface is anchored to a single
+/// edge, or to an edge and both of the edges next to it.
+fn apply_exclusive_zone(usable_area: &mut Area,
+                        anchor: Anchor,
+                        exclusive_zone: i32,
+                        margin: (u32, u32, u32, u32)) {
+    if exclusive_zone <= 0 {
+        return
+    }
+    let (margin_top, margin_right, margin_bottom, margin_left) = margin;
+    let horizontal = Anchor::LEFT | Anchor::RIGHT;
+    let vertical = Anchor::TOP | Anchor::BOTTOM;
+    let anchored_to = |edge: Anchor, sides: Anchor| anchor == edge || anchor == edge | sides;
+    let Area { ref mut origin,
+               ref mut size } = *usable_area;
+    if anchored_to(Anchor::TOP, horizontal) {
+        let zone = exclusive_zone + margin_top as i32;
+        origin.y += zone;
+        size.height -= zone;
+    } else if anchored_to(Anchor::BOTTOM, horizontal) {
+        size.height -= exclusive_zone + margin_bottom as i32;
+    } else if anchored_to(Anchor::LEFT, vertical) {
+        let zone = exclusive_zone + margin_left as i32;
+        origin.x += zone;
+        size.width -= zone;
+    } else if anchored_to(Anchor::RIGHT, vertical) {
+        size.width -= exclusive_zone + margin_right as i32;
+    }
+}
+
+/// Give keyboard focus to the top most layer surface that wants it, or back to
+/// the focused view if there is none.
+pub fn update_layer_focus(server: &mut ::Server) {
+    let ::Server { ref mut seat,
+                   ref layer_surfaces,
+                   .. } = *server;
+    let focus = LAYERS_ABOVE_VIEWS.iter().rev().filter_map(|layer| {
+        layer_surfaces.iter()
+                      .rev()
+                      .filter(|surface| surface.layer == *layer && surface.mapped.get())
+                      .find(|surface| surface.keyboard_interactive())
+    }).next().cloned();
+    seat.focus_layer(focus);
+}
+
+/// Rearrange the output of the layer surface and update the keyboard focus.
+fn layer_surface_changed(server: &mut ::Server, layer_surface: &LayerSurfaceHandle) {
+    let output = server.layer_surfaces.iter()
+                       .find(|surface| surface.layer_surface == *layer_surface)
+                       .map(|surface| surface.output.clone());
+    if let Some(output) = output {
+        arrange_layers(server, &output);
+    }
+    update_layer_focus(server);
+    ::damage_changes(server);
+}
+
+pub struct LayerShell;
+
+impl LayerShellHandler for LayerShell {
+    fn on_commit(&mut self,
+                 compositor: CompositorHandle,
+                 _: SurfaceHandle,
+                 layer_surface: LayerSurfaceHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            layer_surface_changed(server, &layer_surface);
+        }).unwrap();
+    }
+
+    fn on_map(&mut self,
+              compositor: CompositorHandle,
+              _: SurfaceHandle,
+              layer_surface: LayerSurfaceHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(surface) = server.layer_surfaces.iter()
+                                         .find(|surface| surface.layer_surface == layer_surface) {
+                surface.mapped.set(true);
+            }
+            layer_surface_changed(server, &layer_surface);
+        }).unwrap();
+    }
+
+    fn on_unmap(&mut self,
+                compositor: CompositorHandle,
+                _: SurfaceHandle,
+                layer_surface: LayerSurfaceHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(surface) = server.layer_surfaces.iter()
+                                         .find(|surface| surface.layer_surface == layer_surface) {
+                surface.mapped.set(false);
+            }
+            layer_surface_changed(server, &layer_surface);
+        }).unwrap();
+    }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, layer_surface: LayerSurfaceHandle) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let output = server.layer_surfaces.iter()
+                               .find(|surface| surface.layer_surface == layer_surface)
+                               .map(|surface| surface.output.clone());
+            server.layer_surfaces.retain(|surface| surface.layer_surface != layer_surface);
+            if let Some(output) = output {
+                arrange_layers(server, &output);
+            }
+            update_layer_focus(server);
+            ::damage_changes(server);
+        }).unwrap();
+    }
+}
+
+pub struct LayerShellManager;
+
+impl LayerShellManagerHandler for LayerShellManager {
+    fn new_surface(&mut self,
+                   compositor: CompositorHandle,
+                   layer_surface: LayerSurfaceHandle)
+                   -> (Option<Box<LayerShellHandler>>, Option<Box<SurfaceHandler>>) {
+        let handle = layer_surface.clone();
+        let added = with_handles!([(compositor: {compositor}),
+                                   (layer_surface: {layer_surface})] => {
+            let server: &mut ::Server = compositor.into();
+            // Clients may leave it up to us to pick the output.
+            let output = match layer_surface.output() {
+                Some(output) => Some(output),
+                None => server.outputs.get(0).cloned().map(|mut output| {
+                    // wlroots keeps the output of the surface as well, so
+                    // it's set there too.
+                    if let Ok(output_ptr) = output.run(|output| output.as_ptr()) {
+                        unsafe { (*layer_surface.as_ptr()).output = output_ptr }
+                    }
+                    output
+                })
+            };
+            match output {
+                Some(output) => {
+                    let surface = LayerSurface { layer_surface: handle,
+                                                 output,
+                                                 layer: layer_surface.layer(),
+                                                 area: Cell::new(Area::default()),
+                                                 mapped: Cell::new(false) };
+                    server.layer_surfaces.push(Rc::new(surface));
+                    true
+                },
+                None => {
+                    warn!("Closing layer surface because there are no outputs");
+                    layer_surface.close();
+                    false
+                }
+            }
+        }).unwrap();
+        if added {
+            (Some(Box::new(LayerShell)), Some(Box::new(::SurfaceDamage)))
+        } else {
+            (None, None)
+        }
+    }
+}
@@ -1,16 +1,25 @@
+mod decoration;
+mod layer_shell;
+mod view_state;
 mod xdg_v6;
 mod xdg;
 
+pub use self::decoration::*;
+pub use self::layer_shell::*;
+pub use self::view_state::*;
 pub use self::xdg_v6::*;
 pub use self::xdg::*;
 
-use wlroots::{Area, HandleResult, SurfaceHandle, XdgV6ShellSurfaceHandle,
-              XdgShellSurfaceHandle};
+use std::rc::Rc;
+
+use wlroots::{Area, HandleResult, Origin, Size, SurfaceHandle, XWaylandSurfaceHandle,
+              XdgV6ShellSurfaceHandle, XdgShellSurfaceHandle};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Shell {
     XdgV6(XdgV6ShellSurfaceHandle),
-    Xdg(XdgShellSurfaceHandle)
+    Xdg(XdgShellSurfaceHandle),
+    XWayland(XWaylandSurfaceHandle)
 }
 
 impl Shell {
@@ -24,6 +33,12 @@ impl Shell {
             Shell::Xdg(shell) => {
                 shell.run(|shell| shell.surface())
                     .expect("An xdg client did not provide us a surface")
+            },
+            Shell::XWayland(shell) => {
+                shell.run(|shell| shell.surface())
+                     .ok()
+                     .and_then(|surface| surface)
+                     .expect("An XWayland client did not provide us a surface")
             }
         }
     }
@@ -32,11 +47,53 @@ impl Shell {
     pub fn geometry(&mut self) -> HandleResult<Area> {
         match *self {
             Shell::XdgV6(ref mut shell) => shell.run(|shell| shell.geometry()),
-            Shell::Xdg(ref mut shell) => shell.run(|shell| shell.geometry())
+            Shell::Xdg(ref mut shell) => shell.run(|shell| shell.geometry()),
+            Shell::XWayland(ref mut shell) => {
+                // X11 has no notion of the part of the surface that is the
+                // window, so the geometry is the whole surface, including
+                // any shadows that client side decorations draw.
+                shell.run(|shell| Area::new(Origin::default(),
+                                            Size::new(shell.width() as i32,
+                                                      shell.height() as i32)))
+            }
         }
     }
 }
 
+/// Get the area a popup should be constrained to, in the coordinate space of
+/// the toplevel that it belongs to.
+///
+/// This is the box of the output the toplevel is on. `None` is returned if the
+/// parent surface isn't part of any view.
+pub fn popup_constraint_box(server: &mut ::Server, parent: &SurfaceHandle) -> Option<Area> {
+    let ::Server { ref mut layout,
+                   ref views,
+                   .. } = *server;
+    let view = views.iter().find(|view| view_has_surface(view, parent))?;
+    let Area { origin: Origin { x: view_x, y: view_y },
+               size: Size { width, height } } = view.geometry();
+    let (center_x, center_y) = (view_x + width / 2, view_y + height / 2);
+    with_handles!([(layout: {layout})] => {
+        let mut output = layout.output_at(center_x as f64, center_y as f64)?;
+        with_handles!([(output: {&mut output})] => {
+            let Area { origin: Origin { x, y }, size } = layout.get_box(output);
+            Area { origin: Origin { x: x - view_x, y: y - view_y },
+                   size }
+        }).ok()
+    }).ok()
+      .and_then(|area| area)
+}
+
+/// Determine if the surface is one of the surfaces of the view, including any
+/// of its sub surfaces and popups.
+fn view_has_surface(view: &Rc<::View>, surface: &SurfaceHandle) -> bool {
+    let mut found = false;
+    view.for_each_surface(&mut |view_surface: SurfaceHandle, _, _| {
+        found = found || view_surface == *surface;
+    });
+    found
+}
+
 impl Into<Shell> for XdgV6ShellSurfaceHandle {
     fn into(self) -> Shell {
         Shell::XdgV6(self)
@@ -48,3 +105,9 @@ impl Into<Shell> for XdgShellSurfaceHandle {
         Shell::Xdg(self)
     }
 }
+
+impl Into<Shell> for XWaylandSurfaceHandle {
+    fn into(self) -> Shell {
+        Shell::XWayland(self)
+    }
+}
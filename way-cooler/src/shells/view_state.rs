@@ -0,0 +1,122 @@
+//! Maximizing, fullscreening and minimizing views.
+//!
+//! Maximized views fill the usable area of their output and fullscreen views
+//! the whole output, above the layer surfaces. Both go back to where they
+//! were before once they are neither.
+
+use std::rc::Rc;
+
+use wlroots::{Area, OutputHandle};
+
+/// Maximize the view or restore its size.
+pub fn maximize_view(server: &mut ::Server, view: &Rc<::View>, maximized: bool) {
+    if view.maximized.get() == maximized {
+        return
+    }
+    save_geometry(view);
+    view.maximized.set(maximized);
+    view.set_maximized(maximized);
+    if !view.fullscreen.get() {
+        let output = view_output(server, view);
+        arrange_view(server, view, output);
+    }
+    ::damage_changes(server);
+}
+
+/// Make the view fullscreen on the output, or on the output it is on if
+/// there is none, or restore its size.
+pub fn fullscreen_view(server: &mut ::Server,
+                       view: &Rc<::View>,
+                       fullscreen: bool,
+                       output: Option<OutputHandle>) {
+    if view.fullscreen.get() == fullscreen {
+        return
+    }
+    save_geometry(view);
+    view.fullscreen.set(fullscreen);
+    view.set_fullscreen(fullscreen);
+    let output = output.or_else(|| view_output(server, view));
+    arrange_view(server, view, output);
+    ::damage_changes(server);
+}
+
+/// Hide the view and move it to the bottom of the stack, giving the focus
+/// to the view that is on top now.
+///
+/// It is shown again when the window manager focuses it.
+pub fn minimize_view(server: &mut ::Server, view: &Rc<::View>) {
+    if view.minimized.get() {
+        return
+    }
+    view.minimized.set(true);
+    {
+        let ::Server { ref mut seat,
+                       ref mut views,
+                       .. } = *server;
+        if let Some(index) = views.iter().position(|v| v == view) {
+            let minimized = views.remove(index);
+            views.push(minimized);
+        }
+        if seat.focused.as_ref() == Some(view) {
+            match ::Seat::next_focus(views) {
+                Some(view) => seat.focus_view(view, views),
+                None => seat.clear_focus()
+            }
+        }
+    }
+    ::damage_changes(server);
+}
+
+/// The number of views from the top of the stack that are drawn above the
+/// layer surfaces: the topmost fullscreen view and the views above it.
+pub fn views_above_layers(views: &[Rc<::View>]) -> usize {
+    views.iter()
+         .position(|view| view.fullscreen.get() && !view.minimized.get())
+         .map(|index| index + 1)
+         .unwrap_or(0)
+}
+
+/// Remember the geometry of the view if it is neither maximized nor
+/// fullscreen, so it can be restored later.
+fn save_geometry(view: &::View) {
+    if !view.maximized.get() && !view.fullscreen.get() {
+        view.saved_geometry.set(Some(view.geometry()));
+    }
+}
+
+/// Move and resize the view to where its state says it should be.
+fn arrange_view(server: &mut ::Server, view: &Rc<::View>, output: Option<OutputHandle>) {
+    let area = match output {
+        Some(ref output) if view.fullscreen.get() => output_box(server, output),
+        Some(ref output) if view.maximized.get() => {
+            let usable_area = server.usable_areas.get(output).cloned();
+            usable_area.or_else(|| output_box(server, output))
+        }
+        _ if view.maximized.get() || view.fullscreen.get() => None,
+        _ => view.saved_geometry.take()
+    };
+    if let Some(area) = area {
+        view.move_resize(area);
+    }
+}
+
+/// The output under the center of the view, or the first output if there is
+/// none.
+fn view_output(server: &mut ::Server, view: &::View) -> Option<OutputHandle> {
+    let ::Server { ref mut layout,
+                   ref outputs,
+                   .. } = *server;
+    let Area { origin, size } = view.geometry();
+    let (center_x, center_y) = (origin.x + size.width / 2, origin.y + size.height / 2);
+    with_handles!([(layout: {layout})] => {
+        layout.output_at(center_x as f64, center_y as f64)
+    }).ok()
+      .and_then(|output| output)
+      .or_else(|| outputs.first().cloned())
+}
+
+fn output_box(server: &mut ::Server, output: &OutputHandle) -> Option<Area> {
+    with_handles!([(layout: {&mut server.layout}), (output: {output})] => {
+        layout.get_box(output)
+    }).ok()
+}
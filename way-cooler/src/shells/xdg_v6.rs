@@ -1,9 +1,9 @@
 use std::rc::Rc;
 
 use wlroots::{CompositorHandle, Origin, SurfaceHandle, SurfaceHandler, XdgV6ShellHandler,
-              XdgV6ShellManagerHandler, XdgV6ShellSurfaceHandle};
+              XdgV6ShellManagerHandler, XdgV6ShellState, XdgV6ShellSurfaceHandle};
 
-use wlroots::xdg_shell_v6_events::{MoveEvent, ResizeEvent};
+use wlroots::xdg_shell_v6_events::{MoveEvent, ResizeEvent, SetFullscreenEvent};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct XdgV6 {
@@ -62,6 +62,47 @@ impl XdgV6ShellHandler for XdgV6 {
         }).unwrap();
     }
 
+    fn maximize_request(&mut self,
+                        compositor: CompositorHandle,
+                        _: SurfaceHandle,
+                        shell_surface: XdgV6ShellSurfaceHandle) {
+        let shell = shell_surface.into();
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(view) = server.views.iter().find(|view| view.shell == shell).cloned() {
+                let (maximized, _) = view.requested_state();
+                ::maximize_view(server, &view, maximized);
+            }
+        }).unwrap();
+    }
+
+    fn fullscreen_request(&mut self,
+                          compositor: CompositorHandle,
+                          _: SurfaceHandle,
+                          shell_surface: XdgV6ShellSurfaceHandle,
+                          event: &SetFullscreenEvent) {
+        let shell = shell_surface.into();
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(view) = server.views.iter().find(|view| view.shell == shell).cloned() {
+                ::fullscreen_view(server, &view, event.fullscreen(), event.output());
+            }
+        }).unwrap();
+    }
+
+    fn minimize_request(&mut self,
+                        compositor: CompositorHandle,
+                        _: SurfaceHandle,
+                        shell_surface: XdgV6ShellSurfaceHandle) {
+        let shell = shell_surface.into();
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(view) = server.views.iter().find(|view| view.shell == shell).cloned() {
+                ::minimize_view(server, &view);
+            }
+        }).unwrap();
+    }
+
     fn on_commit(&mut self,
                  compositor: CompositorHandle,
                  _: SurfaceHandle,
@@ -73,32 +114,40 @@ impl XdgV6ShellHandler for XdgV6 {
         };
 
         let surface = shell_surface.into();
-        with_handles!([(compositor: {compositor})] => {
+        with_handles!([(compositor: {&compositor})] => {
             let server: &mut ::Server = compositor.into();
             let ::Server { ref mut views, .. } = *server;
 
             if let Some(view) = views.iter().find(|view| view.shell == surface).cloned() {
                 if let Some(move_resize) = view.pending_move_resize.get() {
-                    if move_resize.serial >= configure_serial {
-                        let Origin {mut x, mut y} = view.origin.get();
-                        if move_resize.update_x {
-                            x  = move_resize.area.origin.x + move_resize.area.size.width -
-                                 view.get_size().width;
-                        }
-                        if move_resize.update_y {
-                            y  = move_resize.area.origin.y + move_resize.area.size.height -
-                                 view.get_size().height;
-                        }
-
-                        view.origin.set(Origin { x, y });
-
-                        if move_resize.serial == configure_serial {
-                            view.pending_move_resize.set(None);
-                        }
+                    // The client may commit another size than it was asked
+                    // for, e.g. a terminal that snaps to its cells, so the
+                    // view is placed for the size it actually committed.
+                    let anchored = ::anchored_origin(move_resize.area,
+                                                     view.get_size(),
+                                                     move_resize.update_x,
+                                                     move_resize.update_y);
+                    let Origin { mut x, mut y } = view.origin.get();
+                    if move_resize.update_x {
+                        x = anchored.x;
+                    }
+                    if move_resize.update_y {
+                        y = anchored.y;
+                    }
+
+                    view.origin.set(Origin { x, y });
+
+                    // Once the configure has been acked, later commits keep
+                    // the origin they have.
+                    if configure_serial >= move_resize.serial {
+                        view.pending_move_resize.set(None);
                     }
                 }
             }
         }).unwrap();
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 
     fn map_request(&mut self,
@@ -107,6 +156,7 @@ impl XdgV6ShellHandler for XdgV6 {
                    shell_surface: XdgV6ShellSurfaceHandle) {
         use wlroots::XdgV6ShellState::*;
 
+        let shell: ::Shell = shell_surface.clone().into();
         let is_toplevel = dehandle!(
             // we can't combine this with the next block because it needs to use the handle
             @shell_surface = {&shell_surface};
@@ -117,21 +167,35 @@ impl XdgV6ShellHandler for XdgV6 {
         );
 
         dehandle!(
-            @compositor = {compositor};
+            @compositor = {&compositor};
             let server: &mut ::Server = compositor.into();
             let ::Server { ref mut seat,
                          ref mut views,
                          ref cursor,
                          ref mut xcursor_manager,
+                         ref layer_surfaces,
+                         ref mut window_management,
                          .. } = *server;
             if is_toplevel {
                 let view = Rc::new(::View::new(::Shell::XdgV6(shell_surface.into())));
                 views.push(view.clone());
-                seat.focus_view(view, views);
+                seat.focus_view(view.clone(), views);
+                window_management.new_window(&view, seat.focused.as_ref());
             };
             @cursor = {cursor};
-            seat.update_cursor_position(cursor, xcursor_manager, views, None)
+            seat.update_cursor_position(cursor, xcursor_manager, views, layer_surfaces, None)
         );
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            // Clients can ask to be maximized or fullscreen before they are
+            // mapped.
+            if let Some(view) = server.views.iter().find(|view| view.shell == shell).cloned() {
+                let (maximized, fullscreen) = view.requested_state();
+                ::maximize_view(server, &view, maximized);
+                ::fullscreen_view(server, &view, fullscreen, None);
+            }
+            ::damage_changes(server);
+        }).unwrap();
     }
 
     fn unmap_request(&mut self,
@@ -139,24 +203,32 @@ impl XdgV6ShellHandler for XdgV6 {
                      _: SurfaceHandle,
                      shell_surface: XdgV6ShellSurfaceHandle) {
         dehandle!(
-            @compositor = {compositor};
+            @compositor = {&compositor};
             let server: &mut ::Server = compositor.into();
             let ::Server { ref mut seat,
                            ref mut views,
                            ref cursor,
                            ref mut xcursor_manager,
+                           ref layer_surfaces,
+                           ref mut window_management,
                            .. } = *server;
             let destroyed_shell = shell_surface.into();
+            if let Some(view) = views.iter().find(|view| view.shell == destroyed_shell).cloned() {
+                window_management.close_window(&view);
+            }
             views.retain(|view| view.shell != destroyed_shell);
 
-            if let Some(view) = views.get(0).cloned() {
+            if let Some(view) = ::Seat::next_focus(views) {
                 seat.focus_view(view.clone(), views);
             } else {
                 seat.clear_focus();
             };
             @cursor = {cursor};
-            seat.update_cursor_position(cursor, xcursor_manager, views, None)
+            seat.update_cursor_position(cursor, xcursor_manager, views, layer_surfaces, None)
         );
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 
     fn destroyed(&mut self,
@@ -164,13 +236,16 @@ impl XdgV6ShellHandler for XdgV6 {
                  shell_surface: XdgV6ShellSurfaceHandle) {
         let surface = shell_surface.into();
         dehandle!(
-            @compositor = {compositor};
+            @compositor = {&compositor};
             let server: &mut ::Server = compositor.into();
             let ::Server { ref mut views, .. } = *server;
             if let Some(index) = views.iter().position(|view| view.shell == surface) {
                 views.remove(index);
             }
-        )
+        );
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 }
 
@@ -178,9 +253,22 @@ pub struct XdgV6ShellManager;
 
 impl XdgV6ShellManagerHandler for XdgV6ShellManager {
     fn new_surface(&mut self,
-                   _: CompositorHandle,
-                   _: XdgV6ShellSurfaceHandle)
+                   compositor: CompositorHandle,
+                   shell_surface: XdgV6ShellSurfaceHandle)
                    -> (Option<Box<XdgV6ShellHandler>>, Option<Box<SurfaceHandler>>) {
-        (Some(Box::new(::XdgV6::new())), None)
+        // Popups are positioned relative to their parent by wlroots using the
+        // positioner rules, we only have to keep them on the output.
+        //
+        // Outside clicks are handled by the popup grab that wlroots sets up
+        // when the client asks for one.
+        with_handles!([(compositor: {compositor}), (shell_surface: {shell_surface})] => {
+            if let Some(&mut XdgV6ShellState::Popup(ref mut popup)) = shell_surface.state() {
+                let server: &mut ::Server = compositor.into();
+                if let Some(area) = ::popup_constraint_box(server, &popup.parent()) {
+                    popup.unconstrain_from_box(area);
+                }
+            }
+        }).unwrap();
+        (Some(Box::new(::XdgV6::new())), Some(Box::new(::SurfaceDamage)))
     }
 }
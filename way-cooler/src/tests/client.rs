@@ -0,0 +1,399 @@
+//! A small Wayland client that lives in the same process as the compositor.
+//!
+//! The client is connected over a socket pair, so both ends have to be
+//! dispatched by hand. `TestClient::roundtrip` takes care of that.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::{io::{AsRawFd, FromRawFd, IntoRawFd}, net::UnixStream};
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
+
+use nix::{fcntl::{self, FcntlArg}, unistd};
+use tempfile::tempfile;
+use wayland_client::{Display, EventQueue, GlobalManager, Proxy};
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_callback;
+use wayland_client::protocol::wl_compositor::{RequestsTrait as WlCompositorTrait, WlCompositor};
+use wayland_client::protocol::wl_data_device::{self, RequestsTrait as WlDataDeviceTrait,
+                                               WlDataDevice};
+use wayland_client::protocol::wl_data_device_manager::{RequestsTrait as WlDataDeviceManagerTrait,
+                                                       WlDataDeviceManager};
+use wayland_client::protocol::wl_data_offer::{RequestsTrait as WlDataOfferTrait, WlDataOffer};
+use wayland_client::protocol::wl_data_source::{self, RequestsTrait as WlDataSourceTrait,
+                                               WlDataSource};
+use wayland_client::protocol::wl_display::RequestsTrait as WlDisplayTrait;
+use wayland_client::protocol::wl_keyboard;
+use wayland_client::protocol::wl_seat::{RequestsTrait as WlSeatTrait, WlSeat};
+use wayland_client::protocol::wl_shm::{self, RequestsTrait as WlShmTrait, WlShm};
+use wayland_client::protocol::wl_shm_pool::RequestsTrait as WlShmPoolTrait;
+use wayland_client::protocol::wl_surface::{RequestsTrait as WlSurfaceTrait, WlSurface};
+use wayland_sys::server::*;
+use wlroots::Size;
+
+use super::xdg_shell::xdg_surface::{self, RequestsTrait as XdgSurfaceTrait, XdgSurface};
+use super::xdg_shell::xdg_toplevel::{self, RequestsTrait as XdgToplevelTrait, XdgToplevel};
+use super::xdg_shell::xdg_wm_base::{self, RequestsTrait as XdgWmBaseTrait, XdgWmBase};
+
+/// The version of the xdg_wm_base global to bind to.
+const XDG_WM_BASE_VERSION: u32 = 1;
+
+/// How many times both ends are dispatched before giving up on a roundtrip.
+const MAX_DISPATCHES: usize = 1000;
+
+/// The connection of the test client to the compositor.
+pub struct TestClient {
+    server: *mut wl_display,
+    /// The client on the side of the compositor.
+    client: *mut wl_client,
+    display: Display,
+    event_queue: EventQueue,
+    compositor: Proxy<WlCompositor>,
+    shm: Proxy<WlShm>,
+    wm_base: Proxy<XdgWmBase>,
+    data_device_manager: Proxy<WlDataDeviceManager>,
+    data_device: Proxy<WlDataDevice>,
+    /// The clipboard the compositor offers the client.
+    selection: Arc<Mutex<Option<Proxy<WlDataOffer>>>>,
+    keys: Arc<Mutex<KeyboardState>>
+}
+
+/// What the keyboard of the client was sent.
+#[derive(Default)]
+struct KeyboardState {
+    /// The surface with keyboard focus.
+    focus: Option<Proxy<WlSurface>>,
+    /// The serial of the last time the keyboard entered a surface, which
+    /// setting the clipboard needs.
+    serial: u32,
+    /// The keys that were pressed, with the surface that had focus.
+    pressed: Vec<(Proxy<WlSurface>, u32)>
+}
+
+/// The state the compositor asked a toplevel to be in.
+#[derive(Default)]
+struct ToplevelState {
+    size: Size,
+    pending_size: Option<Size>,
+    buffer: Option<Proxy<WlBuffer>>
+}
+
+/// A toplevel that draws a buffer of whatever size the compositor asks for.
+pub struct TestToplevel {
+    surface: Proxy<WlSurface>,
+    xdg_surface: Proxy<XdgSurface>,
+    toplevel: Proxy<XdgToplevel>,
+    state: Arc<Mutex<ToplevelState>>,
+    keys: Arc<Mutex<KeyboardState>>
+}
+
+impl TestClient {
+    /// Connect a new client to the compositor that owns the display.
+    pub fn connect(server: *mut wl_display) -> TestClient {
+        let (server_socket, client_socket) =
+            UnixStream::pair().expect("Could not create a socket pair");
+        let client = unsafe {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_client_create,
+                          server,
+                          server_socket.into_raw_fd())
+        };
+        assert!(!client.is_null(), "Could not create the client");
+        let (display, mut event_queue) = unsafe { Display::from_fd(client_socket.into_raw_fd()) }
+            .expect("Could not connect to the compositor");
+        let globals = GlobalManager::new(display.get_registry().unwrap());
+        roundtrip(server, &display, &mut event_queue);
+        let compositor = globals.instantiate_auto::<WlCompositor>()
+                                .expect("Missing wl_compositor global")
+                                .implement(|_, _| {});
+        let shm = globals.instantiate_auto::<WlShm>()
+                         .expect("Missing wl_shm global")
+                         .implement(|_, _| {});
+        let wm_base = globals.instantiate_exact::<XdgWmBase>(XDG_WM_BASE_VERSION)
+                             .expect("Missing xdg_wm_base global")
+                             .implement(|event, wm_base: Proxy<XdgWmBase>| match event {
+                                            xdg_wm_base::Event::Ping { serial } => {
+                                                wm_base.pong(serial)
+                                            }
+                                        });
+        let data_device_manager = globals.instantiate_auto::<WlDataDeviceManager>()
+                                         .expect("Missing wl_data_device_manager global")
+                                         .implement(|_, _| {});
+        let seat = globals.instantiate_auto::<WlSeat>()
+                          .expect("Missing wl_seat global")
+                          .implement(|_, _| {});
+        let keys = Arc::new(Mutex::new(KeyboardState::default()));
+        {
+            let keys = keys.clone();
+            seat.get_keyboard()
+                .expect("Could not get the keyboard")
+                .implement(move |event, _| {
+                               let mut keys = keys.lock().unwrap();
+                               match event {
+                                   wl_keyboard::Event::Keymap { fd, .. } => {
+                                       // The keymap isn't needed, only closed.
+                                       drop(unsafe { File::from_raw_fd(fd) });
+                                   }
+                                   wl_keyboard::Event::Enter { serial, surface, .. } => {
+                                       keys.focus = Some(surface);
+                                       keys.serial = serial;
+                                   }
+                                   wl_keyboard::Event::Leave { .. } => keys.focus = None,
+                                   wl_keyboard::Event::Key { key, state, .. } => {
+                                       let focus = keys.focus.clone();
+                                       if let (Some(focus), wl_keyboard::KeyState::Pressed) =
+                                           (focus, state) {
+                                           keys.pressed.push((focus, key));
+                                       }
+                                   }
+                                   _ => {}
+                               }
+                           });
+        }
+        let selection = Arc::new(Mutex::new(None));
+        let data_device = {
+            let selection = selection.clone();
+            data_device_manager.get_data_device(&seat)
+                               .expect("Could not get the data device")
+                               .implement(move |event, _| match event {
+                                              wl_data_device::Event::DataOffer { id } => {
+                                                  id.implement(|_, _| {});
+                                              }
+                                              wl_data_device::Event::Selection { id } => {
+                                                  *selection.lock().unwrap() = id
+                                              }
+                                              _ => {}
+                                          })
+        };
+        let mut client = TestClient { server,
+                                      client,
+                                      display,
+                                      event_queue,
+                                      compositor,
+                                      shm,
+                                      wm_base,
+                                      data_device_manager,
+                                      data_device,
+                                      selection,
+                                      keys };
+        client.roundtrip();
+        client
+    }
+
+    /// Wait until the compositor handled everything that was sent so far and
+    /// the client handled everything the compositor sent back.
+    pub fn roundtrip(&mut self) {
+        roundtrip(self.server, &self.display, &mut self.event_queue)
+    }
+
+    /// Disconnect from the compositor, which destroys everything the client
+    /// created, like it does when a program quits.
+    pub fn disconnect(self) {
+        unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_destroy, self.client) }
+    }
+
+    /// Put the data in the mime type on the clipboard.
+    pub fn copy(&mut self, mime_type: &str, data: &[u8]) {
+        let data = data.to_vec();
+        let source = self.data_device_manager
+                         .create_data_source()
+                         .expect("Could not create a data source")
+                         .implement(move |event, source: Proxy<WlDataSource>| match event {
+                                        wl_data_source::Event::Send { fd, .. } => {
+                                            let mut file = unsafe { File::from_raw_fd(fd) };
+                                            file.write_all(&data).ok();
+                                        }
+                                        wl_data_source::Event::Cancelled => source.destroy(),
+                                        _ => {}
+                                    });
+        source.offer(mime_type.into());
+        let serial = self.keys.lock().unwrap().serial;
+        self.data_device.set_selection(Some(&source), serial);
+        self.roundtrip();
+    }
+
+    /// Read the clipboard in the mime type, if the compositor offers one.
+    pub fn paste(&mut self, mime_type: &str) -> Option<Vec<u8>> {
+        let offer = self.selection.lock().unwrap().clone()?;
+        let (read_fd, write_fd) = unistd::pipe2(fcntl::O_CLOEXEC).expect("Could not create a pipe");
+        fcntl::fcntl(read_fd, FcntlArg::F_SETFL(fcntl::O_NONBLOCK)).ok();
+        offer.receive(mime_type.into(), write_fd);
+        unistd::close(write_fd).ok();
+        let mut file = unsafe { File::from_raw_fd(read_fd) };
+        let mut data = Vec::new();
+        let mut buffer = [0; 4096];
+        for _ in 0..MAX_DISPATCHES {
+            // The compositor writes whenever the pipe has room.
+            self.roundtrip();
+            loop {
+                match file.read(&mut buffer) {
+                    Ok(0) => return Some(data),
+                    Ok(count) => data.extend_from_slice(&buffer[..count]),
+                    Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => return None
+                }
+            }
+        }
+        panic!("The clipboard was never completely sent");
+    }
+
+    /// Create a toplevel with the app id and wait until the compositor has
+    /// mapped it.
+    pub fn create_toplevel(&mut self, app_id: &str, size: Size) -> TestToplevel {
+        let surface = self.compositor.create_surface()
+                                     .expect("Could not create a surface")
+                                     .implement(|_, _| {});
+        let state = Arc::new(Mutex::new(ToplevelState { size, ..ToplevelState::default() }));
+        let xdg_surface = {
+            let (surface, shm, state) = (surface.clone(), self.shm.clone(), state.clone());
+            self.wm_base
+                .get_xdg_surface(&surface)
+                .expect("Could not create an xdg surface")
+                .implement(move |event, xdg_surface: Proxy<XdgSurface>| match event {
+                               xdg_surface::Event::Configure { serial } => {
+                                   xdg_surface.ack_configure(serial);
+                                   let mut state = state.lock().unwrap();
+                                   let size = state.pending_size.take().unwrap_or(state.size);
+                                   if state.buffer.is_none() || size != state.size {
+                                       let buffer = create_buffer(&shm, size);
+                                       surface.attach(Some(&buffer), 0, 0);
+                                       surface.damage(0, 0, size.width, size.height);
+                                       state.size = size;
+                                       state.buffer = Some(buffer);
+                                   }
+                                   surface.commit();
+                               }
+                           })
+        };
+        let toplevel = {
+            let state = state.clone();
+            xdg_surface.get_toplevel()
+                       .expect("Could not create a toplevel")
+                       .implement(move |event, _| match event {
+                                      xdg_toplevel::Event::Configure { width, height, .. } => {
+                                          // Zero means that the client can pick the size.
+                                          if width > 0 && height > 0 {
+                                              state.lock().unwrap().pending_size =
+                                                  Some(Size::new(width, height));
+                                          }
+                                      }
+                                      xdg_toplevel::Event::Close => {}
+                                  })
+        };
+        toplevel.set_app_id(app_id.into());
+        surface.commit();
+        // The first roundtrip gets the configure, the second maps the buffer
+        // that was committed in response to it.
+        self.roundtrip();
+        self.roundtrip();
+        TestToplevel { surface,
+                       xdg_surface,
+                       toplevel,
+                       state,
+                       keys: self.keys.clone() }
+    }
+}
+
+impl TestToplevel {
+    /// The size of the buffer that is attached to the toplevel.
+    pub fn size(&self) -> Size {
+        self.state.lock().unwrap().size
+    }
+
+    /// The evdev keycodes of the keys that were pressed while the toplevel
+    /// had keyboard focus.
+    pub fn pressed_keys(&self) -> Vec<u32> {
+        self.keys.lock()
+                 .unwrap()
+                 .pressed
+                 .iter()
+                 .filter(|&&(ref surface, _)| surface.equals(&self.surface))
+                 .map(|&(_, key)| key)
+                 .collect()
+    }
+
+    /// Tell the compositor the smallest size the toplevel can be.
+    pub fn set_min_size(&self, width: i32, height: i32) {
+        self.toplevel.set_min_size(width, height);
+        self.surface.commit();
+    }
+
+    /// Ask the compositor to maximize the toplevel or to restore it.
+    pub fn set_maximized(&self, maximized: bool) {
+        if maximized {
+            self.toplevel.set_maximized();
+        } else {
+            self.toplevel.unset_maximized();
+        }
+    }
+
+    /// Ask the compositor to make the toplevel fullscreen on any output, or
+    /// to restore it.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if fullscreen {
+            self.toplevel.set_fullscreen(None);
+        } else {
+            self.toplevel.unset_fullscreen();
+        }
+    }
+
+    /// Ask the compositor to minimize the toplevel.
+    pub fn set_minimized(&self) {
+        self.toplevel.set_minimized();
+    }
+
+    /// Destroy the toplevel, which unmaps it.
+    pub fn destroy(self) {
+        self.toplevel.destroy();
+        self.xdg_surface.destroy();
+        self.surface.destroy();
+    }
+}
+
+/// Dispatch both ends of the connection until the compositor answered a sync
+/// request.
+fn roundtrip(server: *mut wl_display, display: &Display, event_queue: &mut EventQueue) {
+    let done = Arc::new(AtomicBool::new(false));
+    {
+        let done = done.clone();
+        display.sync()
+               .expect("Could not sync with the compositor")
+               .implement(move |event, _| match event {
+                              wl_callback::Event::Done { .. } => done.store(true, Ordering::SeqCst)
+                          });
+    }
+    for _ in 0..MAX_DISPATCHES {
+        display.flush().expect("Could not send the requests of the client");
+        unsafe {
+            let event_loop = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_get_event_loop, server);
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_event_loop_dispatch, event_loop, 0);
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_display_flush_clients, server);
+        }
+        if let Some(guard) = event_queue.prepare_read() {
+            // The socket doesn't block, so this fails when nothing arrived yet.
+            guard.read_events().ok();
+        }
+        event_queue.dispatch_pending().expect("Could not dispatch the client events");
+        if done.load(Ordering::SeqCst) {
+            return
+        }
+    }
+    panic!("The compositor did not answer the test client");
+}
+
+/// Create a buffer of the size, filled with an opaque colour.
+fn create_buffer(shm: &Proxy<WlShm>, size: Size) -> Proxy<WlBuffer> {
+    let Size { width, height } = size;
+    let stride = width * 4;
+    let mut file = tempfile().expect("Could not create the buffer file");
+    let pixels = vec![0xff; (stride * height) as usize];
+    file.write_all(&pixels).expect("Could not fill the buffer");
+    let pool = shm.create_pool(file.as_raw_fd(), stride * height)
+                  .expect("Could not create a shm pool")
+                  .implement(|_, _| {});
+    let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888)
+                     .expect("Could not create a buffer")
+                     .implement(|_, _| {});
+    // The buffer keeps the memory of the pool alive.
+    pool.destroy();
+    buffer
+}
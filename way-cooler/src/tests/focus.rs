@@ -0,0 +1,82 @@
+use wlroots::pointer_events::BTN_LEFT;
+
+use super::harness::run;
+
+/// The evdev keycode of the A key.
+const KEY_A: u32 = 30;
+
+#[test]
+fn new_views_are_focused_and_on_top() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+        assert_eq!(compositor.view_order(), vec!["first"]);
+        assert_eq!(compositor.focused(), Some("first".into()));
+
+        let _second = compositor.create_toplevel("second", 200, 200);
+        assert_eq!(compositor.view_order(), vec!["second", "first"]);
+        assert_eq!(compositor.focused(), Some("second".into()));
+    });
+}
+
+#[test]
+fn clicking_a_view_focuses_and_raises_it() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+        let _second = compositor.create_toplevel("second", 200, 200);
+        compositor.place_view("second", 300, 0);
+
+        compositor.move_pointer(100.0, 100.0);
+        compositor.click(BTN_LEFT);
+        assert_eq!(compositor.view_order(), vec!["first", "second"]);
+        assert_eq!(compositor.focused(), Some("first".into()));
+
+        compositor.move_pointer(400.0, 100.0);
+        compositor.click(BTN_LEFT);
+        assert_eq!(compositor.view_order(), vec!["second", "first"]);
+        assert_eq!(compositor.focused(), Some("second".into()));
+    });
+}
+
+#[test]
+fn clicking_the_background_clears_focus() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+
+        compositor.move_pointer(500.0, 400.0);
+        compositor.click(BTN_LEFT);
+        assert_eq!(compositor.view_order(), vec!["first"]);
+        assert_eq!(compositor.focused(), None);
+    });
+}
+
+#[test]
+fn closing_the_focused_view_focuses_the_next() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+        let second = compositor.create_toplevel("second", 200, 200);
+
+        second.destroy();
+        compositor.roundtrip();
+        assert_eq!(compositor.view_order(), vec!["first"]);
+        assert_eq!(compositor.focused(), Some("first".into()));
+    });
+}
+
+#[test]
+fn keys_go_to_the_focused_view() {
+    run(1, |compositor| {
+        let first = compositor.create_toplevel("first", 200, 200);
+        let second = compositor.create_toplevel("second", 200, 200);
+        compositor.place_view("second", 300, 0);
+
+        compositor.tap_key(KEY_A);
+        assert_eq!(second.pressed_keys(), vec![KEY_A]);
+        assert!(first.pressed_keys().is_empty());
+
+        compositor.move_pointer(100.0, 100.0);
+        compositor.click(BTN_LEFT);
+        compositor.tap_key(KEY_A);
+        assert_eq!(first.pressed_keys(), vec![KEY_A]);
+        assert_eq!(second.pressed_keys(), vec![KEY_A]);
+    });
+}
@@ -0,0 +1,306 @@
+//! Runs Way Cooler on the headless backend for the integration tests.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::thread;
+use std::time::Duration;
+
+use wayland_sys::server::wl_display;
+use wlroots::{self, utils::Edges,
+              wlroots_sys::{wlr_event_keyboard_key, wlr_headless_add_input_device,
+                            wlr_input_device_type::WLR_INPUT_DEVICE_KEYBOARD,
+                            wlr_headless_add_output, wlr_keyboard_notify_key,
+                            wlr_output_destroy},
+              Area, Backend, KeyboardModifier, Origin, Size, WLR_KEY_PRESSED, WLR_KEY_RELEASED};
+
+use super::client::{TestClient, TestToplevel};
+
+/// The size of every virtual output.
+pub const OUTPUT_WIDTH: i32 = 800;
+pub const OUTPUT_HEIGHT: i32 = 600;
+
+/// Whether a test compositor is running.
+///
+/// wlroots only allows one compositor at a time, so the tests take turns.
+static RUNNING: AtomicBool = ATOMIC_BOOL_INIT;
+
+struct Turn;
+
+impl Turn {
+    fn take() -> Turn {
+        while RUNNING.compare_and_swap(false, true, Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        Turn
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A running compositor, with a client connected to it.
+pub struct TestCompositor {
+    display: *mut wl_display,
+    client: TestClient,
+    time_msec: u32
+}
+
+/// Run the test against a fresh compositor on the headless backend with the
+/// number of outputs and a keyboard.
+pub fn run<F>(outputs: usize, test: F)
+    where F: FnOnce(&mut TestCompositor)
+{
+    run_with_config(::Config::default(), outputs, test)
+}
+
+/// Run the test like `run`, with the configuration.
+pub fn run_with_config<F>(config: ::Config, outputs: usize, test: F)
+    where F: FnOnce(&mut TestCompositor)
+{
+    let _turn = Turn::take();
+    let mut compositor = ::init_compositor(config,
+                                          |builder, server| builder.build_headless(server));
+    match compositor.backend {
+        Backend::Headless(ref mut backend) => {
+            for _ in 0..outputs {
+                backend.add_output(OUTPUT_WIDTH, OUTPUT_HEIGHT);
+            }
+            unsafe {
+                wlr_headless_add_input_device(backend.as_ptr(), WLR_INPUT_DEVICE_KEYBOARD);
+            }
+        }
+        _ => unreachable!("The test compositor must use the headless backend")
+    }
+    let mut result = Ok(());
+    compositor.run_with(|compositor| {
+        let display = compositor.display as *mut wl_display;
+        // The compositor has to stop even if the test fails, otherwise the
+        // next test can't start its own.
+        result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut test_compositor = TestCompositor { display,
+                                                       client: TestClient::connect(display),
+                                                       time_msec: 0 };
+            test(&mut test_compositor);
+        }));
+    });
+    if let Err(err) = result {
+        panic::resume_unwind(err)
+    }
+}
+
+impl TestCompositor {
+    /// Create a toplevel and wait until it's mapped.
+    pub fn create_toplevel(&mut self, app_id: &str, width: i32, height: i32) -> TestToplevel {
+        self.client.create_toplevel(app_id, Size::new(width, height))
+    }
+
+    /// Connect another client, e.g. to share the clipboard with.
+    pub fn connect_client(&mut self) -> TestClient {
+        TestClient::connect(self.display)
+    }
+
+    /// Read the clipboard in the mime type, as the client of the compositor.
+    pub fn paste(&mut self, mime_type: &str) -> Option<Vec<u8>> {
+        self.client.paste(mime_type)
+    }
+
+    /// Let the compositor and the client handle everything that is pending.
+    pub fn roundtrip(&mut self) {
+        self.client.roundtrip()
+    }
+
+    /// Run the function with the state of the compositor.
+    pub fn with_server<F, R>(&mut self, f: F) -> R
+        where F: FnOnce(&mut ::Server) -> R
+    {
+        let compositor = wlroots::compositor_handle().expect("Compositor is not running");
+        with_handles!([(compositor: {compositor})] => {
+            f(compositor.into())
+        }).expect("Compositor was destroyed")
+    }
+
+    /// The views, from the top of the stack to the bottom.
+    pub fn views(&mut self) -> Vec<Rc<::View>> {
+        self.with_server(|server| server.views.clone())
+    }
+
+    /// The app ids of the views, from the top of the stack to the bottom.
+    pub fn view_order(&mut self) -> Vec<String> {
+        self.views().iter().map(|view| view.app_id()).collect()
+    }
+
+    /// The view of the toplevel with the app id.
+    pub fn view(&mut self, app_id: &str) -> Rc<::View> {
+        self.views().into_iter()
+                    .find(|view| view.app_id() == app_id)
+                    .unwrap_or_else(|| panic!("No view for {}", app_id))
+    }
+
+    /// The app id of the view with keyboard focus.
+    pub fn focused(&mut self) -> Option<String> {
+        self.with_server(|server| server.seat.focused.as_ref().map(|view| view.app_id()))
+    }
+
+    /// The boxes of the outputs in the output layout.
+    pub fn output_boxes(&mut self) -> Vec<Area> {
+        self.with_server(|server| {
+            let ::Server { ref mut layout,
+                           ref mut outputs,
+                           .. } = *server;
+            outputs.iter_mut()
+                   .map(|output| {
+                            with_handles!([(layout: {&mut *layout}), (output: {output})] => {
+                                layout.get_box(&mut *output)
+                            }).unwrap()
+                        })
+                   .collect()
+        })
+    }
+
+    /// Plug in another output.
+    pub fn add_output(&mut self) {
+        let compositor = wlroots::compositor_handle().expect("Compositor is not running");
+        let backend = with_handles!([(compositor: {compositor})] => {
+            match compositor.backend {
+                Backend::Headless(ref mut backend) => backend.as_ptr(),
+                _ => unreachable!("The test compositor must use the headless backend")
+            }
+        }).expect("Compositor was destroyed");
+        // The compositor handles the new output right away, so it can't be
+        // in use here.
+        unsafe { wlr_headless_add_output(backend, OUTPUT_WIDTH as _, OUTPUT_HEIGHT as _) };
+        self.roundtrip();
+    }
+
+    /// Unplug the output at the index in `Server::outputs`.
+    pub fn remove_output(&mut self, index: usize) {
+        let output = self.with_server(|server| server.outputs[index].clone());
+        let output = output.run(|output| output.as_ptr()).unwrap();
+        unsafe { wlr_output_destroy(output) };
+        self.roundtrip();
+    }
+
+    /// Move the view of the toplevel with the app id to the position in
+    /// output layout coordinates.
+    pub fn place_view(&mut self, app_id: &str, x: i32, y: i32) {
+        let view = self.view(app_id);
+        view.origin.set(Origin::new(x, y));
+        self.with_server(|server| ::damage_changes(server));
+        self.roundtrip();
+    }
+
+    /// Start resizing the view of the toplevel with the app id from the
+    /// edges, as if the client had asked for it.
+    pub fn begin_resize(&mut self, app_id: &str, edges: Edges) {
+        let view = self.view(app_id);
+        self.with_server(|server| {
+            let ::Server { ref mut seat,
+                           ref mut cursor,
+                           ref mut views,
+                           .. } = *server;
+            seat.begin_resize(cursor, view, views, edges);
+        });
+        self.roundtrip();
+    }
+
+    /// Move the pointer to the position in output layout coordinates.
+    pub fn move_pointer(&mut self, lx: f64, ly: f64) {
+        let time_msec = self.tick();
+        self.with_server(|server| {
+            server.cursor.run(|cursor| cursor.warp(None, lx, ly)).unwrap();
+            ::pointer_moved(server, Some(time_msec));
+        });
+        self.roundtrip();
+    }
+
+    pub fn press_button(&mut self, button: u32) {
+        self.button(button, true)
+    }
+
+    pub fn release_button(&mut self, button: u32) {
+        self.button(button, false)
+    }
+
+    /// Press and release the button where the pointer is.
+    pub fn click(&mut self, button: u32) {
+        self.press_button(button);
+        self.release_button(button);
+    }
+
+    /// The position of the pointer in output layout coordinates.
+    pub fn pointer_position(&mut self) -> (f64, f64) {
+        self.with_server(|server| server.cursor.run(|cursor| cursor.coords()).unwrap())
+    }
+
+    /// Put a touch point down at the position in output layout coordinates.
+    pub fn touch_down(&mut self, touch_id: i32, lx: f64, ly: f64) {
+        let time_msec = self.tick();
+        self.with_server(|server| ::touch_down(server, time_msec, touch_id, lx, ly));
+        self.roundtrip();
+    }
+
+    /// Move a touch point to the position in output layout coordinates.
+    pub fn touch_motion(&mut self, touch_id: i32, lx: f64, ly: f64) {
+        let time_msec = self.tick();
+        self.with_server(|server| ::touch_motion(server, time_msec, touch_id, lx, ly));
+        self.roundtrip();
+    }
+
+    pub fn touch_up(&mut self, touch_id: i32) {
+        let time_msec = self.tick();
+        self.with_server(|server| ::touch_up(server, time_msec, touch_id));
+        self.roundtrip();
+    }
+
+    /// Where the events of the touch point go, if it's down.
+    pub fn touch_point(&mut self, touch_id: i32) -> Option<::TouchPoint> {
+        self.with_server(|server| server.seat.touch_points.get(&touch_id).cloned())
+    }
+
+    /// Hold down exactly the modifiers.
+    pub fn set_modifiers(&mut self, modifiers: KeyboardModifier) {
+        self.with_server(|server| ::modifiers_changed(server, modifiers));
+        self.roundtrip();
+    }
+
+    /// Press and release the key with the evdev keycode on the keyboard.
+    ///
+    /// Unlike the other input, the key goes through wlroots and the keyboard
+    /// handler, just like a key on a real keyboard.
+    pub fn tap_key(&mut self, keycode: u32) {
+        self.key(keycode, true);
+        self.key(keycode, false);
+    }
+
+    fn key(&mut self, keycode: u32, pressed: bool) {
+        let time_msec = self.tick();
+        let keyboard = self.with_server(|server| {
+            server.keyboards[0].run(|keyboard| keyboard.as_ptr()).unwrap()
+        });
+        let state = if pressed { WLR_KEY_PRESSED } else { WLR_KEY_RELEASED };
+        let mut event = wlr_event_keyboard_key { time_msec,
+                                                 keycode,
+                                                 update_state: true,
+                                                 state };
+        // The keyboard handler needs the compositor, so this can't be done
+        // from `with_server`.
+        unsafe { wlr_keyboard_notify_key(keyboard, &mut event) };
+        self.roundtrip();
+    }
+
+    fn button(&mut self, button: u32, pressed: bool) {
+        let time_msec = self.tick();
+        self.with_server(|server| ::pointer_button(server, time_msec, button, pressed));
+        self.roundtrip();
+    }
+
+    /// The time of the next input event.
+    fn tick(&mut self) -> u32 {
+        self.time_msec += 1;
+        self.time_msec
+    }
+}
@@ -0,0 +1,17 @@
+//! Integration tests that run Way Cooler on the headless backend.
+//!
+//! Every test gets its own compositor with virtual outputs and a Wayland
+//! client that lives in the same process. Keys are sent from a virtual
+//! keyboard, other input is injected by calling the same functions the input
+//! handlers use, so no GPU or real display is needed.
+
+mod client;
+mod harness;
+mod xdg_shell;
+
+mod focus;
+mod move_resize;
+mod outputs;
+mod selection;
+mod touch;
+mod view_state;
@@ -0,0 +1,100 @@
+use wlroots::{pointer_events::BTN_LEFT, utils::Edges, KeyboardModifier, Origin, Size};
+
+use super::harness::run;
+
+#[test]
+fn dragging_with_the_modifier_moves_the_view() {
+    run(1, |compositor| {
+        let _view = compositor.create_toplevel("view", 200, 200);
+
+        compositor.move_pointer(50.0, 50.0);
+        compositor.set_modifiers(KeyboardModifier::WLR_MODIFIER_LOGO);
+        compositor.press_button(BTN_LEFT);
+        compositor.move_pointer(150.0, 100.0);
+        compositor.release_button(BTN_LEFT);
+        compositor.set_modifiers(KeyboardModifier::empty());
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(100, 50));
+
+        // The view stays put once the button is released.
+        compositor.move_pointer(300.0, 300.0);
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(100, 50));
+    });
+}
+
+#[test]
+fn dragging_without_the_modifier_does_not_move_the_view() {
+    run(1, |compositor| {
+        let _view = compositor.create_toplevel("view", 200, 200);
+
+        compositor.move_pointer(50.0, 50.0);
+        compositor.press_button(BTN_LEFT);
+        compositor.move_pointer(150.0, 100.0);
+        compositor.release_button(BTN_LEFT);
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(0, 0));
+    });
+}
+
+#[test]
+fn resizing_from_the_bottom_right_keeps_the_origin() {
+    run(1, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", 100, 100);
+
+        compositor.move_pointer(290.0, 290.0);
+        compositor.begin_resize("view", Edges::WLR_EDGE_BOTTOM | Edges::WLR_EDGE_RIGHT);
+        compositor.move_pointer(390.0, 340.0);
+        // Wait for the buffer the client committed for the new size.
+        compositor.roundtrip();
+        assert_eq!(view.size(), Size::new(300, 250));
+        assert_eq!(compositor.view("view").get_size(), Size::new(300, 250));
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(100, 100));
+    });
+}
+
+#[test]
+fn resizing_from_the_top_left_moves_the_origin() {
+    run(1, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", 100, 100);
+
+        compositor.move_pointer(110.0, 110.0);
+        compositor.begin_resize("view", Edges::WLR_EDGE_TOP | Edges::WLR_EDGE_LEFT);
+        compositor.move_pointer(60.0, 90.0);
+        compositor.roundtrip();
+        assert_eq!(view.size(), Size::new(250, 220));
+        assert_eq!(compositor.view("view").get_size(), Size::new(250, 220));
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(50, 80));
+    });
+}
+
+#[test]
+fn resizing_past_the_opposite_edge_keeps_it_in_place() {
+    run(1, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", 100, 100);
+
+        compositor.move_pointer(110.0, 110.0);
+        compositor.begin_resize("view", Edges::WLR_EDGE_TOP | Edges::WLR_EDGE_LEFT);
+        compositor.move_pointer(400.0, 400.0);
+        compositor.roundtrip();
+        assert_eq!(view.size(), Size::new(::MIN_VIEW_SIZE, ::MIN_VIEW_SIZE));
+        assert_eq!(compositor.view("view").origin.get(),
+                   Origin::new(300 - ::MIN_VIEW_SIZE, 300 - ::MIN_VIEW_SIZE));
+    });
+}
+
+#[test]
+fn resizing_stops_at_the_minimum_size_of_the_client() {
+    run(1, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        view.set_min_size(150, 120);
+        compositor.place_view("view", 100, 100);
+
+        compositor.move_pointer(290.0, 290.0);
+        compositor.begin_resize("view", Edges::WLR_EDGE_BOTTOM | Edges::WLR_EDGE_RIGHT);
+        compositor.move_pointer(150.0, 150.0);
+        compositor.roundtrip();
+        assert_eq!(view.size(), Size::new(150, 120));
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(100, 100));
+    });
+}
@@ -0,0 +1,78 @@
+use wlroots::{pointer_events::BTN_LEFT, Area, KeyboardModifier, Origin, Size};
+
+use super::harness::{run, OUTPUT_HEIGHT, OUTPUT_WIDTH};
+
+#[test]
+fn outputs_are_laid_out_left_to_right() {
+    run(3, |compositor| {
+        let boxes = compositor.output_boxes();
+        let expected: Vec<Area> =
+            (0..3).map(|i| {
+                          Area::new(Origin::new(i * OUTPUT_WIDTH, 0),
+                                    Size::new(OUTPUT_WIDTH, OUTPUT_HEIGHT))
+                      })
+                  .collect();
+        assert_eq!(boxes, expected);
+    });
+}
+
+#[test]
+fn views_can_be_moved_to_another_output() {
+    run(2, |compositor| {
+        let _view = compositor.create_toplevel("view", 200, 200);
+
+        compositor.move_pointer(50.0, 50.0);
+        compositor.set_modifiers(KeyboardModifier::WLR_MODIFIER_LOGO);
+        compositor.press_button(BTN_LEFT);
+        compositor.move_pointer(50.0 + OUTPUT_WIDTH as f64, 50.0);
+        compositor.release_button(BTN_LEFT);
+        compositor.set_modifiers(KeyboardModifier::empty());
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(OUTPUT_WIDTH, 0));
+
+        // Nothing is left on the first output.
+        compositor.move_pointer(50.0, 50.0);
+        compositor.click(BTN_LEFT);
+        assert_eq!(compositor.focused(), None);
+    });
+}
+
+#[test]
+fn views_move_off_removed_outputs() {
+    run(2, |compositor| {
+        let _view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", OUTPUT_WIDTH + 100, 100);
+        compositor.move_pointer(OUTPUT_WIDTH as f64 + 50.0, 50.0);
+
+        compositor.remove_output(1);
+        assert_eq!(compositor.output_boxes(),
+                   vec![Area::new(Origin::new(0, 0), Size::new(OUTPUT_WIDTH, OUTPUT_HEIGHT))]);
+        assert_eq!(compositor.view("view").origin.get(), Origin::new(0, 0));
+        let (x, y) = compositor.pointer_position();
+        assert!(x < OUTPUT_WIDTH as f64 && y < OUTPUT_HEIGHT as f64);
+    });
+}
+
+#[test]
+fn applied_settings_outlive_plugging_in_outputs() {
+    run(1, |compositor| {
+        // Like a client of the output management protocol would.
+        compositor.with_server(|server| {
+            let output = server.outputs[0].clone();
+            let name = output.clone().run(|output| output.name()).unwrap();
+            let output_config = ::OutputConfig { enabled: Some(true),
+                                                 position: Some((0, OUTPUT_HEIGHT)),
+                                                 ..::OutputConfig::default() };
+            server.output_management.applied.insert(name, output_config.clone());
+            ::apply_output_configs(server, vec![(output, output_config)]);
+        });
+
+        compositor.add_output();
+        let boxes = compositor.output_boxes();
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(boxes[0].origin, Origin::new(0, OUTPUT_HEIGHT));
+
+        // Until the configuration is loaded again.
+        compositor.with_server(|server| ::configure_outputs(server));
+        assert_eq!(compositor.output_boxes()[0].origin, Origin::new(0, 0));
+    });
+}
@@ -0,0 +1,19 @@
+use super::harness::run_with_config;
+
+#[test]
+fn the_clipboard_is_kept_after_its_client_quits() {
+    let config = ::Config { persist_clipboard: true,
+                            ..::Config::default() };
+    run_with_config(config, 1, |compositor| {
+        let mut copier = compositor.connect_client();
+        let _copied_from = copier.create_toplevel("copier", 200, 200);
+        copier.copy("text/plain", b"kept");
+        // The compositor reads the clipboard while the client is around.
+        copier.roundtrip();
+        copier.disconnect();
+
+        let _pasted_into = compositor.create_toplevel("paster", 200, 200);
+        compositor.roundtrip();
+        assert_eq!(compositor.paste("text/plain"), Some(b"kept".to_vec()));
+    });
+}
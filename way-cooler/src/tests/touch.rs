@@ -0,0 +1,48 @@
+use wlroots::Origin;
+
+use super::harness::run;
+
+#[test]
+fn touching_a_view_focuses_it() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+        let _second = compositor.create_toplevel("second", 200, 200);
+        compositor.place_view("first", 300, 100);
+
+        compositor.touch_down(0, 350.0, 150.0);
+        assert_eq!(compositor.view_order(), vec!["first", "second"]);
+        assert_eq!(compositor.focused(), Some("first".into()));
+        assert_eq!(compositor.touch_point(0),
+                   Some(::TouchPoint::Surface { offset: Origin::new(300, 100) }));
+
+        // The point stays with the view it went down on.
+        compositor.touch_motion(0, 100.0, 100.0);
+        assert_eq!(compositor.focused(), Some("first".into()));
+
+        compositor.touch_up(0);
+        assert_eq!(compositor.touch_point(0), None);
+    });
+}
+
+#[test]
+fn touching_empty_space_moves_the_pointer() {
+    run(1, |compositor| {
+        let _first = compositor.create_toplevel("first", 200, 200);
+
+        compositor.touch_down(0, 500.0, 400.0);
+        assert_eq!(compositor.touch_point(0), Some(::TouchPoint::Pointer));
+        assert_eq!(compositor.pointer_position(), (500.0, 400.0));
+        assert_eq!(compositor.focused(), None);
+
+        // Only the first point drives the pointer.
+        compositor.touch_down(1, 600.0, 400.0);
+        assert_eq!(compositor.touch_point(1), None);
+
+        compositor.touch_motion(0, 550.0, 450.0);
+        assert_eq!(compositor.pointer_position(), (550.0, 450.0));
+
+        compositor.touch_up(0);
+        assert_eq!(compositor.touch_point(0), None);
+        assert_eq!(compositor.with_server(|server| server.seat.action), None);
+    });
+}
@@ -0,0 +1,67 @@
+use wlroots::{Area, Origin, Size};
+
+use super::harness::{run, OUTPUT_HEIGHT, OUTPUT_WIDTH};
+
+#[test]
+fn maximized_views_fill_the_output_until_restored() {
+    run(1, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", 100, 100);
+
+        view.set_maximized(true);
+        // Wait for the buffer the client committed for the new size.
+        compositor.roundtrip();
+        compositor.roundtrip();
+        assert!(compositor.view("view").maximized.get());
+        assert_eq!(compositor.view("view").geometry(),
+                   Area::new(Origin::new(0, 0), Size::new(OUTPUT_WIDTH, OUTPUT_HEIGHT)));
+
+        view.set_maximized(false);
+        compositor.roundtrip();
+        compositor.roundtrip();
+        assert!(!compositor.view("view").maximized.get());
+        assert_eq!(compositor.view("view").geometry(),
+                   Area::new(Origin::new(100, 100), Size::new(200, 200)));
+    });
+}
+
+#[test]
+fn fullscreen_views_cover_the_output() {
+    run(2, |compositor| {
+        let view = compositor.create_toplevel("view", 200, 200);
+        compositor.place_view("view", OUTPUT_WIDTH + 100, 100);
+
+        view.set_fullscreen(true);
+        compositor.roundtrip();
+        compositor.roundtrip();
+        assert_eq!(view.size(), Size::new(OUTPUT_WIDTH, OUTPUT_HEIGHT));
+        assert_eq!(compositor.view("view").geometry(),
+                   Area::new(Origin::new(OUTPUT_WIDTH, 0), Size::new(OUTPUT_WIDTH, OUTPUT_HEIGHT)));
+        let views = compositor.views();
+        assert_eq!(::views_above_layers(&views), 1);
+
+        view.set_fullscreen(false);
+        compositor.roundtrip();
+        compositor.roundtrip();
+        assert_eq!(compositor.view("view").geometry(),
+                   Area::new(Origin::new(OUTPUT_WIDTH + 100, 100), Size::new(200, 200)));
+    });
+}
+
+#[test]
+fn minimized_views_are_hidden_and_lose_focus() {
+    run(1, |compositor| {
+        let _below = compositor.create_toplevel("below", 200, 200);
+        let above = compositor.create_toplevel("above", 200, 200);
+        assert_eq!(compositor.focused(), Some("above".to_string()));
+
+        above.set_minimized();
+        compositor.roundtrip();
+        assert!(compositor.view("above").minimized.get());
+        assert_eq!(compositor.focused(), Some("below".to_string()));
+        assert_eq!(compositor.view_order(), vec!["below", "above"]);
+        let mut views = compositor.views();
+        let (view, _, _, _) = ::Seat::view_at(&mut views, 50.0, 50.0);
+        assert_eq!(view.map(|view| view.app_id()), Some("below".to_string()));
+    });
+}
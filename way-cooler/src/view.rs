@@ -2,6 +2,10 @@ use std::cell::Cell;
 use wlroots::{XdgShellState, XdgV6ShellState};
 use wlroots::{Area, Origin, Size, SurfaceHandle};
 
+/// The smallest width and height of a view, so that it can't be resized
+/// out of existence.
+pub const MIN_VIEW_SIZE: i32 = 32;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct PendingMoveResize {
     pub update_x: bool,
@@ -14,14 +18,31 @@ pub struct PendingMoveResize {
 pub struct View {
     pub shell: ::Shell,
     pub origin: Cell<Origin>,
-    pub pending_move_resize: Cell<Option<PendingMoveResize>>
+    pub pending_move_resize: Cell<Option<PendingMoveResize>>,
+    /// Who draws the decorations, set by the window manager.
+    pub decoration_policy: Cell<::DecorationPolicy>,
+    /// The border drawn around the view, set by the window manager.
+    pub border: Cell<::Border>,
+    pub maximized: Cell<bool>,
+    pub fullscreen: Cell<bool>,
+    /// Minimized views aren't drawn and don't get any input.
+    pub minimized: Cell<bool>,
+    /// Where the view goes back to once it is neither maximized nor
+    /// fullscreen any more.
+    pub saved_geometry: Cell<Option<Area>>
 }
 
 impl View {
     pub fn new(shell: ::Shell) -> View {
         View { shell: shell,
                origin: Cell::new(Origin::default()),
-               pending_move_resize: Cell::new(None) }
+               pending_move_resize: Cell::new(None),
+               decoration_policy: Cell::new(::DecorationPolicy::default()),
+               border: Cell::new(::Border::default()),
+               maximized: Cell::new(false),
+               fullscreen: Cell::new(false),
+               minimized: Cell::new(false),
+               saved_geometry: Cell::new(None) }
     }
 
     pub fn surface(&self) -> SurfaceHandle {
@@ -37,7 +58,8 @@ impl View {
                         Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) => {
                             toplevel.set_activated(activate);
                         },
-                        _ => unimplemented!()
+                        // Popups follow the activation of their toplevel.
+                        _ => {}
                     }
                 );
             },
@@ -48,13 +70,204 @@ impl View {
                         Some(&mut XdgShellState::TopLevel(ref mut toplevel)) => {
                             toplevel.set_activated(activate);
                         },
-                        _ => unimplemented!()
+                        // Popups follow the activation of their toplevel.
+                        _ => {}
                     }
                 );
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                dehandle!(
+                    @xwayland_surface = {xwayland_surface};
+                    xwayland_surface.set_activated(activate)
+                );
+            }
+        }
+    }
+
+    /// Whether the client last asked for the view to be maximized and to be
+    /// fullscreen.
+    pub fn requested_state(&self) -> (bool, bool) {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgV6ShellState::TopLevel(ref toplevel)) => {
+                            let state = toplevel.client_pending_state();
+                            (state.maximized(), state.fullscreen())
+                        },
+                        _ => (false, false)
+                    }
+                }).unwrap_or((false, false))
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgShellState::TopLevel(ref toplevel)) => {
+                            let state = toplevel.client_pending_state();
+                            (state.maximized(), state.fullscreen())
+                        },
+                        _ => (false, false)
+                    }
+                }).unwrap_or((false, false))
+            },
+            ::Shell::XWayland(_) => (false, false)
+        }
+    }
+
+    /// Tell the client whether the view is maximized.
+    ///
+    /// The new state is sent with the next configure event.
+    pub fn set_maximized(&self, maximized: bool) {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.set_maximized(maximized);
+                    }
+                }).ok();
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.set_maximized(maximized);
+                    }
+                }).ok();
+            },
+            // X11 windows only learn about their new size.
+            ::Shell::XWayland(_) => {}
+        }
+    }
+
+    /// Tell the client whether the view is fullscreen.
+    ///
+    /// The new state is sent with the next configure event.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.set_fullscreen(fullscreen);
+                    }
+                }).ok();
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.set_fullscreen(fullscreen);
+                    }
+                }).ok();
+            },
+            ::Shell::XWayland(_) => {}
+        }
+    }
+
+    /// Whether the view should get keyboard focus when clicked.
+    ///
+    /// Override redirect X11 windows (menus, tooltips, etc.) never do.
+    pub fn wants_focus(&self) -> bool {
+        match self.shell.clone() {
+            ::Shell::XWayland(xwayland_surface) => {
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    !xwayland_surface.override_redirect()
+                }).unwrap_or(false)
+            },
+            _ => true
+        }
+    }
+
+    /// The title of the view, or an empty string if it doesn't have one.
+    pub fn title(&self) -> String {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgV6ShellState::TopLevel(ref toplevel)) => toplevel.title(),
+                        _ => String::new()
+                    }
+                }).unwrap_or_default()
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgShellState::TopLevel(ref toplevel)) => toplevel.title(),
+                        _ => String::new()
+                    }
+                }).unwrap_or_default()
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    xwayland_surface.title().unwrap_or_default()
+                }).unwrap_or_default()
+            }
+        }
+    }
+
+    /// The application id of the view, or an empty string if it doesn't have
+    /// one.
+    pub fn app_id(&self) -> String {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgV6ShellState::TopLevel(ref toplevel)) => toplevel.app_id(),
+                        _ => String::new()
+                    }
+                }).unwrap_or_default()
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgShellState::TopLevel(ref toplevel)) => toplevel.app_id(),
+                        _ => String::new()
+                    }
+                }).unwrap_or_default()
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                // The WM_CLASS class is the closest thing X11 has to an app id.
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    xwayland_surface.class().unwrap_or_default()
+                }).unwrap_or_default()
             }
         }
     }
 
+    /// Ask the client to close the view.
+    pub fn close(&self) {
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.close();
+                    }
+                }).unwrap();
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    if let Some(&mut XdgShellState::TopLevel(ref mut toplevel)) =
+                        xdg_surface.state() {
+                        toplevel.close();
+                    }
+                }).unwrap();
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    xwayland_surface.close();
+                }).unwrap();
+            }
+        }
+    }
+
+    /// The area the view takes up in output layout coordinates.
+    pub fn geometry(&self) -> Area {
+        Area { origin: self.origin.get(),
+               size: self.get_size() }
+    }
+
     pub fn get_size(&self) -> Size {
         match self.shell.clone() {
             ::Shell::XdgV6(xdg_surface) => {
@@ -68,21 +281,73 @@ impl View {
                     let Area { origin: _, size } = xdg_surface.geometry();
                     size
                 }).unwrap()
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    Size::new(xwayland_surface.width() as i32, xwayland_surface.height() as i32)
+                }).unwrap()
             }
         }
     }
 
-    pub fn move_resize(&self, area: Area) {
-        let Area { origin: Origin { x, y },
-                   size: Size { width, height } } = area;
-        let width = width as u32;
-        let height = height as u32;
+    /// The smallest and the largest size the client accepts for the view.
+    ///
+    /// A largest size of zero means there is no limit.
+    pub fn size_constraints(&self) -> (Size, Size) {
+        let no_constraints = (Size::default(), Size::default());
+        let constraints = |min_width: u32, min_height: u32, max_width: u32, max_height: u32| {
+            (Size::new(min_width as i32, min_height as i32),
+             Size::new(max_width as i32, max_height as i32))
+        };
+        match self.shell.clone() {
+            ::Shell::XdgV6(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgV6ShellState::TopLevel(ref toplevel)) => {
+                            let state = toplevel.current_state();
+                            constraints(state.min_width(),
+                                        state.min_height(),
+                                        state.max_width(),
+                                        state.max_height())
+                        },
+                        _ => no_constraints
+                    }
+                }).unwrap_or(no_constraints)
+            },
+            ::Shell::Xdg(xdg_surface) => {
+                with_handles!([(xdg_surface: {xdg_surface})] => {
+                    match xdg_surface.state() {
+                        Some(&mut XdgShellState::TopLevel(ref toplevel)) => {
+                            let state = toplevel.current_state();
+                            constraints(state.min_width(),
+                                        state.min_height(),
+                                        state.max_width(),
+                                        state.max_height())
+                        },
+                        _ => no_constraints
+                    }
+                }).unwrap_or(no_constraints)
+            },
+            ::Shell::XWayland(_) => no_constraints
+        }
+    }
 
+    /// Move and resize the view.
+    ///
+    /// The size is clamped to the size constraints of the view. The edges
+    /// that move stay on the same side of the edges that don't, so e.g. when
+    /// resizing from the left edge the right edge stays put.
+    pub fn move_resize(&self, area: Area) {
         let Origin { x: view_x,
                      y: view_y } = self.origin.get();
 
-        let update_x = x != view_x;
-        let update_y = y != view_y;
+        let update_x = area.origin.x != view_x;
+        let update_y = area.origin.y != view_y;
+        let (min, max) = self.size_constraints();
+        let Size { width, height } = clamp_size(area.size, min, max);
+        let Origin { x, y } = anchored_origin(area, Size::new(width, height), update_x, update_y);
+        let width = width as u32;
+        let height = height as u32;
         let mut serial = 0;
 
         match self.shell.clone() {
@@ -90,10 +355,10 @@ impl View {
                 with_handles!([(xdg_surface: {xdg_surface})] => {
                     match xdg_surface.state() {
                         Some(&mut XdgV6ShellState::TopLevel(ref mut toplevel)) => {
-                            // TODO apply size constraints
                             serial = toplevel.set_size(width, height);
                         },
-                        _ => unimplemented!()
+                        // Popups are placed by their positioner.
+                        _ => {}
                     }
                 }).unwrap();
             },
@@ -101,12 +366,19 @@ impl View {
                 with_handles!([(xdg_surface: {xdg_surface})] => {
                     match xdg_surface.state() {
                         Some(&mut XdgShellState::TopLevel(ref mut toplevel)) => {
-                            // TODO apply size constraints
                             serial = toplevel.set_size(width, height);
                         },
-                        _ => unimplemented!()
+                        // Popups are placed by their positioner.
+                        _ => {}
                     }
                 }).unwrap();
+            },
+            ::Shell::XWayland(xwayland_surface) => {
+                // X11 configures are applied immediately, there is no serial
+                // to wait on.
+                with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                    xwayland_surface.configure(x as i16, y as i16, width as u16, height as u16);
+                }).unwrap();
             }
         }
 
@@ -121,6 +393,23 @@ impl View {
         }
     }
 
+    /// Move the view without resizing it.
+    ///
+    /// X11 windows are told where they are, so that their menus and tooltips
+    /// open next to them.
+    pub fn move_to(&self, origin: Origin) {
+        self.origin.set(origin);
+        if let ::Shell::XWayland(xwayland_surface) = self.shell.clone() {
+            with_handles!([(xwayland_surface: {xwayland_surface})] => {
+                let (width, height) = (xwayland_surface.width(), xwayland_surface.height());
+                xwayland_surface.configure(origin.x as i16,
+                                           origin.y as i16,
+                                           width as u16,
+                                           height as u16);
+            }).ok();
+        }
+    }
+
     pub fn for_each_surface(&self, f: &mut FnMut(SurfaceHandle, i32, i32)) {
         match self.shell.clone() {
             ::Shell::XdgV6(xdg_v6_surface) => {
@@ -132,7 +421,68 @@ impl View {
                 with_handles!([(xdg_surface: {xdg_surface})] => {
                     xdg_surface.for_each_surface(f);
                 }).unwrap();
+            },
+            ::Shell::XWayland(_) => {
+                with_handles!([(surface: {self.surface()})] => {
+                    surface.for_each_surface(f);
+                }).unwrap();
             }
         }
     }
 }
+
+/// Clamp the size to the smallest and the largest size, which has no limit
+/// where it is zero.
+///
+/// Views are never made smaller than `MIN_VIEW_SIZE`, whatever the client
+/// asks for.
+pub fn clamp_size(size: Size, min: Size, max: Size) -> Size {
+    let clamp = |value: i32, min: i32, max: i32| {
+        let min = min.max(MIN_VIEW_SIZE);
+        let value = if max > 0 { value.min(max) } else { value };
+        value.max(min)
+    };
+    Size::new(clamp(size.width, min.width, max.width),
+              clamp(size.height, min.height, max.height))
+}
+
+/// The origin of the area when it ends up with the size instead of its own,
+/// keeping the edges opposite of the ones that move where they are.
+pub fn anchored_origin(area: Area, size: Size, update_x: bool, update_y: bool) -> Origin {
+    let Area { origin: Origin { mut x, mut y },
+               size: requested } = area;
+    if update_x {
+        x += requested.width - size.width;
+    }
+    if update_y {
+        y += requested.height - size.height;
+    }
+    Origin { x, y }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes_are_clamped_to_the_constraints() {
+        let (min, max) = (Size::new(100, 50), Size::new(300, 0));
+        assert_eq!(clamp_size(Size::new(200, 200), min, max), Size::new(200, 200));
+        assert_eq!(clamp_size(Size::new(50, 20), min, max), Size::new(100, 50));
+        assert_eq!(clamp_size(Size::new(500, 5000), min, max), Size::new(300, 5000));
+        // Without constraints the size still can't go to zero or below.
+        let none = Size::default();
+        assert_eq!(clamp_size(Size::new(-10, 0), none, none),
+                   Size::new(MIN_VIEW_SIZE, MIN_VIEW_SIZE));
+    }
+
+    #[test]
+    fn clamping_keeps_the_opposite_edge() {
+        // Dragging the top left corner past the bottom right corner.
+        let area = Area::new(Origin::new(300, 300), Size::new(-100, -100));
+        let size = Size::new(MIN_VIEW_SIZE, MIN_VIEW_SIZE);
+        assert_eq!(anchored_origin(area, size, true, true),
+                   Origin::new(200 - MIN_VIEW_SIZE, 200 - MIN_VIEW_SIZE));
+        assert_eq!(anchored_origin(area, size, false, false), Origin::new(300, 300));
+    }
+}
@@ -0,0 +1,4 @@
+//! Module for the C interfaces generated from the Wayland extension
+//! protocols that Way Cooler implements itself.
+
+pub mod window_management;
@@ -0,0 +1,20 @@
+/// Generated interfaces from the XML protocol spec.
+pub use self::generated::c_interfaces::*;
+
+mod generated {
+    // Generated code generally doesn't follow standards
+    #![allow(dead_code,non_camel_case_types,unused_unsafe,unused_variables)]
+    #![allow(non_upper_case_globals,non_snake_case,unused_imports, unused_qualifications)]
+
+    pub mod c_interfaces {
+        use wayland_sys::common::*;
+        extern "C" {
+            // The core interfaces that the protocol refers to are exported
+            // by libwayland-server.
+            static wl_buffer_interface: wl_interface;
+        }
+        #[doc(hidden)]
+        include!(concat!(env!("OUT_DIR"),
+                         "/way-cooler-window-management-unstable-v1_interface.rs"));
+    }
+}
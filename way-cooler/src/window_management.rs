@@ -0,0 +1,617 @@
+//! Implementation of the private window management protocol.
+//!
+//! The awesome process binds to the `zway_cooler_window_manager_v1` global
+//! so that it can learn about the views that Way Cooler maps and ask for them
+//! to be moved, resized, focused, closed or captured, and to decorate them.
+//!
+//! Way Cooler starts awesome itself, and the global is hidden from every
+//! other client.
+
+use std::{mem, ffi::CString, process::Command, ptr, rc::{Rc, Weak}};
+use std::os::raw::{c_int, c_void};
+
+use nix::libc;
+use wayland_sys::server::*;
+use wlroots::{self, Area, Compositor, KeyboardModifier, Origin, Size};
+use wlroots::wlroots_sys::wlr_axis_orientation;
+
+use wayland_protocols::window_management::{zway_cooler_window_manager_v1_interface,
+                                           zway_cooler_window_v1_interface};
+
+/// The version of the window management protocol that is implemented.
+pub const WINDOW_MANAGEMENT_VERSION: u32 = 4;
+
+/// The version of the manager that added the scroll event.
+const MANAGER_SCROLL_SINCE: c_int = 2;
+
+/// How far a scroll without discrete steps, e.g. on a touchpad, has to go
+/// before it counts as one step. This is what libinput reports for one
+/// click of a mouse wheel.
+const SCROLL_STEP: f64 = 15.0;
+
+const MANAGER_ERROR_ALREADY_BOUND: u32 = 0;
+
+const WINDOW_ERROR_INVALID_DECORATION_POLICY: u32 = 0;
+
+const DECORATION_POLICY_CLIENT: u32 = 0;
+const DECORATION_POLICY_SERVER: u32 = 1;
+
+const MANAGER_EVENT_WINDOW: u32 = 0;
+const MANAGER_EVENT_SCROLL: u32 = 1;
+
+const WINDOW_EVENT_TITLE: u32 = 0;
+const WINDOW_EVENT_APP_ID: u32 = 1;
+const WINDOW_EVENT_GEOMETRY: u32 = 2;
+const WINDOW_EVENT_ACTIVATED: u32 = 3;
+const WINDOW_EVENT_DONE: u32 = 4;
+const WINDOW_EVENT_CLOSED: u32 = 5;
+const WINDOW_EVENT_CAPTURED: u32 = 6;
+const WINDOW_EVENT_CAPTURE_FAILED: u32 = 7;
+
+#[repr(C)]
+struct ManagerImplementation {
+    destroy: unsafe extern "C" fn(*mut wl_client, *mut wl_resource)
+}
+
+#[repr(C)]
+struct WindowImplementation {
+    destroy: unsafe extern "C" fn(*mut wl_client, *mut wl_resource),
+    set_geometry: unsafe extern "C" fn(*mut wl_client, *mut wl_resource, i32, i32, i32, i32),
+    focus: unsafe extern "C" fn(*mut wl_client, *mut wl_resource),
+    close: unsafe extern "C" fn(*mut wl_client, *mut wl_resource),
+    capture: unsafe extern "C" fn(*mut wl_client, *mut wl_resource, *mut wl_resource),
+    set_decoration_policy: unsafe extern "C" fn(*mut wl_client, *mut wl_resource, u32),
+    set_border: unsafe extern "C" fn(*mut wl_client, *mut wl_resource, u32, u32)
+}
+
+static MANAGER_IMPLEMENTATION: ManagerImplementation =
+    ManagerImplementation { destroy: destroy_resource };
+
+static WINDOW_IMPLEMENTATION: WindowImplementation =
+    WindowImplementation { destroy: destroy_resource,
+                           set_geometry: window_set_geometry,
+                           focus: window_focus,
+                           close: window_close,
+                           capture: window_capture,
+                           set_decoration_policy: window_set_decoration_policy,
+                           set_border: window_set_border };
+
+/// The state of a window as it was last sent to the window manager.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct WindowState {
+    title: String,
+    app_id: String,
+    geometry: Area,
+    activated: bool
+}
+
+/// A view that has been announced to the window manager.
+#[derive(Debug)]
+struct Window {
+    resource: *mut wl_resource,
+    view: Weak<::View>,
+    state: Option<WindowState>
+}
+
+impl Window {
+    fn is_view(&self, view: &Rc<::View>) -> bool {
+        self.view.upgrade().map(|ref v| Rc::ptr_eq(v, view)).unwrap_or(false)
+    }
+}
+
+/// Keeps track of the window manager client and the views it was told about.
+#[derive(Debug, Default)]
+pub struct WindowManagement {
+    global: Option<*mut wl_global>,
+    /// The window manager that Way Cooler started, while it's connected.
+    client: Option<*mut wl_client>,
+    manager: Option<*mut wl_resource>,
+    windows: Vec<Window>,
+    /// The vertical and horizontal scroll that didn't add up to a step yet.
+    scroll_remainder: [f64; 2]
+}
+
+impl WindowManagement {
+    /// Advertise the window management global on the display.
+    pub fn new(display: *mut wl_display) -> Self {
+        let global = unsafe {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_global_create,
+                          display,
+                          &zway_cooler_window_manager_v1_interface,
+                          WINDOW_MANAGEMENT_VERSION as c_int,
+                          ptr::null_mut(),
+                          bind_manager)
+        };
+        if global.is_null() {
+            panic!("Could not create the window management global");
+        }
+        WindowManagement { global: Some(global),
+                           ..WindowManagement::default() }
+    }
+
+    /// The window management global, which `filter_privileged_globals` hides
+    /// from every client but the window manager.
+    pub fn global(&self) -> *mut wl_global {
+        self.global.unwrap_or(ptr::null_mut())
+    }
+
+    /// Whether the client is the window manager that Way Cooler started.
+    pub fn is_window_manager(&self, client: *const wl_client) -> bool {
+        self.client.map(|window_manager| window_manager as *const _ == client)
+            .unwrap_or(false)
+    }
+
+    /// Announce a newly mapped view to the window manager.
+    pub fn new_window(&mut self, view: &Rc<::View>, focused: Option<&Rc<::View>>) {
+        if let Some(manager) = self.manager {
+            if let Some(window) = create_window(manager, view) {
+                self.windows.push(window);
+            }
+        }
+        let is_focused = focused.map(|focused| Rc::ptr_eq(focused, view)).unwrap_or(false);
+        self.refresh_window(view, is_focused);
+    }
+
+    /// Tell the window manager that the view is no longer mapped.
+    pub fn close_window(&mut self, view: &Rc<::View>) {
+        self.windows.retain(|window| {
+            if window.is_view(view) {
+                unsafe {
+                    ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                  wl_resource_post_event,
+                                  window.resource,
+                                  WINDOW_EVENT_CLOSED);
+                }
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Send any state that changed since the last refresh.
+    ///
+    /// This is called once per frame so that the window manager sees a
+    /// consistent view of the world.
+    pub fn refresh(&mut self, views: &[Rc<::View>], focused: Option<&Rc<::View>>) {
+        if self.manager.is_none() {
+            return
+        }
+        for view in views {
+            let is_focused = focused.map(|focused| Rc::ptr_eq(focused, view)).unwrap_or(false);
+            self.refresh_window(view, is_focused);
+        }
+    }
+
+    /// Tell the window manager that the pointer scrolled over a part of the
+    /// screen that has no surface.
+    pub fn scroll(&mut self,
+                  orientation: wlr_axis_orientation,
+                  delta: f64,
+                  delta_discrete: i32,
+                  modifiers: KeyboardModifier) {
+        let manager = match self.manager {
+            Some(manager) => manager,
+            None => return
+        };
+        let axis = orientation as usize;
+        let steps = scroll_steps(&mut self.scroll_remainder[axis], delta, delta_discrete);
+        unsafe {
+            let version = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_version, manager);
+            if steps == 0 || version < MANAGER_SCROLL_SINCE {
+                return
+            }
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_resource_post_event,
+                          manager,
+                          MANAGER_EVENT_SCROLL,
+                          axis as u32,
+                          steps,
+                          modifiers.bits());
+        }
+    }
+
+    fn refresh_window(&mut self, view: &Rc<::View>, focused: bool) {
+        let window = match self.windows.iter_mut().find(|window| window.is_view(view)) {
+            Some(window) => window,
+            None => return
+        };
+        let new_state = WindowState { title: view.title(),
+                                      app_id: view.app_id(),
+                                      geometry: view.geometry(),
+                                      activated: focused };
+        let old_state = window.state.take().unwrap_or_else(|| {
+            // Make sure everything is sent the first time.
+            WindowState { title: String::new(),
+                          app_id: String::new(),
+                          geometry: Area::default(),
+                          activated: !focused }
+        });
+        if old_state != new_state {
+            unsafe { send_window_state(window.resource, &old_state, &new_state) }
+        }
+        window.state = Some(new_state);
+    }
+
+    fn manager_destroyed(&mut self, resource: *mut wl_resource) {
+        if self.manager == Some(resource) {
+            self.manager = None;
+        }
+    }
+
+    fn window_destroyed(&mut self, resource: *mut wl_resource) {
+        self.windows.retain(|window| window.resource != resource);
+    }
+
+    fn view_for_resource(&self, resource: *mut wl_resource) -> Option<Rc<::View>> {
+        self.windows.iter()
+            .find(|window| window.resource == resource)
+            .and_then(|window| window.view.upgrade())
+    }
+}
+
+/// Start the window manager as a client of the compositor.
+///
+/// It gets a connection of its own, so that it's the only client that is
+/// shown the window management global. Its X11 windows go to XWayland, if it
+/// runs.
+pub fn spawn_window_manager(compositor: &mut Compositor, program: &str) {
+    let display = compositor.display as *mut wl_display;
+    let x_display = compositor.xwayland
+                              .as_mut()
+                              .map(|xwayland| unsafe { (*xwayland.as_ptr()).display })
+                              .map(|number| format!(":{}", number));
+    let mut fds = [0; 2];
+    unsafe {
+        if libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) != 0 {
+            error!("Could not create a socket for the window manager");
+            return
+        }
+        // Only the end of the window manager is inherited by it.
+        libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+    let mut command = Command::new(program);
+    command.env("WAYLAND_SOCKET", fds[1].to_string());
+    if let Some(x_display) = x_display {
+        command.env("DISPLAY", x_display);
+    }
+    let spawned = command.spawn();
+    unsafe {
+        libc::close(fds[1]);
+    }
+    if let Err(err) = spawned {
+        error!("Could not start the window manager {}: {}", program, err);
+        unsafe {
+            libc::close(fds[0]);
+        }
+        return
+    }
+    let client = unsafe { ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_create, display, fds[0]) };
+    if client.is_null() {
+        error!("Could not connect to the window manager {}", program);
+        unsafe {
+            libc::close(fds[0]);
+        }
+        return
+    }
+    unsafe {
+        let listener = Box::into_raw(Box::new(wl_listener { link: mem::zeroed(),
+                                                            notify: window_manager_destroyed }));
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_add_destroy_listener, client, listener);
+    }
+    let server: &mut ::Server = compositor.into();
+    server.window_management.client = Some(client);
+}
+
+/// The number of steps that were scrolled.
+///
+/// Scrolls without discrete steps are added up in the remainder until they
+/// reach a step.
+fn scroll_steps(remainder: &mut f64, delta: f64, delta_discrete: i32) -> i32 {
+    if delta_discrete != 0 {
+        *remainder = 0.0;
+        return delta_discrete
+    }
+    if remainder.signum() != delta.signum() {
+        // Changing direction starts over.
+        *remainder = 0.0;
+    }
+    *remainder += delta;
+    let steps = (*remainder / SCROLL_STEP).trunc();
+    *remainder -= steps * SCROLL_STEP;
+    steps as i32
+}
+
+/// Creates the protocol object for the view and sends it to the manager.
+fn create_window(manager: *mut wl_resource, view: &Rc<::View>) -> Option<Window> {
+    unsafe {
+        let client = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_client, manager);
+        let version = ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_get_version, manager);
+        let resource = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                     wl_resource_create,
+                                     client,
+                                     &zway_cooler_window_v1_interface,
+                                     version,
+                                     0);
+        if resource.is_null() {
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_post_no_memory, client);
+            return None
+        }
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_set_implementation,
+                      resource,
+                      &WINDOW_IMPLEMENTATION as *const _ as *const c_void,
+                      ptr::null_mut(),
+                      Some(window_resource_destroyed));
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_post_event,
+                      manager,
+                      MANAGER_EVENT_WINDOW,
+                      resource);
+        Some(Window { resource,
+                      view: Rc::downgrade(view),
+                      state: None })
+    }
+}
+
+/// Sends the difference between the old and the new state, followed by a done.
+unsafe fn send_window_state(resource: *mut wl_resource, old: &WindowState, new: &WindowState) {
+    if old.title != new.title {
+        let title = CString::new(new.title.clone()).unwrap_or_default();
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_post_event,
+                      resource,
+                      WINDOW_EVENT_TITLE,
+                      title.as_ptr());
+    }
+    if old.app_id != new.app_id {
+        let app_id = CString::new(new.app_id.clone()).unwrap_or_default();
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_post_event,
+                      resource,
+                      WINDOW_EVENT_APP_ID,
+                      app_id.as_ptr());
+    }
+    if old.geometry != new.geometry {
+        let Area { origin: Origin { x, y },
+                   size: Size { width, height } } = new.geometry;
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_post_event,
+                      resource,
+                      WINDOW_EVENT_GEOMETRY,
+                      x,
+                      y,
+                      width,
+                      height);
+    }
+    if old.activated != new.activated {
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                      wl_resource_post_event,
+                      resource,
+                      WINDOW_EVENT_ACTIVATED,
+                      new.activated as u32);
+    }
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                  wl_resource_post_event,
+                  resource,
+                  WINDOW_EVENT_DONE);
+}
+
+unsafe extern "C" fn bind_manager(client: *mut wl_client,
+                                  _: *mut c_void,
+                                  version: u32,
+                                  id: u32) {
+    let resource = ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                                 wl_resource_create,
+                                 client,
+                                 &zway_cooler_window_manager_v1_interface,
+                                 version as c_int,
+                                 id);
+    if resource.is_null() {
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_client_post_no_memory, client);
+        return
+    }
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                  wl_resource_set_implementation,
+                  resource,
+                  &MANAGER_IMPLEMENTATION as *const _ as *const c_void,
+                  ptr::null_mut(),
+                  Some(manager_resource_destroyed));
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        let ::Server { ref mut window_management,
+                       ref views,
+                       ref seat,
+                       .. } = *server;
+        if window_management.manager.is_some() {
+            warn!("A client tried to manage windows while another one already does");
+            let message = CString::new("window management is already bound").unwrap();
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_resource_post_error,
+                          resource,
+                          MANAGER_ERROR_ALREADY_BOUND,
+                          message.as_ptr());
+            return
+        }
+        window_management.manager = Some(resource);
+        // Announce the views from the bottom of the stack to the top.
+        for view in views.iter().rev() {
+            if let Some(window) = create_window(resource, view) {
+                window_management.windows.push(window);
+            }
+        }
+        window_management.refresh(views, seat.focused.as_ref());
+    }).unwrap();
+}
+
+unsafe extern "C" fn destroy_resource(_: *mut wl_client, resource: *mut wl_resource) {
+    ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_destroy, resource);
+}
+
+unsafe extern "C" fn manager_resource_destroyed(resource: *mut wl_resource) {
+    if let Some(compositor) = wlroots::compositor_handle() {
+        // This can fail if the compositor is shutting down, in which case
+        // there is nothing to clean up anyways.
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.window_management.manager_destroyed(resource);
+        }).ok();
+    }
+}
+
+unsafe extern "C" fn window_manager_destroyed(listener: *mut wl_listener, _: *mut c_void) {
+    ::ffi::remove_listener(listener);
+    warn!("The window manager quit");
+    if let Some(compositor) = wlroots::compositor_handle() {
+        // This can fail if the compositor is shutting down, in which case
+        // there is nothing to forget anyways.
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.window_management.client = None;
+        }).ok();
+    }
+}
+
+unsafe extern "C" fn window_resource_destroyed(resource: *mut wl_resource) {
+    if let Some(compositor) = wlroots::compositor_handle() {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            server.window_management.window_destroyed(resource);
+        }).ok();
+    }
+}
+
+unsafe extern "C" fn window_set_geometry(_: *mut wl_client,
+                                         resource: *mut wl_resource,
+                                         x: i32,
+                                         y: i32,
+                                         width: i32,
+                                         height: i32) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        if let Some(view) = server.window_management.view_for_resource(resource) {
+            view.move_resize(Area { origin: Origin { x, y },
+                                    size: Size { width, height } });
+        }
+        ::damage_changes(server);
+    }).unwrap();
+}
+
+unsafe extern "C" fn window_focus(_: *mut wl_client, resource: *mut wl_resource) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        {
+            let ::Server { ref mut seat,
+                           ref mut views,
+                           ref window_management,
+                           .. } = *server;
+            if let Some(view) = window_management.view_for_resource(resource) {
+                // Focusing a minimized window is how it is restored.
+                view.minimized.set(false);
+                seat.focus_view(view, views);
+            }
+        }
+        ::damage_changes(server);
+    }).unwrap();
+}
+
+unsafe extern "C" fn window_close(_: *mut wl_client, resource: *mut wl_resource) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        if let Some(view) = server.window_management.view_for_resource(resource) {
+            view.close();
+        }
+    }).unwrap();
+}
+
+unsafe extern "C" fn window_capture(_: *mut wl_client,
+                                    resource: *mut wl_resource,
+                                    buffer: *mut wl_resource) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let backend = compositor.backend.as_ptr();
+        let server: &mut ::Server = compositor.into();
+        // The window can't be captured any more once it was closed.
+        let captured = match server.window_management.view_for_resource(resource) {
+            Some(view) => ::capture_view(backend, &view, buffer),
+            None => false
+        };
+        let event = if captured {
+            WINDOW_EVENT_CAPTURED
+        } else {
+            WINDOW_EVENT_CAPTURE_FAILED
+        };
+        ffi_dispatch!(WAYLAND_SERVER_HANDLE, wl_resource_post_event, resource, event, buffer);
+    }).unwrap();
+}
+
+unsafe extern "C" fn window_set_decoration_policy(_: *mut wl_client,
+                                                  resource: *mut wl_resource,
+                                                  policy: u32) {
+    let policy = match policy {
+        DECORATION_POLICY_CLIENT => ::DecorationPolicy::Client,
+        DECORATION_POLICY_SERVER => ::DecorationPolicy::Server,
+        _ => {
+            let message = CString::new(format!("{} is not a decoration policy", policy)).unwrap();
+            ffi_dispatch!(WAYLAND_SERVER_HANDLE,
+                          wl_resource_post_error,
+                          resource,
+                          WINDOW_ERROR_INVALID_DECORATION_POLICY,
+                          message.as_ptr());
+            return
+        }
+    };
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        if let Some(view) = server.window_management.view_for_resource(resource) {
+            view.decoration_policy.set(policy);
+            ::update_decoration(server, &view);
+        }
+    }).unwrap();
+}
+
+unsafe extern "C" fn window_set_border(_: *mut wl_client,
+                                       resource: *mut wl_resource,
+                                       width: u32,
+                                       color: u32) {
+    let compositor = wlroots::compositor_handle().expect("Compositor was not running");
+    with_handles!([(compositor: {compositor})] => {
+        let server: &mut ::Server = compositor.into();
+        if let Some(view) = server.window_management.view_for_resource(resource) {
+            let geometry = view.geometry();
+            let old_border = view.border.replace(::Border { width: width as i32, color });
+            // The colour doesn't change the scene, so the border is damaged
+            // here.
+            let damaged = old_border.area(geometry)
+                                    .into_iter()
+                                    .chain(view.border.get().area(geometry));
+            for area in damaged {
+                ::damage_area(server, area);
+            }
+        }
+        ::damage_changes(server);
+    }).unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn continuous_scrolls_add_up_to_steps() {
+        let mut remainder = 0.0;
+        assert_eq!(scroll_steps(&mut remainder, 10.0, 0), 0);
+        assert_eq!(scroll_steps(&mut remainder, 10.0, 0), 1);
+        assert_eq!(scroll_steps(&mut remainder, 25.0, 0), 2);
+        assert_eq!(scroll_steps(&mut remainder, -10.0, 0), 0);
+        assert_eq!(scroll_steps(&mut remainder, -10.0, 0), -1);
+        assert_eq!(scroll_steps(&mut remainder, 1.0, -1), -1);
+        assert_eq!(remainder, 0.0);
+    }
+}
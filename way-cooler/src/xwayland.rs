@@ -1,8 +1,11 @@
 //! This handles the XWayland server and any XWayland clients that connect to
 //! Way Cooler.
 
-use wlroots::{CompositorHandle, SurfaceHandler, XWaylandManagerHandler, XWaylandSurfaceHandle,
-              XWaylandSurfaceHandler};
+use std::rc::Rc;
+
+use wlroots::{CompositorHandle, Origin, SurfaceHandle, SurfaceHandler, XWaylandManagerHandler,
+              XWaylandSurfaceHandle, XWaylandSurfaceHandler};
+use wlroots::xwayland_events::{ConfigureEvent, MoveEvent, ResizeEvent};
 
 pub struct XWaylandManager;
 
@@ -15,11 +18,181 @@ impl XWaylandManager {
 impl XWaylandManagerHandler for XWaylandManager {
     fn on_ready(&mut self, _: CompositorHandle) {}
 
-    // TODO
     fn new_surface(&mut self,
                    _: CompositorHandle,
                    _: XWaylandSurfaceHandle)
                    -> (Option<Box<XWaylandSurfaceHandler>>, Option<Box<SurfaceHandler>>) {
-        (None, None)
+        (Some(Box::new(XWayland::new())), Some(Box::new(::SurfaceDamage)))
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct XWayland;
+
+impl XWayland {
+    pub fn new() -> Self {
+        XWayland
+    }
+}
+
+impl XWaylandSurfaceHandler for XWayland {
+    fn on_request_configure(&mut self,
+                            compositor: CompositorHandle,
+                            _: Option<SurfaceHandle>,
+                            shell_surface: XWaylandSurfaceHandle,
+                            event: &ConfigureEvent) {
+        // X11 clients place themselves, so we honor whatever they ask for.
+        with_handles!([(shell_surface: {shell_surface.clone()})] => {
+            shell_surface.configure(event.x(), event.y(), event.width(), event.height());
+        }).unwrap();
+
+        let surface = shell_surface.into();
+        with_handles!([(compositor: {&compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            if let Some(view) = server.views.iter().find(|view| view.shell == surface) {
+                view.origin.set(Origin::new(event.x() as i32, event.y() as i32));
+            }
+        }).unwrap();
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
+    }
+
+    fn on_move_request(&mut self,
+                       compositor: CompositorHandle,
+                       _: Option<SurfaceHandle>,
+                       shell_surface: XWaylandSurfaceHandle,
+                       _: &MoveEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let ref mut seat = server.seat;
+            let ref mut cursor = server.cursor;
+
+            if let Some(ref mut view) = seat.focused {
+                let shell: ::Shell = shell_surface.into();
+                let action = &mut seat.action;
+                if view.shell == shell {
+                    with_handles!([(cursor: {cursor})] => {
+                        let (lx, ly) = cursor.coords();
+                        let Origin { x: shell_x, y: shell_y } = view.origin.get();
+                        let (view_sx, view_sy) = (lx - shell_x as f64, ly - shell_y as f64);
+                        let start = Origin::new(view_sx as _, view_sy as _);
+                        *action = Some(::Action::Moving { start: start });
+                    }).unwrap();
+                }
+            }
+        }).unwrap();
+    }
+
+    fn on_resize_request(&mut self,
+                         compositor: CompositorHandle,
+                         _: Option<SurfaceHandle>,
+                         shell_surface: XWaylandSurfaceHandle,
+                         event: &ResizeEvent) {
+        with_handles!([(compositor: {compositor})] => {
+            let server: &mut ::Server = compositor.into();
+            let ::Server { ref mut seat,
+                           ref mut views,
+                           ref mut cursor,
+                           .. } = *server;
+            let resizing_shell = shell_surface.into();
+
+            if let Some(view) = views.iter().find(|view| view.shell == resizing_shell).cloned() {
+                seat.begin_resize(cursor, view.clone(), views, event.edges())
+            }
+        }).unwrap();
+    }
+
+    fn on_map(&mut self,
+              compositor: CompositorHandle,
+              _: Option<SurfaceHandle>,
+              shell_surface: XWaylandSurfaceHandle) {
+        let (origin, override_redirect) = dehandle!(
+            @shell_surface = {&shell_surface};
+            (Origin::new(shell_surface.x() as i32, shell_surface.y() as i32),
+             shell_surface.override_redirect())
+        );
+
+        dehandle!(
+            @compositor = {&compositor};
+            let server: &mut ::Server = compositor.into();
+            let ::Server { ref mut seat,
+                           ref mut views,
+                           ref cursor,
+                           ref mut xcursor_manager,
+                           ref layer_surfaces,
+                           ref mut window_management,
+                           .. } = *server;
+            let view = Rc::new(::View::new(::Shell::XWayland(shell_surface.into())));
+            view.origin.set(origin);
+            // Override redirect windows are menus, tooltips and the like.
+            // They are drawn on top but are not managed or focused.
+            if override_redirect {
+                views.insert(0, view);
+            } else {
+                views.push(view.clone());
+                seat.focus_view(view.clone(), views);
+                window_management.new_window(&view, seat.focused.as_ref());
+            }
+            @cursor = {cursor};
+            seat.update_cursor_position(cursor, xcursor_manager, views, layer_surfaces, None)
+        );
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
+    }
+
+    fn on_unmap(&mut self,
+                compositor: CompositorHandle,
+                _: Option<SurfaceHandle>,
+                shell_surface: XWaylandSurfaceHandle) {
+        dehandle!(
+            @compositor = {&compositor};
+            let server: &mut ::Server = compositor.into();
+            let ::Server { ref mut seat,
+                           ref mut views,
+                           ref cursor,
+                           ref mut xcursor_manager,
+                           ref layer_surfaces,
+                           ref mut window_management,
+                           .. } = *server;
+            let destroyed_shell: ::Shell = shell_surface.into();
+            let was_focused = seat.focused.as_ref()
+                                  .map(|view| view.shell == destroyed_shell)
+                                  .unwrap_or(false);
+            if let Some(view) = views.iter().find(|view| view.shell == destroyed_shell).cloned() {
+                window_management.close_window(&view);
+            }
+            views.retain(|view| view.shell != destroyed_shell);
+
+            if was_focused {
+                seat.focused = None;
+                if let Some(view) = ::Seat::next_focus(views) {
+                    seat.focus_view(view, views);
+                } else {
+                    seat.clear_focus();
+                }
+            }
+            @cursor = {cursor};
+            seat.update_cursor_position(cursor, xcursor_manager, views, layer_surfaces, None)
+        );
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
+    }
+
+    fn destroyed(&mut self, compositor: CompositorHandle, shell_surface: XWaylandSurfaceHandle) {
+        let surface = shell_surface.into();
+        dehandle!(
+            @compositor = {&compositor};
+            let server: &mut ::Server = compositor.into();
+            let ::Server { ref mut views, .. } = *server;
+            if let Some(index) = views.iter().position(|view| view.shell == surface) {
+                views.remove(index);
+            }
+        );
+        with_handles!([(compositor: {compositor})] => {
+            ::damage_changes(compositor.into());
+        }).unwrap();
     }
 }